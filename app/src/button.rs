@@ -1,14 +1,11 @@
-extern crate rustpi_io;
-
-use rustpi_io::gpio::*;
-use chrono::Utc;
-
 use std::error::Error;
 use std::sync::mpsc::Sender;
 use std::thread::{JoinHandle, spawn, sleep};
 use std::time::Duration;
 
+use crate::clock::SharedClock;
 use crate::event::{Event, button::ButtonEvent};
+use crate::hardware::{DigitalInput, Hardware};
 use crate::settings::ButtonSettings;
 
 pub struct Buttons {
@@ -25,19 +22,18 @@ impl Drop for Buttons {
 
 struct Button {
 	name: String,
-	gpio: GPIO
+	input: Box<dyn DigitalInput>
 }
 
 impl Button {
-	fn new(settings: &ButtonSettings) -> Result<Self, Box<dyn Error>> {
-		let gpio = GPIO::new(settings.gpio, GPIOMode::Read)?;
-		Ok(Button { name: settings.name.clone(), gpio })
+	fn new(settings: &ButtonSettings, hardware: &dyn Hardware) -> Result<Self, Box<dyn Error>> {
+		let input = hardware.digital_input(settings.gpio)?;
+		Ok(Button { name: settings.name.clone(), input })
 	}
 
 	fn read(&self) -> bool {
-		match self.gpio.value() {
-			Ok(GPIOData::Low) => false,
-			Ok(GPIOData::High) => true,
+		match self.input.read() {
+			Ok(value) => value,
 			Err(e) => {
 				error!("button error {}", e);
 				false
@@ -50,7 +46,7 @@ fn read_all(buttons: &Vec<Button>) -> Vec<(&Button, bool)> {
 	buttons.iter().map(|b| (b, b.read())).collect()
 }
 
-fn main(buttons: Vec<Button>, channel: Sender<Event>) {
+fn main(buttons: Vec<Button>, clock: SharedClock, channel: Sender<Event>) {
 	info!("Started polling {} button(s)", buttons.len());
 
 	let mut prev_values = read_all(&buttons);
@@ -61,7 +57,7 @@ fn main(buttons: Vec<Button>, channel: Sender<Event>) {
 				.zip(curr_values.iter())
 				.filter(|(prev, curr)| prev.1 != curr.1)
 				.map(|(_, curr)| curr) {
-			send_event(change, &channel);
+			send_event(change, &clock, &channel);
 		}
 
 		prev_values = curr_values;
@@ -69,9 +65,9 @@ fn main(buttons: Vec<Button>, channel: Sender<Event>) {
 	}
 }
 
-fn send_event(button: &(&Button, bool), channel: &Sender<Event>) {
+fn send_event(button: &(&Button, bool), clock: &SharedClock, channel: &Sender<Event>) {
 	let event = ButtonEvent {
-		time: Utc::now(),
+		time: clock.now(),
 		name: button.0.name.clone(),
 		state: !button.1 // default is active high
 	};
@@ -83,11 +79,11 @@ fn send_event(button: &(&Button, bool), channel: &Sender<Event>) {
 }
 
 impl Buttons {
-	pub fn new(settings: &Vec<ButtonSettings>, channel: Sender<Event>) -> Result<Self, Box<dyn Error>> {
+	pub fn new(settings: &Vec<ButtonSettings>, hardware: &dyn Hardware, clock: SharedClock, channel: Sender<Event>) -> Result<Self, Box<dyn Error>> {
 		let buttons = settings.iter()
-			.map(|b| Button::new(b).unwrap())
+			.map(|b| Button::new(b, hardware).unwrap())
 			.collect();
-		let thread = spawn(move || { main(buttons, channel) });
+		let thread = spawn(move || { main(buttons, clock, channel) });
 		Ok(Buttons { 
 			thread: Some(thread)
 		})
@@ -0,0 +1,195 @@
+use chrono::{DateTime, Duration, Utc};
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::sleep;
+use std::time::Instant;
+
+use crate::settings::ClockSettings;
+
+pub trait Clock: Send + Sync {
+	fn now(&self) -> DateTime<Utc>;
+
+	fn sleep_until(&self, time: DateTime<Utc>);
+
+	// How long to wait in real time for the clock to reach `time`
+	fn real_time_until(&self, time: DateTime<Utc>) -> std::time::Duration;
+
+	fn sleep(&self, duration: std::time::Duration) {
+		match Duration::from_std(duration) {
+			Ok(d) => self.sleep_until(self.now() + d),
+			Err(e) => error!("invalid sleep duration {:?}: {}", duration, e)
+		}
+	}
+}
+
+pub type SharedClock = Arc<dyn Clock>;
+
+pub fn from_settings(settings: &ClockSettings) -> SharedClock {
+	if settings.speed > 1 {
+		info!("Running clock at {}x real time", settings.speed);
+		Arc::new(AcceleratedClock::new(Utc::now(), settings.speed))
+	} else {
+		Arc::new(RealClock)
+	}
+}
+
+pub struct RealClock;
+
+impl Clock for RealClock {
+	fn now(&self) -> DateTime<Utc> {
+		Utc::now()
+	}
+
+	fn sleep_until(&self, time: DateTime<Utc>) {
+		sleep(self.real_time_until(time));
+	}
+
+	fn real_time_until(&self, time: DateTime<Utc>) -> std::time::Duration {
+		(time - Utc::now()).to_std().unwrap_or_default()
+	}
+}
+
+// Wall-clock driven but running a fixed multiple faster than real time, for
+// watching the simulation play out over days.
+pub struct AcceleratedClock {
+	start: DateTime<Utc>,
+	started: Instant,
+	speed: u32
+}
+
+impl AcceleratedClock {
+	pub fn new(start: DateTime<Utc>, speed: u32) -> Self {
+		AcceleratedClock { start, started: Instant::now(), speed: speed.max(1) }
+	}
+}
+
+impl Clock for AcceleratedClock {
+	fn now(&self) -> DateTime<Utc> {
+		let elapsed = self.started.elapsed() * self.speed;
+		self.start + Duration::from_std(elapsed).unwrap_or_else(|_| Duration::zero())
+	}
+
+	fn sleep_until(&self, time: DateTime<Utc>) {
+		sleep(self.real_time_until(time));
+	}
+
+	fn real_time_until(&self, time: DateTime<Utc>) -> std::time::Duration {
+		(time - self.now()).to_std().unwrap_or_default() / self.speed
+	}
+}
+
+struct VirtualState {
+	now: DateTime<Utc>,
+	sleepers: Vec<DateTime<Utc>>
+}
+
+// Time only moves when advanced explicitly. Sleeping threads block until the
+// clock passes their wake-up time, so tests can step through a day at will.
+pub struct VirtualClock {
+	state: Mutex<VirtualState>,
+	changed: Condvar
+}
+
+impl VirtualClock {
+	pub fn new(now: DateTime<Utc>) -> Self {
+		VirtualClock {
+			state: Mutex::new(VirtualState { now, sleepers: vec![] }),
+			changed: Condvar::new()
+		}
+	}
+
+	pub fn advance(&self, duration: Duration) {
+		let mut state = self.state.lock().unwrap();
+		state.now += duration;
+		self.changed.notify_all();
+	}
+
+	pub fn advance_to(&self, time: DateTime<Utc>) {
+		let mut state = self.state.lock().unwrap();
+		if time > state.now {
+			state.now = time;
+			self.changed.notify_all();
+		}
+	}
+
+	// Blocks until at least `count` threads are asleep waiting for a time the
+	// clock has not yet reached, i.e. they have caught up with any advance
+	pub fn wait_for_sleepers(&self, count: usize) {
+		let mut state = self.state.lock().unwrap();
+		while state.sleepers.iter().filter(|t| **t > state.now).count() < count {
+			state = self.changed.wait(state).unwrap();
+		}
+	}
+}
+
+impl Clock for VirtualClock {
+	fn now(&self) -> DateTime<Utc> {
+		self.state.lock().unwrap().now
+	}
+
+	fn sleep_until(&self, time: DateTime<Utc>) {
+		let mut state = self.state.lock().unwrap();
+		if state.now >= time {
+			return;
+		}
+		state.sleepers.push(time);
+		self.changed.notify_all();
+		while state.now < time {
+			state = self.changed.wait(state).unwrap();
+		}
+		if let Some(index) = state.sleepers.iter().position(|t| *t == time) {
+			state.sleepers.remove(index);
+		}
+	}
+
+	// Nothing short of an advance moves the clock, so waiters poll
+	fn real_time_until(&self, time: DateTime<Utc>) -> std::time::Duration {
+		if self.now() >= time {
+			std::time::Duration::ZERO
+		} else {
+			std::time::Duration::from_millis(10)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use chrono::TimeZone;
+	use std::thread::spawn;
+
+	#[test]
+	fn virtual_clock_only_moves_when_advanced() {
+		let start = Utc.with_ymd_and_hms(2020, 6, 1, 0, 0, 0).unwrap();
+		let clock = VirtualClock::new(start);
+		assert_eq!(start, clock.now());
+		clock.advance(Duration::hours(3));
+		assert_eq!(start + Duration::hours(3), clock.now());
+		clock.advance_to(start);
+		assert_eq!(start + Duration::hours(3), clock.now());
+	}
+
+	#[test]
+	fn virtual_sleep_wakes_when_clock_passes_deadline() {
+		let start = Utc.with_ymd_and_hms(2020, 6, 1, 0, 0, 0).unwrap();
+		let clock = Arc::new(VirtualClock::new(start));
+		let sleeper = clock.clone();
+		let thread = spawn(move || {
+			sleeper.sleep(std::time::Duration::from_secs(3600));
+			sleeper.now()
+		});
+		clock.wait_for_sleepers(1);
+		clock.advance(Duration::minutes(30));
+		clock.advance(Duration::minutes(45));
+		assert_eq!(start + Duration::minutes(75), thread.join().unwrap());
+	}
+
+	#[test]
+	fn accelerated_clock_runs_faster_than_real_time() {
+		let start = Utc.with_ymd_and_hms(2020, 6, 1, 0, 0, 0).unwrap();
+		let clock = AcceleratedClock::new(start, 3600);
+		clock.sleep(std::time::Duration::from_secs(60));
+		assert!(clock.now() >= start + Duration::minutes(1));
+		assert!(clock.now() < start + Duration::hours(1));
+	}
+}
@@ -0,0 +1,59 @@
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError, sync_channel};
+
+use crate::event::Event;
+
+// Events a subscriber hasn't taken yet before further ones are dropped
+const SUBSCRIBER_QUEUE_SIZE: usize = 100;
+
+// Passes every event the controller handles on to whoever is watching. A
+// subscriber that falls behind misses events rather than holding up the
+// controller, and one that has gone away is forgotten on the next event.
+#[derive(Clone, Default)]
+pub struct Broadcast {
+	subscribers: Arc<Mutex<Vec<SyncSender<Event>>>>
+}
+
+impl Broadcast {
+	pub fn subscribe(&self) -> Receiver<Event> {
+		let (tx, rx) = sync_channel(SUBSCRIBER_QUEUE_SIZE);
+		self.subscribers.lock().unwrap().push(tx);
+		rx
+	}
+
+	pub fn send(&self, event: &Event) {
+		self.subscribers.lock().unwrap().retain(|tx| match tx.try_send(event.clone()) {
+			Ok(()) => true,
+			Err(TrySendError::Full(_)) => {
+				debug!("subscriber is behind; dropped {:?}", event);
+				true
+			}
+			Err(TrySendError::Disconnected(_)) => false
+		});
+	}
+
+	pub fn subscribers(&self) -> usize {
+		self.subscribers.lock().unwrap().len()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn events_reach_each_subscriber_until_it_goes_away() {
+		let broadcast = Broadcast::default();
+		let first = broadcast.subscribe();
+		let second = broadcast.subscribe();
+
+		broadcast.send(&Event::IrrigateEvent("beds".to_string()));
+		assert!(matches!(first.try_recv(), Ok(Event::IrrigateEvent(name)) if name == "beds"));
+		assert!(matches!(second.try_recv(), Ok(Event::IrrigateEvent(name)) if name == "beds"));
+
+		drop(second);
+		broadcast.send(&Event::IrrigateEvent("pots".to_string()));
+		assert_eq!(broadcast.subscribers(), 1);
+		assert!(matches!(first.try_recv(), Ok(Event::IrrigateEvent(name)) if name == "pots"));
+	}
+}
@@ -0,0 +1,210 @@
+use chrono::{DateTime, Utc};
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::event::moisture::{Measurement, MoistureEvent};
+use crate::event::weather::WeatherEvent;
+
+// Sensors report every minute or so; a day of readings each is plenty for
+// threshold decisions without letting memory grow on the Pi.
+pub const DEFAULT_CAPACITY: usize = 1440;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Irrigation {
+	pub time: DateTime<Utc>,
+	pub duration: Duration
+}
+
+// Bounded in-process record of recent events, so that watering decisions do
+// not depend on the database being reachable. Each series is a ring buffer
+// in time order; the oldest entries are dropped once it is full.
+#[derive(Debug)]
+pub struct History {
+	capacity: usize,
+	since: Option<DateTime<Utc>>,
+	moisture: HashMap<String, VecDeque<(DateTime<Utc>, Measurement)>>,
+	irrigation: HashMap<String, VecDeque<Irrigation>>,
+	weather: VecDeque<WeatherEvent>
+}
+
+// The controller records into the history and the HTTP API reads from it
+pub type SharedHistory = Arc<RwLock<History>>;
+
+fn push<T>(series: &mut VecDeque<T>, capacity: usize, item: T) {
+	while series.len() >= capacity {
+		series.pop_front();
+	}
+	series.push_back(item);
+}
+
+impl History {
+	pub fn new(capacity: usize) -> Self {
+		History {
+			capacity: capacity.max(1),
+			since: None,
+			moisture: HashMap::new(),
+			irrigation: HashMap::new(),
+			weather: VecDeque::new()
+		}
+	}
+
+	pub fn shared(capacity: usize) -> SharedHistory {
+		Arc::new(RwLock::new(History::new(capacity)))
+	}
+
+	// Events before `time` were never recorded, say because the journal was
+	// replayed from then on
+	pub fn cover_from(&mut self, time: DateTime<Utc>) {
+		self.since = Some(time);
+	}
+
+	// From when a series holds every event, if known. A full series may have
+	// dropped older entries, so it only covers from its oldest on.
+	fn covered_from<T>(&self, series: Option<&VecDeque<T>>, time: impl Fn(&T) -> DateTime<Utc>) -> Option<DateTime<Utc>> {
+		let oldest = series
+			.filter(|series| series.len() >= self.capacity)
+			.and_then(|series| series.front())
+			.map(time);
+		self.since.max(oldest)
+	}
+
+	pub fn moisture_covered_from(&self, sensor: &str) -> Option<DateTime<Utc>> {
+		self.covered_from(self.moisture.get(sensor), |(time, _)| *time)
+	}
+
+	pub fn irrigation_covered_from(&self, zone: &str) -> Option<DateTime<Utc>> {
+		self.covered_from(self.irrigation.get(zone), |i| i.time)
+	}
+
+	pub fn weather_covered_from(&self) -> Option<DateTime<Utc>> {
+		self.covered_from(Some(&self.weather), |w| w.time)
+	}
+
+	pub fn record_moisture(&mut self, event: &MoistureEvent) {
+		let series = self.moisture.entry(event.name.clone()).or_default();
+		push(series, self.capacity, (event.time, event.value));
+	}
+
+	pub fn record_irrigation(&mut self, zone: &str, time: DateTime<Utc>, duration: Duration) {
+		let series = self.irrigation.entry(zone.to_string()).or_default();
+		push(series, self.capacity, Irrigation { time, duration });
+	}
+
+	pub fn record_weather(&mut self, event: &WeatherEvent) {
+		push(&mut self.weather, self.capacity, event.clone());
+	}
+
+	fn moisture_since<'a>(&'a self, sensor: &str, since: DateTime<Utc>) -> impl Iterator<Item=Measurement> + 'a {
+		self.moisture.get(sensor)
+			.into_iter()
+			.flat_map(move |series| series.iter().rev().take_while(move |(time, _)| *time >= since))
+			.map(|(_, value)| *value)
+	}
+
+	pub fn min_moisture(&self, sensor: &str, since: DateTime<Utc>) -> Option<Measurement> {
+		self.moisture_since(sensor, since).min()
+	}
+
+	pub fn mean_moisture(&self, sensor: &str, since: DateTime<Utc>) -> Option<f64> {
+		let (sum, count) = self.moisture_since(sensor, since)
+			.fold((0.0, 0), |(sum, count), value| (sum + value as f64, count + 1));
+		if count == 0 {
+			None
+		} else {
+			Some(sum / count as f64)
+		}
+	}
+
+	pub fn latest_moisture(&self, sensor: &str) -> Option<(DateTime<Utc>, Measurement)> {
+		self.moisture.get(sensor).and_then(|series| series.back().cloned())
+	}
+
+	pub fn moisture(&self, sensor: &str, since: DateTime<Utc>) -> Vec<(DateTime<Utc>, Measurement)> {
+		self.moisture.get(sensor)
+			.map(|series| series.iter().filter(|(time, _)| *time >= since).cloned().collect())
+			.unwrap_or_default()
+	}
+
+	pub fn weather(&self, since: DateTime<Utc>) -> Vec<WeatherEvent> {
+		self.weather.iter().filter(|w| w.time >= since).cloned().collect()
+	}
+
+	pub fn irrigations(&self, zone: &str, since: DateTime<Utc>) -> Vec<Irrigation> {
+		self.irrigation.get(zone)
+			.map(|series| series.iter().filter(|i| i.time >= since).cloned().collect())
+			.unwrap_or_default()
+	}
+
+	pub fn last_irrigation(&self, zone: &str) -> Option<Irrigation> {
+		self.irrigation.get(zone).and_then(|series| series.back().cloned())
+	}
+}
+
+impl Default for History {
+	fn default() -> Self {
+		History::new(DEFAULT_CAPACITY)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use chrono::{Duration as ChronoDuration, TimeZone};
+
+	fn moisture(time: DateTime<Utc>, name: &str, value: Measurement) -> MoistureEvent {
+		MoistureEvent { time, name: name.to_string(), value }
+	}
+
+	#[test]
+	fn window_queries_ignore_older_readings() {
+		let start = Utc.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap();
+		let mut history = History::default();
+		history.record_moisture(&moisture(start, "tomato", 400));
+		history.record_moisture(&moisture(start + ChronoDuration::minutes(30), "tomato", 600));
+		history.record_moisture(&moisture(start + ChronoDuration::minutes(60), "tomato", 700));
+		history.record_moisture(&moisture(start + ChronoDuration::minutes(60), "potato", 100));
+
+		let since = start + ChronoDuration::minutes(30);
+		assert_eq!(history.min_moisture("tomato", since), Some(600));
+		assert_eq!(history.mean_moisture("tomato", since), Some(650.0));
+		assert_eq!(history.latest_moisture("tomato"), Some((start + ChronoDuration::minutes(60), 700)));
+		assert_eq!(history.min_moisture("tomato", start + ChronoDuration::hours(2)), None);
+		assert_eq!(history.min_moisture("carrot", start), None);
+	}
+
+	#[test]
+	fn oldest_entries_are_dropped_at_capacity() {
+		let start = Utc.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap();
+		let mut history = History::new(2);
+		for (minutes, value) in [(0, 100), (1, 200), (2, 300)].iter() {
+			history.record_moisture(&moisture(start + ChronoDuration::minutes(*minutes), "tomato", *value));
+		}
+		assert_eq!(history.min_moisture("tomato", start), Some(200));
+
+		history.record_irrigation("beds", start, Duration::from_secs(60));
+		history.record_irrigation("beds", start + ChronoDuration::hours(1), Duration::from_secs(90));
+		history.record_irrigation("beds", start + ChronoDuration::hours(2), Duration::from_secs(120));
+		assert_eq!(history.irrigations("beds", start).len(), 2);
+		assert_eq!(history.last_irrigation("beds").map(|i| i.duration), Some(Duration::from_secs(120)));
+	}
+
+	#[test]
+	fn coverage_starts_at_the_oldest_entry_of_a_full_series() {
+		let start = Utc.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap();
+		let mut history = History::new(2);
+		assert_eq!(history.moisture_covered_from("tomato"), None);
+
+		history.cover_from(start - ChronoDuration::hours(24));
+		history.record_moisture(&moisture(start, "tomato", 100));
+		assert_eq!(history.moisture_covered_from("tomato"), Some(start - ChronoDuration::hours(24)));
+
+		history.record_moisture(&moisture(start + ChronoDuration::minutes(1), "tomato", 200));
+		history.record_moisture(&moisture(start + ChronoDuration::minutes(2), "tomato", 300));
+		assert_eq!(history.moisture_covered_from("tomato"), Some(start + ChronoDuration::minutes(1)));
+		assert_eq!(history.moisture_covered_from("potato"), Some(start - ChronoDuration::hours(24)));
+		assert_eq!(history.weather_covered_from(), Some(start - ChronoDuration::hours(24)));
+		assert_eq!(history.irrigation_covered_from("beds"), Some(start - ChronoDuration::hours(24)));
+	}
+}
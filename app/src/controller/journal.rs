@@ -0,0 +1,279 @@
+use chrono::{DateTime, TimeZone, Utc};
+
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::event::Event;
+use crate::event::irrigate::IrrigatedEvent;
+use crate::event::moisture::MoistureEvent;
+use crate::settings::HistorySettings;
+
+const FILE_NAME: &str = "history.log";
+
+// Append-only log of the events the controller's history is built from, one
+// tab-separated line per event, with tabs and line breaks in names escaped.
+// The live file is rotated to `history.log.1`, `history.log.2` and so on when
+// it reaches the size limit, and the oldest file is deleted once there are
+// `max_files` of them.
+pub struct Journal {
+	dir: PathBuf,
+	max_bytes: u64,
+	max_files: usize,
+	file: File,
+	size: u64
+}
+
+fn escape(name: &str) -> String {
+	let mut escaped = String::with_capacity(name.len());
+	for c in name.chars() {
+		match c {
+			'\\' => escaped.push_str("\\\\"),
+			'\t' => escaped.push_str("\\t"),
+			'\n' => escaped.push_str("\\n"),
+			'\r' => escaped.push_str("\\r"),
+			c => escaped.push(c)
+		}
+	}
+	escaped
+}
+
+fn unescape(field: &str) -> Option<String> {
+	let mut name = String::with_capacity(field.len());
+	let mut chars = field.chars();
+	while let Some(c) = chars.next() {
+		if c == '\\' {
+			name.push(match chars.next()? {
+				'\\' => '\\',
+				't' => '\t',
+				'n' => '\n',
+				'r' => '\r',
+				_ => return None
+			});
+		} else {
+			name.push(c);
+		}
+	}
+	Some(name)
+}
+
+fn encode(event: &Event) -> Option<String> {
+	match event {
+		Event::MoistureEvent(m) => Some(format!("M\t{}\t{}\t{}\n", m.time.timestamp(), escape(&m.name), m.value)),
+		Event::IrrigatedEvent(i) => Some(format!("I\t{}\t{}\t{}\n", i.time.timestamp(), escape(&i.name), i.seconds)),
+		_ => None
+	}
+}
+
+fn decode(line: &str) -> Option<Event> {
+	let fields: Vec<&str> = line.split('\t').collect();
+	if fields.len() != 4 {
+		return None;
+	}
+	let time = Utc.timestamp_opt(fields[1].parse().ok()?, 0).single()?;
+	let name = unescape(fields[2])?;
+	match fields[0] {
+		"M" => Some(Event::MoistureEvent(MoistureEvent { time, name, value: fields[3].parse().ok()? })),
+		"I" => Some(Event::IrrigatedEvent(IrrigatedEvent { time, name, seconds: fields[3].parse().ok()? })),
+		_ => None
+	}
+}
+
+fn event_time(event: &Event) -> Option<DateTime<Utc>> {
+	match event {
+		Event::MoistureEvent(m) => Some(m.time),
+		Event::IrrigatedEvent(i) => Some(i.time),
+		_ => None
+	}
+}
+
+fn open_append(path: &Path) -> std::io::Result<File> {
+	OpenOptions::new().create(true).append(true).open(path)
+}
+
+// A crash mid-write can leave a partial line, which must not run into the
+// next event appended
+fn ends_with_newline(path: &Path) -> std::io::Result<bool> {
+	let mut file = File::open(path)?;
+	file.seek(SeekFrom::End(-1))?;
+	let mut last = [0u8];
+	file.read_exact(&mut last)?;
+	Ok(last[0] == b'\n')
+}
+
+impl Journal {
+	pub fn open(settings: &HistorySettings) -> Result<Option<Self>, Box<dyn Error>> {
+		let dir = match &settings.path {
+			Some(path) => PathBuf::from(path),
+			None => return Ok(None)
+		};
+		fs::create_dir_all(&dir)?;
+		let path = dir.join(FILE_NAME);
+		let mut file = open_append(&path)?;
+		let mut size = file.metadata()?.len();
+		if size > 0 && !ends_with_newline(&path)? {
+			file.write_all(b"\n")?;
+			size += 1;
+		}
+		Ok(Some(Journal {
+			dir,
+			max_bytes: settings.max_file_kb.max(1) * 1024,
+			max_files: settings.max_files.max(1),
+			file,
+			size
+		}))
+	}
+
+	fn path(&self, index: usize) -> PathBuf {
+		if index == 0 {
+			self.dir.join(FILE_NAME)
+		} else {
+			self.dir.join(format!("{}.{}", FILE_NAME, index))
+		}
+	}
+
+	// The full file is synced before it is renamed so that a power cut
+	// can't leave rotated files with their last events missing
+	fn rotate(&mut self) -> std::io::Result<()> {
+		self.file.sync_all()?;
+		let oldest = self.path(self.max_files - 1);
+		if oldest.exists() {
+			fs::remove_file(oldest)?;
+		}
+		for index in (0..self.max_files - 1).rev() {
+			let from = self.path(index);
+			if from.exists() {
+				fs::rename(from, self.path(index + 1))?;
+			}
+		}
+		self.file = open_append(&self.path(0))?;
+		File::open(&self.dir)?.sync_all()?;
+		self.size = 0;
+		Ok(())
+	}
+
+	pub fn append(&mut self, event: &Event) -> std::io::Result<()> {
+		if let Some(line) = encode(event) {
+			if self.size > 0 && self.size + line.len() as u64 > self.max_bytes {
+				self.rotate()?;
+			}
+			self.file.write_all(line.as_bytes())?;
+			self.size += line.len() as u64;
+		}
+		Ok(())
+	}
+
+	// Journalled events at or after `since`, oldest first. Lines that cannot
+	// be decoded, such as one cut short by a crash, are skipped.
+	pub fn replay(&self, since: DateTime<Utc>) -> Vec<Event> {
+		let mut events = vec![];
+		for index in (0..self.max_files).rev() {
+			let file = match File::open(self.path(index)) {
+				Ok(file) => file,
+				Err(_) => continue
+			};
+			BufReader::new(file).lines()
+				.map_while(Result::ok)
+				.filter_map(|line| decode(&line))
+				.filter(|event| matches!(event_time(event), Some(time) if time >= since))
+				.for_each(|event| events.push(event));
+		}
+		events
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::testing::TempDir;
+	use chrono::Duration;
+
+	fn settings(dir: &Path, max_file_kb: u64) -> HistorySettings {
+		HistorySettings {
+			path: Some(dir.to_string_lossy().into_owned()),
+			max_file_kb,
+			max_files: 3,
+			..HistorySettings::default()
+		}
+	}
+
+	fn moisture(time: DateTime<Utc>, value: u16) -> Event {
+		Event::MoistureEvent(MoistureEvent { time, name: "tomato bed".to_string(), value })
+	}
+
+	fn values(events: &[Event]) -> Vec<u16> {
+		events.iter().filter_map(|e| match e {
+			Event::MoistureEvent(m) => Some(m.value),
+			_ => None
+		}).collect()
+	}
+
+	#[test]
+	fn replays_recent_events_after_reopening() {
+		let dir = TempDir::new("journal-replay");
+		let settings = settings(&dir, 256);
+		let start = Utc.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap();
+		{
+			let mut journal = Journal::open(&settings).unwrap().unwrap();
+			journal.append(&moisture(start, 400)).unwrap();
+			journal.append(&moisture(start + Duration::hours(2), 500)).unwrap();
+			journal.append(&Event::IrrigatedEvent(IrrigatedEvent {
+				time: start + Duration::hours(3), name: "valve".to_string(), seconds: 60
+			})).unwrap();
+			journal.append(&Event::IrrigateEvent("ignored".to_string())).unwrap();
+		}
+		let path = dir.join(FILE_NAME);
+		OpenOptions::new().append(true).open(&path).unwrap().write_all(b"M\t15910").unwrap();
+
+		let mut journal = Journal::open(&settings).unwrap().unwrap();
+		journal.append(&moisture(start + Duration::hours(4), 600)).unwrap();
+		let events = journal.replay(start + Duration::hours(1));
+		assert_eq!(events.len(), 3);
+		assert_eq!(values(&events), vec![500, 600]);
+	}
+
+	#[test]
+	fn rotation_keeps_a_bounded_number_of_files() {
+		let dir = TempDir::new("journal-rotate");
+		let settings = settings(&dir, 1);
+		let start = Utc.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap();
+		let mut journal = Journal::open(&settings).unwrap().unwrap();
+		for minute in 0..200 {
+			journal.append(&moisture(start + Duration::minutes(minute), minute as u16)).unwrap();
+		}
+		for index in 0..3 {
+			assert!(fs::metadata(journal.path(index)).unwrap().len() <= 1024);
+		}
+		assert!(!journal.path(3).exists());
+
+		let replayed = values(&journal.replay(start));
+		assert!(replayed.len() < 200);
+		assert_eq!(replayed.last(), Some(&199));
+		assert!(replayed.windows(2).all(|w| w[0] + 1 == w[1]));
+	}
+
+	#[test]
+	fn names_with_tabs_and_line_breaks_round_trip() {
+		let dir = TempDir::new("journal-escape");
+		let settings = settings(&dir, 256);
+		let time = Utc.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap();
+		let name = "bed\t1\nwest \\ \\t";
+		{
+			let mut journal = Journal::open(&settings).unwrap().unwrap();
+			journal.append(&Event::MoistureEvent(MoistureEvent { time, name: name.to_string(), value: 612 })).unwrap();
+			journal.append(&moisture(time, 613)).unwrap();
+		}
+		let journal = Journal::open(&settings).unwrap().unwrap();
+		let events = journal.replay(time);
+		assert!(matches!(&events[0], Event::MoistureEvent(m) if m.name == name && m.value == 612));
+		assert_eq!(values(&events), vec![612, 613]);
+		assert!(decode("M\t1591012800\tbed\\x\t612").is_none());
+	}
+
+	#[test]
+	fn no_journal_without_a_path() {
+		let settings = HistorySettings { path: None, ..HistorySettings::default() };
+		assert!(Journal::open(&settings).unwrap().is_none());
+	}
+}
@@ -1,17 +1,29 @@
+mod broadcast;
+mod history;
+mod journal;
 mod scheduler;
 
+use chrono::Duration as ChronoDuration;
+
 use std::sync::mpsc;
 use std::time::Duration;
 
 use crate::button::Buttons;
+use crate::clock::SharedClock;
 use crate::database::Database;
 use crate::event::Event;
 use crate::event::button::ButtonEvent;
+use crate::event::irrigate::IrrigatedEvent;
+use crate::event::moisture::Measurement;
 use crate::moisture::MoistureSensor;
 use crate::settings::controller::{ControllerSettings, Zone};
+use crate::sink::Sinks;
 use crate::valve::Valves;
 use crate::weather::WeatherSensor;
 
+pub use broadcast::Broadcast;
+pub use history::{History, SharedHistory};
+pub use journal::Journal;
 pub use scheduler::Scheduler;
 
 impl Zone {
@@ -22,8 +34,13 @@ impl Zone {
 
 pub struct Controller {
 	pub settings: ControllerSettings,
+	pub clock: SharedClock,
 	pub scheduler: Scheduler,
-	pub database: Database,
+	pub database: Option<Database>,
+	pub sinks: Sinks,
+	pub history: SharedHistory,
+	pub broadcast: Broadcast,
+	pub journal: Option<Journal>,
 	pub weather: Option<WeatherSensor>,
 	pub moisture: Option<MoistureSensor>,
 	pub buttons: Buttons,
@@ -38,12 +55,23 @@ impl Controller {
 
 			debug!("event {:?}", event);
 
-			self.database.store_event(&event);
+			self.sinks.store(&event);
+			self.broadcast.send(&event);
+
+			self.record_history(&event);
+			if let Some(journal) = self.journal.as_mut() {
+				if let Err(e) = journal.append(&event) {
+					warn!("failed to write history journal: {}", e);
+				}
+			}
 
 			match event {
 				Event::ButtonEvent(b) => self.button_event(&b),
 				Event::ConditionalIrrigateEvent(name) => self.conditionally_irrigate_zone_event(&name),
 				Event::IrrigateEvent(name) => self.irrigate_zone_event(&name),
+				Event::IrrigateForEvent(i) => self.irrigate_zone_for(&i.zone, Duration::from_secs(i.seconds as u64)),
+				Event::StopIrrigationEvent(name) => self.stop_zone(&name),
+				Event::StopAllIrrigationEvent => self.valves.stop_all(),
 				_ => {}
 			}
 		}
@@ -55,6 +83,32 @@ impl Controller {
 		}
 	}
 
+	pub fn replay_journal(&mut self, hours: u32) {
+		let now = self.clock.now();
+		let since = now - ChronoDuration::hours(hours as i64);
+		let events = self.journal.as_ref().map(|journal| journal.replay(since)).unwrap_or_default();
+		info!("Replaying {} events from the history journal", events.len());
+		self.history.write().unwrap().cover_from(if self.journal.is_some() { since } else { now });
+		events.iter().for_each(|event| self.record_history(event));
+	}
+
+	fn record_history(&mut self, event: &Event) {
+		let mut history = self.history.write().unwrap();
+		match event {
+			Event::MoistureEvent(m) => history.record_moisture(m),
+			Event::WeatherEvent(w) => history.record_weather(w),
+			Event::IrrigatedEvent(i) => self.record_irrigation(&mut history, i),
+			_ => {}
+		}
+	}
+
+	fn record_irrigation(&self, history: &mut History, event: &IrrigatedEvent) {
+		let duration = Duration::from_secs(event.seconds as u64);
+		for zone in self.settings.zones.iter().filter(|z| z.valve == event.name) {
+			history.record_irrigation(&zone.name, event.time, duration);
+		}
+	}
+
 	fn zone_by_name(&self, name: &str) -> Option<&Zone> {
 		self.settings.zones.iter().find(|z| z.name == name)
 	}
@@ -66,6 +120,20 @@ impl Controller {
 		}
 	}
 
+	pub fn irrigate_zone_for(&self, name: &str, duration: Duration) {
+		match self.zone_by_name(name) {
+			Some(zone) => self.valves.irrigate(&zone.valve, duration),
+			None => warn!("unknown zone for irrigation: {}", name)
+		}
+	}
+
+	pub fn stop_zone(&self, name: &str) {
+		match self.zone_by_name(name) {
+			Some(zone) => self.valves.stop(&zone.valve),
+			None => warn!("unknown zone to stop irrigating: {}", name)
+		}
+	}
+
 	fn conditionally_irrigate_zone_event(&self, name: &str) {
 		match self.zone_by_name(name) {
 			Some(zone) => self.irrigate_if_below_threshold(zone),
@@ -74,9 +142,9 @@ impl Controller {
 	}
 
 	fn irrigate_if_below_threshold(&self, zone: &Zone) {
-		let any_below_threshold =  zone.sensors.iter()
-			.map(|sensor| self.database.get_min_moisture_in_last_hour(sensor))
-			.any(|result| result.map(|m| m < zone.threshold).unwrap_or(false));
+		let any_below_threshold = zone.sensors.iter()
+			.filter_map(|sensor| self.min_moisture_in_last_hour(sensor))
+			.any(|m| m < zone.threshold);
 		if any_below_threshold {
 			debug!("zone {} below moisture threshold in past hour; starting irrigation", zone.name);
 			self.valves.irrigate(&zone.valve, zone.irrigate_duration());
@@ -84,4 +152,19 @@ impl Controller {
 			debug!("zone {} above moisture threshold in past hour; skipping irrigation", zone.name);
 		}
 	}
+
+	// Local history first, so decisions still work while the database is
+	// unreachable; the database covers readings from before a restart.
+	fn min_moisture_in_last_hour(&self, sensor: &str) -> Option<Measurement> {
+		let now = self.clock.now();
+		let min_moisture = self.history.read().unwrap().min_moisture(sensor, now - ChronoDuration::hours(1));
+		min_moisture
+			.or_else(|| match self.database.as_ref()?.get_min_moisture_in_last_hour(sensor, now) {
+				Ok(m) => Some(m),
+				Err(e) => {
+					warn!("no moisture history for {}: {}", sensor, e);
+					None
+				}
+			})
+	}
 }
@@ -4,8 +4,9 @@ use std::error::Error;
 use std::fmt;
 use std::str::FromStr;
 use std::sync::mpsc::Sender;
-use std::thread::{JoinHandle, sleep, spawn};
+use std::thread::{JoinHandle, spawn};
 
+use crate::clock::{Clock, SharedClock};
 use crate::event::Event;
 use crate::settings::controller::{Location, Zone};
 
@@ -86,12 +87,19 @@ impl ScheduledEvent {
 		}
 	}
 
-	fn times(&self, location: &Location, date: &Date<Utc>) -> Vec<DateTime<Utc>> {
+	fn times(&self, location: &Location, date: &NaiveDate) -> Vec<DateTime<Utc>> {
 		let (sunrise, sunset) = sunrise::sunrise_sunset(location.latitude, location.longitude, date.year(), date.month(), date.day());
 		let start = match self.time {
-			Time::Sunrise => Utc.timestamp(sunrise, 0),
-			Time::Sunset => Utc.timestamp(sunset, 0),
-			Time::Fixed { hour, minute } => date.and_hms(hour, minute, 0)
+			Time::Sunrise => Utc.timestamp_opt(sunrise, 0).single(),
+			Time::Sunset => Utc.timestamp_opt(sunset, 0).single(),
+			Time::Fixed { hour, minute } => date.and_hms_opt(hour, minute, 0).map(|t| Utc.from_utc_datetime(&t))
+		};
+		let start = match start {
+			Some(start) => start,
+			None => {
+				warn!("no valid time for {:?} on {}", self.time, date);
+				return vec![];
+			}
 		};
 		let mut dur: Duration = Duration::seconds(0);
 		let mut times = vec![];
@@ -106,6 +114,9 @@ impl ScheduledEvent {
 	}
 }
 
+// Longest the clock may jump forward before missed checks are skipped
+const MAX_CATCH_UP_DAYS: i64 = 1;
+
 #[derive(Debug, Eq, PartialEq)]
 struct Pending<'a> {
 	name: &'a str,
@@ -142,32 +153,56 @@ impl Schedule {
 		Ok(events)
 	}
 
-	fn all_pending(&self, now: DateTime<Utc>) -> Vec<Pending> {
+	// Checks from `from` up to but not including `until`. Checks started on
+	// earlier days can run on past midnight, and sunrise in the far east can
+	// fall on the day before in UTC, so the neighbouring days are looked at too.
+	fn all_pending(&self, from: DateTime<Utc>, until: DateTime<Utc>) -> Vec<Pending> {
+		let days_back = self.events.iter()
+			.map(|event| event.duration.num_days() + 1)
+			.max()
+			.unwrap_or(0);
 		let mut events: Vec<Pending> = vec![];
 		for event in &self.events {
-			let times = event.times(&self.location, &now.date());
-			times.iter()
-				.filter(|t| *t > &now)
-				.for_each(|t| events.push(Pending::new(&event.name, t)));
+			for days in -days_back..=1 {
+				let date = from.date_naive() + Duration::days(days);
+				event.times(&self.location, &date).iter()
+					.filter(|t| **t >= from && **t < until)
+					.for_each(|t| events.push(Pending::new(&event.name, t)));
+			}
 		}
 		events.sort_by(|x, y| x.time.cmp(&y.time) );
 		events
 	}
 
-	fn main(&self, tx: Sender<Event>) {
+	// Works through a day at a time from where the last day ended rather than
+	// from the current time, so a check at midnight or one the clock jumps past
+	// still runs. A jump of more than a day, such as the clock being set by NTP
+	// after booting without one, starts again from now rather than catching up.
+	fn main(&self, clock: &dyn Clock, tx: Sender<Event>) {
+		let mut since = clock.now();
 		loop {
-			let events = self.all_pending(Utc::now());
-			if events.is_empty() {
-				sleep(std::time::Duration::from_secs(600));
-			} else {
-				debug!("scheduled {:?}", events);
-				for event in events {
-					while Utc::now() < event.time {
-						sleep(std::time::Duration::from_secs(1));
-					}
-					tx.send(Event::ConditionalIrrigateEvent(event.name.to_string()))
-						.expect("scheduler send error");
+			let now = clock.now();
+			if now - since > Duration::days(MAX_CATCH_UP_DAYS) {
+				warn!("clock jumped from {} to {}; skipping the checks in between", since, now);
+				since = now;
+			}
+			let tomorrow = since.date_naive().succ_opt().expect("scheduler ran out of dates");
+			let midnight = Utc.from_utc_datetime(&tomorrow.and_hms_opt(0, 0, 0).unwrap());
+			let events = self.all_pending(since, midnight);
+			debug!("scheduled {:?}", events);
+			let mut jumped = false;
+			for event in events {
+				clock.sleep_until(event.time);
+				if clock.now() - event.time > Duration::days(MAX_CATCH_UP_DAYS) {
+					jumped = true;
+					break;
 				}
+				tx.send(Event::ConditionalIrrigateEvent(event.name.to_string()))
+					.expect("scheduler send error");
+			}
+			if !jumped {
+				since = midnight;
+				clock.sleep_until(since);
 			}
 		}
 	}
@@ -186,9 +221,9 @@ impl Drop for Scheduler {
 }
 
 impl Scheduler {
-	pub fn new(location: &Location, zones: &Vec<Zone>, tx: Sender<Event>) -> Result<Self, ParseError> {
+	pub fn new(location: &Location, zones: &Vec<Zone>, clock: SharedClock, tx: Sender<Event>) -> Result<Self, ParseError> {
 		let schedule = Schedule::new(&Schedule::from_zones(zones)?, &location);
-		let thread = spawn(move || schedule.main(tx));
+		let thread = spawn(move || schedule.main(clock.as_ref(), tx));
 		Ok(Scheduler { 
 			thread: Some(thread)
 		})
@@ -198,6 +233,16 @@ impl Scheduler {
 #[cfg(test)]
 mod test {
 	use super::*;
+	use crate::clock::VirtualClock;
+	use std::sync::{Arc, mpsc};
+
+	fn date() -> NaiveDate {
+		NaiveDate::from_ymd_opt(2019, 5, 19).unwrap()
+	}
+
+	fn at(day: u32, hour: u32, minute: u32, second: u32) -> DateTime<Utc> {
+		Utc.with_ymd_and_hms(2019, 5, day, hour, minute, second).unwrap()
+	}
 
 	#[test]
 	fn test_parse_time() {
@@ -212,30 +257,27 @@ mod test {
 	#[test]
 	fn times_for_schedule_with_fixed_times() {
 		let location = Location { longitude: 0.0, latitude: 0.0 };
-		let date = Utc.ymd(2019, 5, 19);
 		assert_eq!(
-			vec![date.and_hms(6, 0, 0), date.and_hms(6, 30, 0), date.and_hms(7, 0, 0), date.and_hms(7, 30, 0)],
-			ScheduledEvent::new("test", "06:00", "30", "120").unwrap().times(&location, &date)
+			vec![at(19, 6, 0, 0), at(19, 6, 30, 0), at(19, 7, 0, 0), at(19, 7, 30, 0)],
+			ScheduledEvent::new("test", "06:00", "30", "120").unwrap().times(&location, &date())
 		);
 	}
 
 	#[test]
 	fn times_for_schedule_at_sunrise() {
 		let location = Location { longitude: 3.297, latitude: 55.9 };
-		let date = Utc.ymd(2019, 5, 19);
 		assert_eq!(
-			vec![date.and_hms(3, 28, 9), date.and_hms(3, 43, 9), date.and_hms(3, 58, 9), date.and_hms(4, 13, 9)],
-			ScheduledEvent::new("test", "sunrise", "15", "60").unwrap().times(&location, &date)
+			vec![at(19, 3, 28, 9), at(19, 3, 43, 9), at(19, 3, 58, 9), at(19, 4, 13, 9)],
+			ScheduledEvent::new("test", "sunrise", "15", "60").unwrap().times(&location, &date())
 		)
 	}
 
 	#[test]
 	fn times_for_schedule_at_sunset() {
 		let location = Location { longitude: 3.297, latitude: 55.9 };
-		let date = Utc.ymd(2019, 5, 19);
 		assert_eq!(
-			vec![date.and_hms(19, 58, 38), date.and_hms(20, 13, 38), date.and_hms(20, 28, 38), date.and_hms(20, 43, 38)],
-			ScheduledEvent::new("test", "sunset", "15", "60").unwrap().times(&location, &date)
+			vec![at(19, 19, 58, 38), at(19, 20, 13, 38), at(19, 20, 28, 38), at(19, 20, 43, 38)],
+			ScheduledEvent::new("test", "sunset", "15", "60").unwrap().times(&location, &date())
 		)		
 	}
 
@@ -249,17 +291,15 @@ mod test {
 	#[test]
 	fn times_for_non_repeating_event() {
 		let location = Location { longitude: 0.0, latitude: 0.0 };
-		let date = Utc.ymd(2019, 5, 19);
 		assert_eq!(
-			vec![date.and_hms(6, 0, 0)],
-			ScheduledEvent::new("test", "06:00", "1", "1").unwrap().times(&location, &date)
+			vec![at(19, 6, 0, 0)],
+			ScheduledEvent::new("test", "06:00", "1", "1").unwrap().times(&location, &date())
 		);
 	}
 
 	#[test]
 	fn schedule_merges_events() {
 		let location = Location { longitude: 3.297, latitude: 55.9 };
-		let date = Utc.ymd(2019, 5, 19);
 		let events = vec![
 			ScheduledEvent::new("foo", "08:00", "30", "65").unwrap(),
 			ScheduledEvent::new("bar", "08:12", "20", "65").unwrap()
@@ -267,22 +307,21 @@ mod test {
 		let schedule = Schedule::new(&events, &location);
 		assert_eq!(
 			vec![
-				Pending::new("foo", &date.and_hms(8, 0, 0)),
-				Pending::new("bar", &date.and_hms(8, 12, 0)),
-				Pending::new("foo", &date.and_hms(8, 30, 0)),
-				Pending::new("bar", &date.and_hms(8, 32, 0)),
-				Pending::new("bar", &date.and_hms(8, 52, 0)),
-				Pending::new("foo", &date.and_hms(9, 0, 0)),
-				Pending::new("bar", &date.and_hms(9, 12, 0))
+				Pending::new("foo", &at(19, 8, 0, 0)),
+				Pending::new("bar", &at(19, 8, 12, 0)),
+				Pending::new("foo", &at(19, 8, 30, 0)),
+				Pending::new("bar", &at(19, 8, 32, 0)),
+				Pending::new("bar", &at(19, 8, 52, 0)),
+				Pending::new("foo", &at(19, 9, 0, 0)),
+				Pending::new("bar", &at(19, 9, 12, 0))
 			],
-			schedule.all_pending(date.and_hms(0, 0, 0))
+			schedule.all_pending(at(19, 0, 0, 0), at(20, 0, 0, 0))
 		)
 	}
 
 	#[test]
 	fn schedule_filters_past_events() {
 		let location = Location { longitude: 3.297, latitude: 55.9 };
-		let date = Utc.ymd(2019, 5, 19);
 		let events = vec![
 			ScheduledEvent::new("foo", "08:00", "30", "65").unwrap(),
 			ScheduledEvent::new("bar", "08:12", "20", "65").unwrap()
@@ -290,10 +329,120 @@ mod test {
 		let schedule = Schedule::new(&events, &location);
 		assert_eq!(
 			vec![
-				Pending::new("bar", &date.and_hms(9, 12, 0))
+				Pending::new("bar", &at(19, 9, 12, 0))
 			],
-			schedule.all_pending(date.and_hms(9, 1, 0))
+			schedule.all_pending(at(19, 9, 1, 0), at(20, 0, 0, 0))
 		)
 	}
 
-}
\ No newline at end of file
+	#[test]
+	fn schedule_includes_checks_running_on_past_midnight() {
+		let location = Location { longitude: 0.0, latitude: 0.0 };
+		let events = vec![
+			ScheduledEvent::new("foo", "23:30", "30", "90").unwrap()
+		];
+		let schedule = Schedule::new(&events, &location);
+		assert_eq!(
+			vec![
+				Pending::new("foo", &at(20, 0, 0, 0)),
+				Pending::new("foo", &at(20, 0, 30, 0)),
+				Pending::new("foo", &at(20, 23, 30, 0))
+			],
+			schedule.all_pending(at(20, 0, 0, 0), at(21, 0, 0, 0))
+		)
+	}
+
+	fn receive_all(rx: &mpsc::Receiver<Event>, clock: &VirtualClock) -> Vec<String> {
+		clock.wait_for_sleepers(1);
+		rx.try_iter()
+			.map(|event| match event {
+				Event::ConditionalIrrigateEvent(name) => name,
+				e => panic!("unexpected event {:?}", e)
+			})
+			.collect()
+	}
+
+	#[test]
+	fn schedule_runs_against_virtual_clock() {
+		let location = Location { longitude: 3.297, latitude: 55.9 };
+		let events = vec![
+			ScheduledEvent::new("foo", "08:00", "30", "65").unwrap(),
+			ScheduledEvent::new("bar", "08:12", "20", "65").unwrap()
+		];
+		let schedule = Schedule::new(&events, &location);
+		let clock = Arc::new(VirtualClock::new(at(19, 0, 0, 0)));
+		let (tx, rx) = mpsc::channel();
+
+		let thread_clock = clock.clone();
+		spawn(move || schedule.main(thread_clock.as_ref(), tx));
+		assert!(receive_all(&rx, &clock).is_empty());
+
+		clock.advance_to(at(19, 8, 31, 0));
+		assert_eq!(vec!["foo", "bar", "foo"], receive_all(&rx, &clock));
+
+		clock.advance_to(at(19, 12, 0, 0));
+		assert_eq!(vec!["bar", "bar", "foo", "bar"], receive_all(&rx, &clock));
+
+		clock.advance_to(at(20, 8, 0, 0));
+		assert_eq!(vec!["foo"], receive_all(&rx, &clock));
+	}
+
+	#[test]
+	fn schedule_runs_checks_at_and_past_midnight() {
+		let location = Location { longitude: 0.0, latitude: 0.0 };
+		let events = vec![
+			ScheduledEvent::new("foo", "23:30", "30", "90").unwrap(),
+			ScheduledEvent::new("bar", "00:00", "60", "60").unwrap()
+		];
+		let schedule = Schedule::new(&events, &location);
+		let clock = Arc::new(VirtualClock::new(at(19, 12, 0, 0)));
+		let (tx, rx) = mpsc::channel();
+
+		let thread_clock = clock.clone();
+		spawn(move || schedule.main(thread_clock.as_ref(), tx));
+		assert!(receive_all(&rx, &clock).is_empty());
+
+		clock.advance_to(at(20, 0, 0, 0));
+		assert_eq!(vec!["foo", "foo", "bar"], receive_all(&rx, &clock));
+
+		clock.advance_to(at(20, 1, 0, 0));
+		assert_eq!(vec!["foo"], receive_all(&rx, &clock));
+	}
+
+	#[test]
+	fn schedule_runs_a_day_of_checks_in_one_step() {
+		let location = Location { longitude: 0.0, latitude: 0.0 };
+		let events = vec![
+			ScheduledEvent::new("foo", "06:00", "60", "720").unwrap()
+		];
+		let schedule = Schedule::new(&events, &location);
+		let clock = Arc::new(VirtualClock::new(at(19, 0, 0, 0)));
+		let (tx, rx) = mpsc::channel();
+
+		let thread_clock = clock.clone();
+		spawn(move || schedule.main(thread_clock.as_ref(), tx));
+		clock.wait_for_sleepers(1);
+		clock.advance(Duration::days(1));
+		assert_eq!(12, receive_all(&rx, &clock).len());
+	}
+
+	#[test]
+	fn schedule_skips_checks_missed_in_a_jump_of_days() {
+		let location = Location { longitude: 0.0, latitude: 0.0 };
+		let events = vec![
+			ScheduledEvent::new("foo", "06:00", "60", "720").unwrap()
+		];
+		let schedule = Schedule::new(&events, &location);
+		let clock = Arc::new(VirtualClock::new(at(19, 0, 0, 0)));
+		let (tx, rx) = mpsc::channel();
+
+		let thread_clock = clock.clone();
+		spawn(move || schedule.main(thread_clock.as_ref(), tx));
+		clock.wait_for_sleepers(1);
+		clock.advance(Duration::days(5));
+		assert!(receive_all(&rx, &clock).is_empty());
+
+		clock.advance(Duration::hours(7));
+		assert_eq!(vec!["foo", "foo"], receive_all(&rx, &clock));
+	}
+}
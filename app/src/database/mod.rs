@@ -0,0 +1,265 @@
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use influxc::{ApiVersion, Client as InfluxClient, Credentials, Dropped, FileBacklog, Overflow, Precision, Record, RetentionRule};
+use std::error::Error;
+
+use crate::event::{Event, ToInfluxDB};
+use crate::event::moisture::Measurement;
+use crate::settings::{BacklogOverflow, DatabaseSettings, DatabaseVersion};
+use crate::sink::EventSink;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct BacklogMetrics {
+	pub expired: usize,
+	pub overflow: usize
+}
+
+pub struct Database {
+	query: InfluxClient,
+	version: DatabaseVersion,
+	organisation: String,
+	bucket: String
+}
+
+// Writes events in batches, one record each
+pub struct InfluxSink {
+	influx: InfluxClient,
+	backlog_dropped: Option<Dropped>,
+	create_bucket: Option<DatabaseSettings>,
+	organisation: String,
+	bucket: String
+}
+
+// Empty windows come back as a null minimum
+#[derive(Deserialize)]
+struct MoistureRow {
+	#[serde(rename = "_value")]
+	value: Option<f64>
+}
+
+fn add_event(record: &mut Record, event: &Event) {
+	match event {
+		Event::ButtonEvent(b) => b.add_to(record),
+		Event::WeatherEvent(w) => w.add_to(record),
+		Event::MoistureEvent(m) => m.add_to(record),
+		Event::IrrigatedEvent(i) => i.add_to(record),
+		_ => ()
+	};
+}
+
+fn credentials(settings: &DatabaseSettings) -> Credentials {
+	match &settings.username {
+		Some(username) => Credentials::from_basic(username, settings.password.as_deref().unwrap_or("")),
+		None => Credentials::from_token(&settings.token)
+	}
+}
+
+fn api_version(settings: &DatabaseSettings) -> ApiVersion {
+	match settings.version {
+		DatabaseVersion::V1 => ApiVersion::V1,
+		DatabaseVersion::V2 => ApiVersion::V2
+	}
+}
+
+impl InfluxSink {
+	pub fn new(settings: &DatabaseSettings) -> Result<Self, Box<dyn Error>> {
+		let mut builder = InfluxClient::build(settings.url.clone(), credentials(settings))
+			.api_version(api_version(settings));
+
+		// Writes that fail, say while the Wi-Fi is down, are kept here and
+		// retried ahead of the next write
+		let mut backlog_dropped = None;
+		if let Some(path) = &settings.backlog_path {
+			std::fs::create_dir_all(path)?;
+			let backlog = bounded_backlog(FileBacklog::new(path)?, settings);
+			backlog_dropped = Some(backlog.dropped());
+			builder = builder.backlog(backlog);
+		}
+
+		// Kept until the bucket is there, as a greenhouse may start before
+		// its network does
+		let create_bucket = match (settings.create_bucket, settings.version) {
+			(true, DatabaseVersion::V1) => {
+				warn!("Not creating bucket {}: buckets can only be created in InfluxDB 2.x", settings.bucket);
+				None
+			}
+			(true, _) => Some(settings.clone()),
+			(false, _) => None
+		};
+
+		Ok(InfluxSink {
+			influx: builder.finish()?,
+			backlog_dropped,
+			create_bucket,
+			organisation: settings.organisation.clone(),
+			bucket: settings.bucket.clone()
+		})
+	}
+
+	pub fn backlog_dropped(&self) -> Option<Dropped> {
+		self.backlog_dropped.clone()
+	}
+}
+
+impl BacklogMetrics {
+	pub fn of(dropped: Option<&Dropped>) -> Self {
+		dropped
+			.map(|dropped| BacklogMetrics { expired: dropped.expired(), overflow: dropped.overflow() })
+			.unwrap_or_default()
+	}
+}
+
+impl EventSink for InfluxSink {
+	fn write(&mut self, events: &[Event]) -> Result<(), Box<dyn Error>> {
+		if let Some(settings) = &self.create_bucket {
+			match ensure_bucket(&self.influx, settings) {
+				Ok(()) => self.create_bucket = None,
+				// The write fails as well and is backlogged until it exists
+				Err(e) => warn!("Failed to create bucket {}: {}", settings.bucket, e)
+			}
+		}
+		let mut record = Record::new(&self.organisation, &self.bucket)
+			.precision(Precision::Seconds);
+		events.iter().for_each(|event| add_event(&mut record, event));
+		self.influx.write(&record)?;
+		Ok(())
+	}
+}
+
+impl Database {
+	pub fn new(settings: &DatabaseSettings) -> Result<Self, Box<dyn Error>> {
+		let query = InfluxClient::build(settings.url.clone(), credentials(settings))
+			.api_version(api_version(settings))
+			.finish()?;
+		Ok(Database {
+			query,
+			version: settings.version,
+			organisation: settings.organisation.clone(),
+			bucket: settings.bucket.clone()
+		})
+	}
+
+	pub fn get_min_moisture_in_last_hour(&self, sensor: &str, now: DateTime<Utc>) -> Result<Measurement, Box<dyn Error>> {
+		let (start, stop) = (now - Duration::hours(1), now);
+		let rows = match self.version {
+			DatabaseVersion::V2 => {
+				let query = min_moisture_query(&self.bucket, sensor, start, stop);
+				debug!("influxdb<< {}", query);
+				self.query.query_as::<MoistureRow>(&self.organisation, &query)?
+			}
+			DatabaseVersion::V1 => {
+				let (database, query) = min_moisture_influxql(&self.bucket, sensor, start, stop);
+				debug!("influxdb<< {}", query);
+				self.query.influxql_as::<MoistureRow>(&database, &query)?
+			}
+		};
+
+		rows.iter()
+			.filter_map(|row| row.value)
+			.map(|value| value.round() as Measurement)
+			.min()
+			.ok_or_else(|| format!("no moisture readings for {} in the last hour", sensor).into())
+	}
+}
+
+// Saves setting up a new greenhouse's bucket in the InfluxDB UI
+fn ensure_bucket(client: &InfluxClient, settings: &DatabaseSettings) -> Result<(), Box<dyn Error>> {
+	if client.bucket(&settings.organisation, &settings.bucket)?.is_some() {
+		return Ok(());
+	}
+	let org_id = client.organization_id(&settings.organisation)?
+		.ok_or_else(|| format!("no such organisation {}", settings.organisation))?;
+	let rules: Vec<RetentionRule> = settings.bucket_retention_days.iter()
+		.map(|days| RetentionRule::expire(std::time::Duration::from_secs(days * 24 * 3600)))
+		.collect();
+	client.create_bucket(&org_id, &settings.bucket, &rules)?;
+	info!("Created bucket {} in organisation {}", settings.bucket, settings.organisation);
+	Ok(())
+}
+
+// A week-long outage must not fill the SD card
+fn bounded_backlog(backlog: FileBacklog, settings: &DatabaseSettings) -> FileBacklog {
+	let mut backlog = backlog.overflow(match settings.backlog_overflow {
+		BacklogOverflow::DropOldest => Overflow::DropOldest,
+		BacklogOverflow::DropNewest => Overflow::DropNewest,
+		BacklogOverflow::Downsample => Overflow::Downsample
+	});
+	if let Some(kb) = settings.backlog_max_kb {
+		backlog = backlog.max_size(kb * 1024);
+	}
+	if let Some(hours) = settings.backlog_max_hours {
+		backlog = backlog.max_age(std::time::Duration::from_secs(hours * 3600));
+	}
+	backlog
+}
+
+fn flux_string(s: &str) -> String {
+	format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Absolute time bounds rather than a relative range so that "the last hour"
+// follows the app's clock, not the database server's
+fn min_moisture_query(bucket: &str, sensor: &str, start: DateTime<Utc>, stop: DateTime<Utc>) -> String {
+	format!(
+		"from(bucket: {})\n\
+		 |> range(start: {}, stop: {})\n\
+		 |> filter(fn: (r) => r._measurement == \"moisture\" and r._field == \"value\" and r.name == {})\n\
+		 |> min()",
+		flux_string(bucket),
+		start.to_rfc3339_opts(SecondsFormat::Secs, true),
+		stop.to_rfc3339_opts(SecondsFormat::Secs, true),
+		flux_string(sensor)
+	)
+}
+
+fn influxql_string(s: &str) -> String {
+	format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+// The same query for InfluxDB 1.x, where the bucket setting is the database
+// and optional retention policy. The minimum is named after the Flux value
+// column so both decode into the same row type.
+fn min_moisture_influxql(bucket: &str, sensor: &str, start: DateTime<Utc>, stop: DateTime<Utc>) -> (String, String) {
+	let (database, measurement) = match bucket.split_once('/') {
+		Some((database, policy)) if !policy.is_empty() => (database, format!("\"{}\".\"moisture\"", policy.replace('"', "\\\""))),
+		Some((database, _)) => (database, "\"moisture\"".to_string()),
+		None => (bucket, "\"moisture\"".to_string())
+	};
+	let query = format!(
+		"SELECT min(\"value\") AS \"_value\" FROM {} WHERE \"name\" = {} AND time >= {} AND time < {}",
+		measurement,
+		influxql_string(sensor),
+		influxql_string(&start.to_rfc3339_opts(SecondsFormat::Secs, true)),
+		influxql_string(&stop.to_rfc3339_opts(SecondsFormat::Secs, true))
+	);
+	(database.to_string(), query)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use chrono::TimeZone;
+
+	#[test]
+	fn query_filters_sensor_within_absolute_range() {
+		let stop = Utc.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap();
+		let query = min_moisture_query("garden", "tomato \"1\"", stop - Duration::hours(1), stop);
+		assert_eq!(query,
+			"from(bucket: \"garden\")\n\
+			 |> range(start: 2020-06-01T11:00:00Z, stop: 2020-06-01T12:00:00Z)\n\
+			 |> filter(fn: (r) => r._measurement == \"moisture\" and r._field == \"value\" and r.name == \"tomato \\\"1\\\"\")\n\
+			 |> min()"
+		);
+	}
+
+	#[test]
+	fn influxql_query_names_database_and_retention_policy() {
+		let stop = Utc.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap();
+		let (database, query) = min_moisture_influxql("garden/week", "tomato 'one'", stop - Duration::hours(1), stop);
+		assert_eq!(database, "garden");
+		assert_eq!(query,
+			"SELECT min(\"value\") AS \"_value\" FROM \"week\".\"moisture\" \
+			 WHERE \"name\" = 'tomato \\'one\\'' AND time >= '2020-06-01T11:00:00Z' AND time < '2020-06-01T12:00:00Z'"
+		);
+		assert_eq!(min_moisture_influxql("garden", "tomato", stop, stop).1.split(' ').nth(5), Some("\"moisture\""));
+	}
+}
@@ -1,18 +1,12 @@
-use chrono::{DateTime, Utc};
+use influxc::Record;
 
-#[derive(Debug)]
-pub struct ButtonEvent {
-	pub time: DateTime<Utc>,
-	pub name: String,
-	pub state: bool
-}
+pub use common::button::ButtonEvent;
 
 impl super::ToInfluxDB for ButtonEvent {
-	fn to_line(&self) -> String {
-		format!("button,name={} state={} {}",
-				self.name,
-				self.state,
-				self.time.timestamp()
-		)
+	fn add_to(&self, record: &mut Record) {
+		record.measurement("button")
+			.tag("name", &self.name)
+			.field("state", self.state)
+			.timestamp(self.time);
 	}
 }
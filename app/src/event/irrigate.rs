@@ -1,18 +1,12 @@
-use chrono::{DateTime, Utc};
+use influxc::Record;
 
-#[derive(Debug)]
-pub struct IrrigatedEvent {
-    pub time: DateTime<Utc>,
-    pub name: String,
-    pub seconds: u32
-}
+pub use common::irrigation::IrrigatedEvent;
 
 impl super::ToInfluxDB for IrrigatedEvent {
-    fn to_line(&self) -> String {
-        format!("irrigated,name={} durationSeconds={} {}",
-                self.name,
-                self.seconds,
-                self.time.timestamp()
-        )
+    fn add_to(&self, record: &mut Record) {
+        record.measurement("irrigated")
+            .tag("name", &self.name)
+            .field("durationSeconds", self.seconds as f64)
+            .timestamp(self.time);
     }
 }
\ No newline at end of file
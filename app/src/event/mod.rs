@@ -3,16 +3,12 @@ pub mod irrigate;
 pub mod moisture;
 pub mod weather;
 
-#[derive(Debug)]
-pub enum Event {
-	WeatherEvent(weather::WeatherEvent),
-	MoistureEvent(moisture::MoistureEvent),
-	ButtonEvent(button::ButtonEvent),
-	ConditionalIrrigateEvent(String),
-	IrrigateEvent(String),
-	IrrigatedEvent(irrigate::IrrigatedEvent)
-}
+pub use common::event::Event;
+
+use influxc::Record;
 
+// Numeric fields are written as floats, the type the existing series in the
+// bucket were created with; InfluxDB rejects writes that change a field's type.
 pub trait ToInfluxDB {
-	fn to_line(&self) -> String;
+	fn add_to(&self, record: &mut Record);
 }
@@ -1,20 +1,12 @@
-use chrono::{DateTime, Utc};
+use influxc::Record;
 
-pub type Measurement = u16;
-
-#[derive(Debug)]
-pub struct MoistureEvent {
-	pub time: DateTime<Utc>,
-	pub name: String,
-	pub value: Measurement
-}
+pub use common::moisture::{Measurement, MoistureEvent};
 
 impl super::ToInfluxDB for MoistureEvent {
-	fn to_line(&self) -> String {
-		format!("moisture,name={} value={} {}",
-			self.name,
-			self.value,
-			self.time.timestamp()
-		)
+	fn add_to(&self, record: &mut Record) {
+		record.measurement("moisture")
+			.tag("name", &self.name)
+			.field("value", self.value as f64)
+			.timestamp(self.time);
 	}
 }
\ No newline at end of file
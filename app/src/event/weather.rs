@@ -1,24 +1,19 @@
-use chrono::{DateTime, Utc};
+use influxc::Record;
 
-pub type Temperature = f64;
-pub type Humidity = f64;
-pub type Pressure = f64;
-
-#[derive(Debug)]
-pub struct WeatherEvent {
-	pub time: DateTime<Utc>,
-	pub temperature: Temperature,
-	pub humidity: Humidity,
-	pub pressure: Pressure
-}
+pub use common::weather::{Humidity, Pressure, Temperature, WeatherEvent};
 
 impl super::ToInfluxDB for WeatherEvent {
-	fn to_line(&self) -> String {
-		format!("weather temperature={},humidity={},pressure={} {}",
-			self.temperature,
-			self.humidity,
-			self.pressure,
-			self.time.timestamp()
-		)
+	// A glitching sensor can read NaN, which InfluxDB can't store and would
+	// fail the rest of the batch with it
+	fn add_to(&self, record: &mut Record) {
+		if ![self.temperature, self.humidity, self.pressure].iter().all(|v| v.is_finite()) {
+			warn!("skipping weather reading that is not a number: {:?}", self);
+			return;
+		}
+		record.measurement("weather")
+			.field("temperature", self.temperature)
+			.field("humidity", self.humidity)
+			.field("pressure", self.pressure)
+			.timestamp(self.time);
 	}
 }
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use crate::event::moisture::Measurement;
+use crate::settings::{ADCSettings, WeatherSensorSettings};
+use super::*;
+
+const DEFAULT_ANALOG_VALUE: Measurement = 512;
+
+#[derive(Debug)]
+struct State {
+	pins: HashMap<u8, bool>,
+	analog: HashMap<u8, Measurement>,
+	environment: EnvironmentReading
+}
+
+// In-memory stand-in for the Raspberry Pi peripherals. Clones share the same
+// state so a test or simulation can drive inputs and observe outputs.
+#[derive(Clone, Debug)]
+pub struct MemoryHardware {
+	state: Arc<Mutex<State>>
+}
+
+impl MemoryHardware {
+	pub fn new() -> Self {
+		MemoryHardware {
+			state: Arc::new(Mutex::new(State {
+				pins: HashMap::new(),
+				analog: HashMap::new(),
+				environment: EnvironmentReading {
+					temperature: 20.0,
+					humidity: 50.0,
+					pressure: 1013.25
+				}
+			}))
+		}
+	}
+
+	pub fn pin(&self, gpio: u8) -> bool {
+		self.state.lock().unwrap().pins.get(&gpio).cloned().unwrap_or(false)
+	}
+
+	pub fn set_pin(&self, gpio: u8, high: bool) {
+		self.state.lock().unwrap().pins.insert(gpio, high);
+	}
+
+	pub fn analog(&self, channel: u8) -> Measurement {
+		self.state.lock().unwrap().analog.get(&channel).cloned().unwrap_or(DEFAULT_ANALOG_VALUE)
+	}
+
+	pub fn set_analog(&self, channel: u8, value: Measurement) {
+		self.state.lock().unwrap().analog.insert(channel, value);
+	}
+
+	pub fn environment(&self) -> EnvironmentReading {
+		self.state.lock().unwrap().environment.clone()
+	}
+
+	pub fn set_environment(&self, reading: EnvironmentReading) {
+		self.state.lock().unwrap().environment = reading;
+	}
+}
+
+impl Default for MemoryHardware {
+	fn default() -> Self {
+		MemoryHardware::new()
+	}
+}
+
+struct Pin {
+	hardware: MemoryHardware,
+	gpio: u8
+}
+
+impl DigitalOutput for Pin {
+	fn set(&mut self, high: bool) -> Result<(), Box<dyn Error>> {
+		self.hardware.set_pin(self.gpio, high);
+		Ok(())
+	}
+}
+
+impl DigitalInput for Pin {
+	fn read(&self) -> Result<bool, Box<dyn Error>> {
+		Ok(self.hardware.pin(self.gpio))
+	}
+}
+
+struct Channel {
+	hardware: MemoryHardware,
+	channel: u8
+}
+
+impl AnalogChannel for Channel {
+	fn read_value(&self) -> Result<Measurement, Box<dyn Error>> {
+		Ok(self.hardware.analog(self.channel))
+	}
+}
+
+impl AnalogDevice for MemoryHardware {
+	fn channels(self: Box<Self>, channels: &[u8]) -> Result<Vec<Box<dyn AnalogChannel>>, Box<dyn Error>> {
+		Ok(channels.iter()
+			.map(|channel| Box::new(Channel { hardware: (*self).clone(), channel: *channel }) as Box<dyn AnalogChannel>)
+			.collect())
+	}
+}
+
+impl EnvironmentSensor for MemoryHardware {
+	fn read(&mut self) -> Result<EnvironmentReading, Box<dyn Error>> {
+		Ok(self.environment())
+	}
+}
+
+impl Hardware for MemoryHardware {
+	fn digital_output(&self, gpio: u8) -> Result<Box<dyn DigitalOutput>, Box<dyn Error>> {
+		Ok(Box::new(Pin { hardware: self.clone(), gpio }))
+	}
+
+	fn digital_input(&self, gpio: u8) -> Result<Box<dyn DigitalInput>, Box<dyn Error>> {
+		Ok(Box::new(Pin { hardware: self.clone(), gpio }))
+	}
+
+	fn analog_device(&self, _settings: &ADCSettings) -> Result<Box<dyn AnalogDevice>, Box<dyn Error>> {
+		Ok(Box::new(self.clone()))
+	}
+
+	fn environment_sensor(&self, _settings: &WeatherSensorSettings) -> Result<Box<dyn EnvironmentSensor>, Box<dyn Error>> {
+		Ok(Box::new(self.clone()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn outputs_are_visible_through_shared_state() {
+		let hardware = MemoryHardware::new();
+		let mut output = hardware.digital_output(17).unwrap();
+		assert!(!hardware.pin(17));
+		output.set(true).unwrap();
+		assert!(hardware.pin(17));
+	}
+
+	#[test]
+	fn inputs_read_shared_state() {
+		let hardware = MemoryHardware::new();
+		let input = hardware.digital_input(4).unwrap();
+		assert!(!input.read().unwrap());
+		hardware.set_pin(4, true);
+		assert!(input.read().unwrap());
+	}
+
+	#[test]
+	fn analog_channels_read_shared_state() {
+		let hardware = MemoryHardware::new();
+		let adc = hardware.analog_device(&ADCSettings {
+			device: "CE0".to_string(),
+			device_type: "MCP3008".to_string(),
+			chip_select_gpio: 8,
+			enable_gpio: 24,
+			update: 60
+		}).unwrap();
+		let channels = adc.channels(&[0, 3]).unwrap();
+		hardware.set_analog(3, 700);
+		assert_eq!(DEFAULT_ANALOG_VALUE, channels[0].read_value().unwrap());
+		assert_eq!(700, channels[1].read_value().unwrap());
+	}
+}
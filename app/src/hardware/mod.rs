@@ -0,0 +1,59 @@
+mod memory;
+mod rpi;
+mod simulation;
+
+use std::error::Error;
+use std::sync::Arc;
+
+use crate::clock::SharedClock;
+use crate::event::moisture::Measurement;
+use crate::event::weather::{Humidity, Pressure, Temperature};
+use crate::settings::{ADCSettings, HardwareBackend, Settings, WeatherSensorSettings};
+
+pub use memory::MemoryHardware;
+pub use rpi::RpiHardware;
+pub use simulation::SimulationHardware;
+
+pub trait DigitalOutput: Send {
+	fn set(&mut self, high: bool) -> Result<(), Box<dyn Error>>;
+}
+
+pub trait DigitalInput: Send {
+	fn read(&self) -> Result<bool, Box<dyn Error>>;
+}
+
+pub trait AnalogChannel {
+	fn read_value(&self) -> Result<Measurement, Box<dyn Error>>;
+}
+
+// An ADC is opened on the caller's thread but its channels may share the
+// device, so they are only created once it has moved to its reading thread.
+pub trait AnalogDevice: Send {
+	fn channels(self: Box<Self>, channels: &[u8]) -> Result<Vec<Box<dyn AnalogChannel>>, Box<dyn Error>>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnvironmentReading {
+	pub temperature: Temperature,
+	pub humidity: Humidity,
+	pub pressure: Pressure
+}
+
+pub trait EnvironmentSensor: Send {
+	fn read(&mut self) -> Result<EnvironmentReading, Box<dyn Error>>;
+}
+
+pub trait Hardware: Send + Sync {
+	fn digital_output(&self, gpio: u8) -> Result<Box<dyn DigitalOutput>, Box<dyn Error>>;
+	fn digital_input(&self, gpio: u8) -> Result<Box<dyn DigitalInput>, Box<dyn Error>>;
+	fn analog_device(&self, settings: &ADCSettings) -> Result<Box<dyn AnalogDevice>, Box<dyn Error>>;
+	fn environment_sensor(&self, settings: &WeatherSensorSettings) -> Result<Box<dyn EnvironmentSensor>, Box<dyn Error>>;
+}
+
+pub fn from_settings(s: &Settings, clock: SharedClock) -> Arc<dyn Hardware> {
+	match s.hardware {
+		HardwareBackend::Rpi => Arc::new(RpiHardware),
+		HardwareBackend::Memory => Arc::new(MemoryHardware::new()),
+		HardwareBackend::Simulation => Arc::new(SimulationHardware::new(s, clock))
+	}
+}
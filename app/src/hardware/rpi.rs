@@ -0,0 +1,88 @@
+use bme280::Bme280Device;
+use mcp3xxx::{AnalogIn, MCPDevice};
+use rustpi_io::gpio::*;
+
+use std::error::Error;
+use std::str::FromStr;
+
+use crate::event::moisture::Measurement;
+use crate::settings::{ADCSettings, WeatherSensorSettings};
+use super::*;
+
+pub struct RpiHardware;
+
+struct Output(GPIO);
+
+impl DigitalOutput for Output {
+	fn set(&mut self, high: bool) -> Result<(), Box<dyn Error>> {
+		self.0.set(if high { GPIOData::High } else { GPIOData::Low })?;
+		Ok(())
+	}
+}
+
+struct Input(GPIO);
+
+impl DigitalInput for Input {
+	fn read(&self) -> Result<bool, Box<dyn Error>> {
+		match self.0.value()? {
+			GPIOData::Low => Ok(false),
+			GPIOData::High => Ok(true)
+		}
+	}
+}
+
+struct Adc(MCPDevice);
+
+impl AnalogDevice for Adc {
+	fn channels(self: Box<Self>, channels: &[u8]) -> Result<Vec<Box<dyn AnalogChannel>>, Box<dyn Error>> {
+		let shared = self.0.share();
+		let mut result: Vec<Box<dyn AnalogChannel>> = vec![];
+		for channel in channels {
+			result.push(Box::new(Channel(AnalogIn::single(shared.clone(), *channel)?)));
+		}
+		Ok(result)
+	}
+}
+
+struct Channel(AnalogIn);
+
+impl AnalogChannel for Channel {
+	fn read_value(&self) -> Result<Measurement, Box<dyn Error>> {
+		Ok(self.0.read_value()?)
+	}
+}
+
+struct Environment(Bme280Device);
+
+impl EnvironmentSensor for Environment {
+	fn read(&mut self) -> Result<EnvironmentReading, Box<dyn Error>> {
+		let data = self.0.read()?;
+		Ok(EnvironmentReading {
+			temperature: data.temperature,
+			humidity: data.humidity,
+			pressure: data.pressure
+		})
+	}
+}
+
+impl Hardware for RpiHardware {
+	fn digital_output(&self, gpio: u8) -> Result<Box<dyn DigitalOutput>, Box<dyn Error>> {
+		Ok(Box::new(Output(GPIO::new(gpio, GPIOMode::Write)?)))
+	}
+
+	fn digital_input(&self, gpio: u8) -> Result<Box<dyn DigitalInput>, Box<dyn Error>> {
+		Ok(Box::new(Input(GPIO::new(gpio, GPIOMode::Read)?)))
+	}
+
+	fn analog_device(&self, settings: &ADCSettings) -> Result<Box<dyn AnalogDevice>, Box<dyn Error>> {
+		let device = mcp3xxx::device_from_str(&settings.device)?;
+		let device_type = FromStr::from_str(&settings.device_type)?;
+		let mcp = MCPDevice::new(device, device_type, settings.chip_select_gpio)?;
+		Ok(Box::new(Adc(mcp)))
+	}
+
+	fn environment_sensor(&self, settings: &WeatherSensorSettings) -> Result<Box<dyn EnvironmentSensor>, Box<dyn Error>> {
+		let device = Bme280Device::new(&settings.device, settings.address)?;
+		Ok(Box::new(Environment(device)))
+	}
+}
@@ -0,0 +1,174 @@
+mod model;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use crate::clock::SharedClock;
+use crate::event::moisture::Measurement;
+use crate::settings::{ADCSettings, Settings, WeatherSensorSettings};
+use super::*;
+use model::Greenhouse;
+
+#[derive(Clone, Debug)]
+struct Probe {
+	bed: usize,
+	min_reading: Measurement,
+	max_reading: Measurement
+}
+
+// Simulated greenhouse: valves water the beds of the zones they feed, the
+// moisture probes and weather sensor read back the modelled state. Pins that
+// are not valves (buttons, the ADC enable line) behave as plain memory.
+#[derive(Clone)]
+pub struct SimulationHardware {
+	clock: SharedClock,
+	greenhouse: Arc<Mutex<Greenhouse>>,
+	pins: MemoryHardware,
+	valves: HashMap<u8, Vec<usize>>,
+	// Keyed by ADC device and channel
+	probes: HashMap<(String, u8), Probe>
+}
+
+impl SimulationHardware {
+	pub fn new(s: &Settings, clock: SharedClock) -> Self {
+		let mut beds: Vec<Vec<String>> = s.controller.zones.iter()
+			.map(|zone| zone.sensors.clone())
+			.collect();
+
+		let device = s.adc.as_ref().map(|adc| adc.device.clone()).unwrap_or_default();
+		let mut probes = HashMap::new();
+		for sensor in s.moisture.iter() {
+			let key = (device.clone(), sensor.channel);
+			if probes.contains_key(&key) {
+				warn!("moisture sensor {} shares channel {} with another sensor and will read the same probe", sensor.name, sensor.channel);
+				continue;
+			}
+			let bed = match beds.iter().position(|sensors| sensors.contains(&sensor.name)) {
+				Some(bed) => bed,
+				None => {
+					beds.push(vec![sensor.name.clone()]);
+					beds.len() - 1
+				}
+			};
+			probes.insert(key, Probe {
+				bed,
+				min_reading: sensor.min_reading,
+				max_reading: sensor.max_reading
+			});
+		}
+
+		let mut valves = HashMap::new();
+		for valve in s.valves.iter() {
+			let zones = s.controller.zones.iter()
+				.enumerate()
+				.filter(|(_, zone)| zone.valve == valve.name)
+				.map(|(bed, _)| bed)
+				.collect();
+			valves.insert(valve.gpio, zones);
+		}
+
+		let settings = s.simulation.clone().unwrap_or_default();
+		let greenhouse = Greenhouse::new(&settings, s.controller.location.longitude, beds.len(), clock.now());
+
+		SimulationHardware {
+			clock,
+			greenhouse: Arc::new(Mutex::new(greenhouse)),
+			pins: MemoryHardware::new(),
+			valves,
+			probes
+		}
+	}
+
+	fn observe<T>(&self, f: impl FnOnce(&mut Greenhouse) -> T) -> T {
+		let mut greenhouse = self.greenhouse.lock().unwrap();
+		greenhouse.advance_to(self.clock.now());
+		f(&mut greenhouse)
+	}
+}
+
+struct Valve {
+	hardware: SimulationHardware,
+	gpio: u8,
+	beds: Vec<usize>
+}
+
+impl DigitalOutput for Valve {
+	fn set(&mut self, high: bool) -> Result<(), Box<dyn Error>> {
+		if high != self.hardware.pins.pin(self.gpio) {
+			let beds = &self.beds;
+			self.hardware.observe(|greenhouse| {
+				beds.iter().for_each(|bed| greenhouse.set_irrigating(*bed, high))
+			});
+			self.hardware.pins.set_pin(self.gpio, high);
+		}
+		Ok(())
+	}
+}
+
+struct Probes {
+	hardware: SimulationHardware,
+	device: String
+}
+
+struct Channel {
+	hardware: SimulationHardware,
+	probe: Option<Probe>
+}
+
+impl AnalogChannel for Channel {
+	fn read_value(&self) -> Result<Measurement, Box<dyn Error>> {
+		match &self.probe {
+			None => Ok(0),
+			Some(probe) => {
+				let wetness = self.hardware.observe(|greenhouse| greenhouse.wetness(probe.bed));
+				let range = probe.max_reading.saturating_sub(probe.min_reading) as f64;
+				let reading = probe.max_reading as f64 - wetness * range;
+				Ok((reading.round() as Measurement).max(1))
+			}
+		}
+	}
+}
+
+impl AnalogDevice for Probes {
+	fn channels(self: Box<Self>, channels: &[u8]) -> Result<Vec<Box<dyn AnalogChannel>>, Box<dyn Error>> {
+		Ok(channels.iter()
+			.map(|channel| Box::new(Channel {
+				hardware: self.hardware.clone(),
+				probe: self.hardware.probes.get(&(self.device.clone(), *channel)).cloned()
+			}) as Box<dyn AnalogChannel>)
+			.collect())
+	}
+}
+
+impl EnvironmentSensor for SimulationHardware {
+	fn read(&mut self) -> Result<EnvironmentReading, Box<dyn Error>> {
+		let weather = self.observe(|greenhouse| greenhouse.weather());
+		Ok(EnvironmentReading {
+			temperature: weather.temperature,
+			humidity: weather.humidity,
+			pressure: weather.pressure
+		})
+	}
+}
+
+impl Hardware for SimulationHardware {
+	fn digital_output(&self, gpio: u8) -> Result<Box<dyn DigitalOutput>, Box<dyn Error>> {
+		match self.valves.get(&gpio) {
+			Some(beds) => Ok(Box::new(Valve { hardware: self.clone(), gpio, beds: beds.clone() })),
+			None => self.pins.digital_output(gpio)
+		}
+	}
+
+	fn digital_input(&self, gpio: u8) -> Result<Box<dyn DigitalInput>, Box<dyn Error>> {
+		self.pins.digital_input(gpio)
+	}
+
+	fn analog_device(&self, settings: &ADCSettings) -> Result<Box<dyn AnalogDevice>, Box<dyn Error>> {
+		Ok(Box::new(Probes { hardware: self.clone(), device: settings.device.clone() }))
+	}
+
+	fn environment_sensor(&self, _settings: &WeatherSensorSettings) -> Result<Box<dyn EnvironmentSensor>, Box<dyn Error>> {
+		Ok(Box::new(self.clone()))
+	}
+}
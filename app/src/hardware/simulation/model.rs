@@ -0,0 +1,200 @@
+use chrono::{DateTime, Duration, Timelike, Utc};
+use std::f64::consts::PI;
+
+use crate::settings::SimulationSettings;
+
+const STEP_SECONDS: i64 = 60;
+const HOTTEST_HOUR: f64 = 15.0;
+const SUNRISE_HOUR: f64 = 6.0;
+const DAY_LENGTH_HOURS: f64 = 12.0;
+const NIGHT_TRANSPIRATION: f64 = 0.2;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Weather {
+	pub temperature: f64,
+	pub humidity: f64,
+	pub pressure: f64
+}
+
+#[derive(Debug)]
+struct Bed {
+	moisture: f64,
+	valves_open: usize
+}
+
+// A handful of soil beds sharing one greenhouse climate. State is integrated
+// lazily in fixed steps up to whatever time the hardware is next observed at.
+#[derive(Debug)]
+pub struct Greenhouse {
+	settings: SimulationSettings,
+	solar_offset_hours: f64,
+	time: DateTime<Utc>,
+	beds: Vec<Bed>
+}
+
+// Tetens' formula, in kPa
+fn saturation_vapour_pressure(temperature: f64) -> f64 {
+	0.6108 * (17.27 * temperature / (temperature + 237.3)).exp()
+}
+
+impl Greenhouse {
+	pub fn new(settings: &SimulationSettings, longitude: f64, beds: usize, time: DateTime<Utc>) -> Self {
+		Greenhouse {
+			settings: settings.clone(),
+			solar_offset_hours: longitude / 15.0,
+			time,
+			beds: (0..beds).map(|_| Bed { moisture: settings.initial_moisture, valves_open: 0 }).collect()
+		}
+	}
+
+	fn solar_hour(&self) -> f64 {
+		let hour = self.time.num_seconds_from_midnight() as f64 / 3600.0 + self.solar_offset_hours;
+		hour.rem_euclid(24.0)
+	}
+
+	fn daylight(&self) -> f64 {
+		let hour = self.solar_hour() - SUNRISE_HOUR;
+		if !(0.0..=DAY_LENGTH_HOURS).contains(&hour) {
+			0.0
+		} else {
+			(PI * hour / DAY_LENGTH_HOURS).sin()
+		}
+	}
+
+	pub fn weather(&self) -> Weather {
+		let s = &self.settings;
+		let hour = self.solar_hour();
+		let mid = (s.max_temperature + s.min_temperature) / 2.0;
+		let amplitude = (s.max_temperature - s.min_temperature) / 2.0;
+		let temperature = mid + amplitude * (2.0 * PI * (hour - HOTTEST_HOUR) / 24.0).cos();
+		let warmth = if amplitude > 0.0 { (temperature - s.min_temperature) / (2.0 * amplitude) } else { 0.5 };
+		let humidity = s.max_humidity - warmth * (s.max_humidity - s.min_humidity);
+		let pressure = s.pressure + 0.5 * (4.0 * PI * (hour - 10.0) / 24.0).cos();
+		Weather { temperature, humidity, pressure }
+	}
+
+	// Reference evapotranspiration in mm per hour, driven by vapour pressure
+	// deficit and scaled down at night when the stomata close.
+	fn evapotranspiration(&self) -> f64 {
+		let weather = self.weather();
+		let deficit = saturation_vapour_pressure(weather.temperature) * (1.0 - weather.humidity / 100.0);
+		let activity = NIGHT_TRANSPIRATION + (1.0 - NIGHT_TRANSPIRATION) * self.daylight();
+		self.settings.evapotranspiration * deficit.max(0.0) * activity
+	}
+
+	fn step(&mut self, seconds: f64) {
+		let s = &self.settings;
+		let et = self.evapotranspiration() * seconds / 3600.0 / s.root_depth;
+		let drainage = 1.0 - (-seconds / (s.drainage_hours * 3600.0)).exp();
+		let irrigation = s.irrigation_rate * seconds / 60.0 / s.root_depth;
+		for bed in self.beds.iter_mut() {
+			let stress = ((bed.moisture - s.wilting_point) / (s.field_capacity - s.wilting_point)).clamp(0.0, 1.0);
+			bed.moisture -= et * stress;
+			if bed.valves_open > 0 {
+				bed.moisture += irrigation;
+			}
+			if bed.moisture > s.field_capacity {
+				bed.moisture -= (bed.moisture - s.field_capacity) * drainage;
+			}
+			bed.moisture = bed.moisture.clamp(0.0, s.saturation);
+		}
+	}
+
+	pub fn advance_to(&mut self, time: DateTime<Utc>) {
+		while self.time < time {
+			let step = (time - self.time).min(Duration::seconds(STEP_SECONDS));
+			self.step(step.num_milliseconds() as f64 / 1000.0);
+			self.time += step;
+		}
+	}
+
+	pub fn set_irrigating(&mut self, bed: usize, on: bool) {
+		if let Some(bed) = self.beds.get_mut(bed) {
+			if on {
+				bed.valves_open += 1;
+			} else if bed.valves_open > 0 {
+				bed.valves_open -= 1;
+			}
+		}
+	}
+
+	pub fn moisture(&self, bed: usize) -> f64 {
+		self.beds.get(bed).map(|b| b.moisture).unwrap_or(0.0)
+	}
+
+	// Fraction of the way from wilting point to saturation, as a capacitive
+	// probe would see it
+	pub fn wetness(&self, bed: usize) -> f64 {
+		let s = &self.settings;
+		((self.moisture(bed) - s.wilting_point) / (s.saturation - s.wilting_point)).clamp(0.0, 1.0)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use chrono::TimeZone;
+
+	fn greenhouse(time: DateTime<Utc>) -> Greenhouse {
+		Greenhouse::new(&SimulationSettings::default(), 0.0, 2, time)
+	}
+
+	#[test]
+	fn afternoon_is_warmer_and_drier_than_night() {
+		let mut g = greenhouse(Utc.with_ymd_and_hms(2020, 6, 1, 3, 0, 0).unwrap());
+		let night = g.weather();
+		g.advance_to(Utc.with_ymd_and_hms(2020, 6, 1, 15, 0, 0).unwrap());
+		let afternoon = g.weather();
+		assert!((night.temperature - 12.0).abs() < 0.001);
+		assert!((afternoon.temperature - 28.0).abs() < 0.001);
+		assert!(afternoon.humidity < night.humidity);
+	}
+
+	#[test]
+	fn soil_dries_out_over_a_day() {
+		let start = Utc.with_ymd_and_hms(2020, 6, 1, 0, 0, 0).unwrap();
+		let mut g = greenhouse(start);
+		let before = g.moisture(0);
+		g.advance_to(start + Duration::days(1));
+		assert!(g.moisture(0) < before);
+		assert!(g.moisture(0) > SimulationSettings::default().wilting_point);
+	}
+
+	#[test]
+	fn drying_is_faster_by_day_than_by_night() {
+		let night = Utc.with_ymd_and_hms(2020, 6, 1, 0, 0, 0).unwrap();
+		let mut g = greenhouse(night);
+		g.advance_to(night + Duration::hours(2));
+		let night_loss = SimulationSettings::default().initial_moisture - g.moisture(0);
+
+		let day = Utc.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap();
+		let mut g = greenhouse(day);
+		g.advance_to(day + Duration::hours(2));
+		let day_loss = SimulationSettings::default().initial_moisture - g.moisture(0);
+
+		assert!(day_loss > night_loss * 2.0);
+	}
+
+	#[test]
+	fn irrigation_only_wets_its_own_bed() {
+		let start = Utc.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap();
+		let mut g = greenhouse(start);
+		g.set_irrigating(1, true);
+		g.advance_to(start + Duration::minutes(5));
+		g.set_irrigating(1, false);
+		assert!(g.moisture(1) > g.moisture(0));
+		assert!(g.moisture(1) > SimulationSettings::default().initial_moisture);
+	}
+
+	#[test]
+	fn excess_water_drains_to_field_capacity() {
+		let start = Utc.with_ymd_and_hms(2020, 6, 1, 0, 0, 0).unwrap();
+		let mut g = greenhouse(start);
+		g.set_irrigating(0, true);
+		g.advance_to(start + Duration::hours(1));
+		g.set_irrigating(0, false);
+		assert!(g.moisture(0) <= SimulationSettings::default().saturation);
+		g.advance_to(start + Duration::hours(12));
+		assert!(g.moisture(0) <= SimulationSettings::default().field_capacity + 0.01);
+	}
+}
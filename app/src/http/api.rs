@@ -0,0 +1,312 @@
+use chrono::{DateTime, Utc};
+use common::auth::Session;
+use common::irrigation::{IrrigateForEvent, IrrigationRow};
+use common::moisture::ZoneMoisture;
+use common::time::{COVERED_FROM_HEADER, TimeRange, UnixTime};
+use common::zone::ZoneSummary;
+use tiny_http::Method;
+
+use std::sync::mpsc::Sender;
+
+use crate::clock::SharedClock;
+use crate::controller::SharedHistory;
+use crate::database::BacklogMetrics;
+use crate::event::Event;
+use crate::settings::controller::Zone;
+use crate::sink::{SinkMetrics, WriterMetrics};
+use crate::valve::SharedValveStatus;
+use super::Reply;
+
+// Longest a zone may be irrigated for in one request
+const MAX_IRRIGATE_SECONDS: u32 = 3600;
+
+// The routes the UI fetches. Readings come from the controller's history;
+// irrigation requests go to the controller like any other event.
+pub struct Api {
+	zones: Vec<Zone>,
+	history: SharedHistory,
+	valves: SharedValveStatus,
+	metrics: SinkMetrics,
+	clock: SharedClock,
+	tx: Sender<Event>
+}
+
+#[derive(Serialize)]
+struct WriterRow<'a> {
+	name: &'a str,
+	#[serde(flatten)]
+	metrics: WriterMetrics
+}
+
+#[derive(Serialize)]
+struct Metrics<'a> {
+	sinks: Vec<WriterRow<'a>>,
+	backlog: BacklogMetrics
+}
+
+impl Api {
+	pub fn new(zones: Vec<Zone>, history: SharedHistory, valves: SharedValveStatus, metrics: SinkMetrics, clock: SharedClock, tx: Sender<Event>) -> Self {
+		Api { zones, history, valves, metrics, clock, tx }
+	}
+
+	pub fn handle(&self, session: &Session, method: &Method, route: &[&str]) -> Reply {
+		if *method != Method::Get && !session.can_operate() {
+			return Reply::text(403, "an operator token is needed");
+		}
+		match (method, route) {
+			(Method::Get, ["session"]) => Reply::json(session),
+			(Method::Get, ["zone", "list"]) =>
+				Reply::json(&self.zones.iter().map(summary).collect::<Vec<_>>()),
+			(Method::Get, ["zone", name, "moisture", from, to]) =>
+				self.with_zone_and_range(name, from, to, |zone, (start, end)| self.moisture(zone, start, end)),
+			(Method::Get, ["zone", name, "irrigation", from, to]) =>
+				self.with_zone_and_range(name, from, to, |zone, (start, end)| self.irrigation(zone, start, end)),
+			(Method::Get, ["weather", from, to]) => match self.range(from, to) {
+				Some((start, end)) => self.weather(start, end),
+				None => Reply::text(400, "malformed time range")
+			},
+			(Method::Get, ["valves"]) => Reply::json(&*self.valves.read().unwrap()),
+			(Method::Get, ["metrics"]) => self.metrics(),
+			(Method::Post, ["zone", name, "irrigate"]) =>
+				self.send_for_zone(name, Event::IrrigateEvent(name.to_string())),
+			(Method::Post, ["zone", name, "irrigate", seconds]) => match seconds.parse::<u32>() {
+				Ok(seconds) if seconds > 0 && seconds <= MAX_IRRIGATE_SECONDS =>
+					self.send_for_zone(name, Event::IrrigateForEvent(IrrigateForEvent { zone: name.to_string(), seconds })),
+				_ => Reply::text(400, &format!("irrigation time must be 1 to {} seconds", MAX_IRRIGATE_SECONDS))
+			},
+			(Method::Post, ["zone", name, "stop"]) =>
+				self.send_for_zone(name, Event::StopIrrigationEvent(name.to_string())),
+			(Method::Post, ["valves", "stop"]) => self.send(Event::StopAllIrrigationEvent),
+			_ => Reply::not_found()
+		}
+	}
+
+	fn metrics(&self) -> Reply {
+		let writers = self.metrics.writers();
+		Reply::json(&Metrics {
+			sinks: writers.into_iter().map(|(name, metrics)| WriterRow { name, metrics }).collect(),
+			backlog: self.metrics.backlog()
+		})
+	}
+
+	fn zone(&self, name: &str) -> Option<&Zone> {
+		self.zones.iter().find(|z| z.name == name)
+	}
+
+	fn range(&self, from: &str, to: &str) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+		TimeRange::parse(from, to).map(|range| range.at(self.clock.now()))
+	}
+
+	fn with_zone_and_range<F>(&self, name: &str, from: &str, to: &str, f: F) -> Reply
+		where F: Fn(&Zone, (DateTime<Utc>, DateTime<Utc>)) -> Reply {
+		match (self.zone(name), self.range(from, to)) {
+			(None, _) => Reply::text(404, &format!("no such zone {}", name)),
+			(_, None) => Reply::text(400, "malformed time range"),
+			(Some(zone), Some(range)) => f(zone, range)
+		}
+	}
+
+	fn moisture(&self, zone: &Zone, start: DateTime<Utc>, end: DateTime<Utc>) -> Reply {
+		let history = self.history.read().unwrap();
+		let series: ZoneMoisture = zone.sensors.iter()
+			.map(|sensor| (sensor.clone(), history.moisture(sensor, start).into_iter()
+				.filter(|(time, _)| *time <= end)
+				.map(|(time, value)| (UnixTime::from(time), value))
+				.collect()))
+			.collect();
+		let covered_from = zone.sensors.iter()
+			.filter_map(|sensor| history.moisture_covered_from(sensor))
+			.max();
+		covered(Reply::json(&series), start, covered_from)
+	}
+
+	fn irrigation(&self, zone: &Zone, start: DateTime<Utc>, end: DateTime<Utc>) -> Reply {
+		let history = self.history.read().unwrap();
+		let rows: Vec<IrrigationRow> = history
+			.irrigations(&zone.name, start)
+			.into_iter()
+			.filter(|i| i.time <= end)
+			.map(|i| IrrigationRow { start: UnixTime::from(i.time), duration: i.duration })
+			.collect();
+		covered(Reply::json(&rows), start, history.irrigation_covered_from(&zone.name))
+	}
+
+	fn weather(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Reply {
+		let history = self.history.read().unwrap();
+		let events: Vec<_> = history
+			.weather(start)
+			.into_iter()
+			.filter(|w| w.time <= end)
+			.collect();
+		covered(Reply::json(&events), start, history.weather_covered_from())
+	}
+
+	fn send_for_zone(&self, name: &str, event: Event) -> Reply {
+		match self.zone(name) {
+			Some(_) => self.send(event),
+			None => Reply::text(404, &format!("no such zone {}", name))
+		}
+	}
+
+	fn send(&self, event: Event) -> Reply {
+		match self.tx.send(event) {
+			Ok(()) => Reply::text(202, "accepted"),
+			Err(_) => Reply::text(503, "controller has stopped")
+		}
+	}
+}
+
+// The history only holds so much, so longer ranges are answered with what
+// it has and the time it holds everything from
+fn covered(reply: Reply, start: DateTime<Utc>, covered_from: Option<DateTime<Utc>>) -> Reply {
+	match covered_from {
+		Some(from) if from > start => reply.with_header(COVERED_FROM_HEADER, UnixTime::from(from).secs().to_string()),
+		_ => reply
+	}
+}
+
+fn summary(zone: &Zone) -> ZoneSummary {
+	ZoneSummary {
+		name: zone.name.clone(),
+		valve: zone.valve.clone(),
+		sensors: zone.sensors.clone(),
+		threshold: zone.threshold,
+		irrigate_seconds: zone.irrigate_seconds
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use chrono::{Duration as ChronoDuration, TimeZone};
+	use common::auth::Role;
+	use std::time::Duration;
+	use std::sync::{Arc, RwLock};
+	use std::sync::mpsc::channel;
+	use crate::clock::VirtualClock;
+	use crate::controller::History;
+	use crate::event::moisture::MoistureEvent;
+
+	fn zone(name: &str, sensors: &[&str]) -> Zone {
+		Zone {
+			name: name.to_string(),
+			valve: name.to_string(),
+			sensors: sensors.iter().map(|s| s.to_string()).collect(),
+			threshold: 500,
+			check: vec![],
+			irrigate_seconds: 60
+		}
+	}
+
+	fn api(history: SharedHistory, now: DateTime<Utc>, tx: Sender<Event>) -> Api {
+		let valves = Arc::new(RwLock::new(vec![]));
+		Api::new(vec![zone("beds", &["tomato"])], history, valves, SinkMetrics::default(), Arc::new(VirtualClock::new(now)), tx)
+	}
+
+	fn session(role: Role) -> Session {
+		Session { name: "test".to_string(), role }
+	}
+
+	fn body(reply: Reply) -> (u16, String) {
+		(reply.status, String::from_utf8(reply.body).unwrap())
+	}
+
+	#[test]
+	fn routes_read_history_and_send_irrigation_requests() {
+		let now = Utc.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap();
+		let history = History::shared(100);
+		{
+			let mut history = history.write().unwrap();
+			for (minutes, value) in [(90, 400), (30, 600), (10, 700)].iter() {
+				let time = now - ChronoDuration::minutes(*minutes);
+				history.record_moisture(&MoistureEvent { time, name: "tomato".to_string(), value: *value });
+			}
+			history.record_irrigation("beds", now - ChronoDuration::minutes(20), Duration::from_secs(90));
+		}
+		let (tx, rx) = channel();
+		let api = api(history, now, tx);
+		let operator = session(Role::Operator);
+
+		assert_eq!(
+			body(api.handle(&operator, &Method::Get, &["zone", "list"])),
+			(200, "[{\"name\":\"beds\",\"valve\":\"beds\",\"sensors\":[\"tomato\"],\"threshold\":500,\"irrigate_seconds\":60}]".to_string())
+		);
+		assert_eq!(
+			body(api.handle(&operator, &Method::Get, &["zone", "beds", "moisture", "-3600", "-900"])),
+			(200, "[[\"tomato\",[[1591011000,600]]]]".to_string())
+		);
+		assert_eq!(
+			body(api.handle(&operator, &Method::Get, &["zone", "beds", "irrigation", "-3600", "-0"])),
+			(200, "[{\"start\":1591011600,\"duration\":{\"secs\":90,\"nanos\":0}}]".to_string())
+		);
+		assert_eq!(body(api.handle(&operator, &Method::Get, &["weather", "-3600", "-0"])), (200, "[]".to_string()));
+		assert_eq!(
+			body(api.handle(&operator, &Method::Get, &["metrics"])),
+			(200, "{\"sinks\":[],\"backlog\":{\"expired\":0,\"overflow\":0}}".to_string())
+		);
+		assert_eq!(api.handle(&operator, &Method::Get, &["zone", "beds", "moisture", "an hour", "-0"]).status, 400);
+		assert_eq!(api.handle(&operator, &Method::Get, &["zone", "roses", "moisture", "-3600", "-0"]).status, 404);
+
+		assert_eq!(api.handle(&operator, &Method::Post, &["zone", "beds", "irrigate"]).status, 202);
+		assert_eq!(api.handle(&operator, &Method::Post, &["zone", "roses", "irrigate"]).status, 404);
+		assert!(matches!(rx.try_recv(), Ok(Event::IrrigateEvent(name)) if name == "beds"));
+		assert!(rx.try_recv().is_err());
+	}
+
+	#[test]
+	fn ranges_older_than_the_history_say_where_it_starts() {
+		let now = Utc.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap();
+		let history = History::shared(2);
+		{
+			let mut history = history.write().unwrap();
+			for (minutes, value) in [(90, 400), (30, 600), (10, 700)].iter() {
+				let time = now - ChronoDuration::minutes(*minutes);
+				history.record_moisture(&MoistureEvent { time, name: "tomato".to_string(), value: *value });
+			}
+		}
+		let (tx, _rx) = channel();
+		let api = api(history, now, tx);
+		let operator = session(Role::Operator);
+
+		let reply = api.handle(&operator, &Method::Get, &["zone", "beds", "moisture", "-3600", "-0"]);
+		assert_eq!(reply.headers, vec![(COVERED_FROM_HEADER, "1591011000".to_string())]);
+		assert!(api.handle(&operator, &Method::Get, &["zone", "beds", "moisture", "-900", "-0"]).headers.is_empty());
+	}
+
+	#[test]
+	fn read_only_sessions_cannot_irrigate() {
+		let (tx, rx) = channel();
+		let now = Utc.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap();
+		let api = api(History::shared(100), now, tx);
+		let viewer = session(Role::ReadOnly);
+
+		assert_eq!(
+			body(api.handle(&viewer, &Method::Get, &["session"])),
+			(200, "{\"name\":\"test\",\"role\":\"read_only\"}".to_string())
+		);
+		assert_eq!(api.handle(&viewer, &Method::Get, &["zone", "list"]).status, 200);
+		assert_eq!(api.handle(&viewer, &Method::Post, &["zone", "beds", "irrigate"]).status, 403);
+		assert_eq!(api.handle(&viewer, &Method::Post, &["valves", "stop"]).status, 403);
+		assert!(rx.try_recv().is_err());
+	}
+
+	#[test]
+	fn irrigation_can_be_timed_and_stopped() {
+		let (tx, rx) = channel();
+		let api = api(History::shared(100), Utc.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap(), tx);
+		let operator = session(Role::Operator);
+
+		assert_eq!(api.handle(&operator, &Method::Post, &["zone", "beds", "irrigate", "300"]).status, 202);
+		assert_eq!(api.handle(&operator, &Method::Post, &["zone", "beds", "irrigate", "0"]).status, 400);
+		assert_eq!(api.handle(&operator, &Method::Post, &["zone", "beds", "irrigate", "86400"]).status, 400);
+		assert_eq!(api.handle(&operator, &Method::Post, &["zone", "beds", "stop"]).status, 202);
+		assert_eq!(api.handle(&operator, &Method::Post, &["zone", "roses", "stop"]).status, 404);
+		assert_eq!(api.handle(&operator, &Method::Post, &["valves", "stop"]).status, 202);
+		assert_eq!(body(api.handle(&operator, &Method::Get, &["valves"])), (200, "[]".to_string()));
+
+		assert!(matches!(rx.try_recv(), Ok(Event::IrrigateForEvent(i)) if i.zone == "beds" && i.seconds == 300));
+		assert!(matches!(rx.try_recv(), Ok(Event::StopIrrigationEvent(name)) if name == "beds"));
+		assert!(matches!(rx.try_recv(), Ok(Event::StopAllIrrigationEvent)));
+		assert!(rx.try_recv().is_err());
+	}
+}
@@ -0,0 +1,83 @@
+use common::auth::{Role, Session};
+use sha2::{Digest, Sha256};
+use tiny_http::Request;
+
+use crate::settings::TokenSettings;
+use super::percent_decode;
+
+// Checks the token a request carries against the hashes in the settings.
+// Browsers can't set headers on an event stream, so the token may also come
+// as a `token` query parameter. Without any tokens the API is read-only, as
+// it is served on every interface by default.
+pub struct Auth {
+	tokens: Vec<TokenSettings>
+}
+
+impl Auth {
+	pub fn new(tokens: &[TokenSettings]) -> Self {
+		if tokens.is_empty() {
+			warn!("No API tokens configured; the API is read-only until an operator token is added to the http settings");
+		}
+		Auth { tokens: tokens.to_vec() }
+	}
+
+	pub fn session(&self, request: &Request) -> Option<Session> {
+		self.session_for(token(request).as_deref())
+	}
+
+	fn session_for(&self, token: Option<&str>) -> Option<Session> {
+		if self.tokens.is_empty() {
+			return Some(Session { name: "anonymous".to_string(), role: Role::ReadOnly });
+		}
+		let hash = sha256(token?);
+		self.tokens.iter()
+			.find(|t| t.sha256.eq_ignore_ascii_case(&hash))
+			.map(|t| Session { name: t.name.clone(), role: t.role })
+	}
+}
+
+fn token(request: &Request) -> Option<String> {
+	let bearer = request.headers().iter()
+		.find(|h| h.field.equiv("Authorization"))
+		.and_then(|h| h.value.as_str().strip_prefix("Bearer "))
+		.map(|token| token.trim().to_string());
+	bearer.or_else(|| query_token(request.url()))
+}
+
+fn query_token(url: &str) -> Option<String> {
+	let (_, query) = url.split_once('?')?;
+	query.split('&')
+		.find_map(|param| param.strip_prefix("token="))
+		.and_then(percent_decode)
+}
+
+fn sha256(token: &str) -> String {
+	Sha256::digest(token.as_bytes()).iter()
+		.map(|b| format!("{:02x}", b))
+		.collect()
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn token(name: &str, token: &str, role: Role) -> TokenSettings {
+		TokenSettings { name: name.to_string(), sha256: sha256(token), role }
+	}
+
+	#[test]
+	fn tokens_are_matched_by_their_hash() {
+		assert_eq!(sha256("secret"), "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b");
+
+		let auth = Auth::new(&[token("wall", "display", Role::ReadOnly), token("neil", "secret", Role::Operator)]);
+		assert_eq!(auth.session_for(Some("display")), Some(Session { name: "wall".to_string(), role: Role::ReadOnly }));
+		assert_eq!(auth.session_for(Some("secret")).map(|s| s.can_operate()), Some(true));
+		assert_eq!(auth.session_for(Some("guess")), None);
+		assert_eq!(auth.session_for(None), None);
+		assert_eq!(Auth::new(&[]).session_for(None).map(|s| s.role), Some(Role::ReadOnly));
+		assert_eq!(Auth::new(&[]).session_for(Some("secret")).map(|s| s.can_operate()), Some(false));
+
+		assert_eq!(query_token("/api/events?since=0&token=a%2Fb"), Some("a/b".to_string()));
+		assert_eq!(query_token("/api/events"), None);
+	}
+}
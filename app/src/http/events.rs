@@ -0,0 +1,72 @@
+use tiny_http::Request;
+
+use std::io::{self, Write};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::thread::Builder;
+use std::time::Duration;
+
+use crate::event::Event;
+
+// A comment is sent when there have been no events for this long, so a
+// client that has gone away is noticed and its thread ends
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+const HEADER: &str = "HTTP/1.1 200 OK\r\n\
+	Content-Type: text/event-stream\r\n\
+	Cache-Control: no-cache\r\n\
+	Connection: keep-alive\r\n\r\n";
+
+// Streams events to a client as Server-Sent Events. Each client has a thread
+// of its own, as the connection stays open for as long as it is watching.
+pub fn stream(request: Request, rx: Receiver<Event>) {
+	let spawned = Builder::new()
+		.name("events".to_string())
+		.spawn(move || {
+			let mut writer = request.into_writer();
+			if let Err(e) = send_all(&mut writer, rx) {
+				debug!("event stream closed: {}", e);
+			}
+		});
+	if let Err(e) = spawned {
+		warn!("cannot start event stream: {}", e);
+	}
+}
+
+fn send_all<W: Write>(writer: &mut W, rx: Receiver<Event>) -> io::Result<()> {
+	writer.write_all(HEADER.as_bytes())?;
+	writer.flush()?;
+	loop {
+		let message = match rx.recv_timeout(KEEP_ALIVE) {
+			Ok(event) => match serde_json::to_string(&event) {
+				Ok(json) => format!("data: {}\n\n", json),
+				Err(e) => {
+					warn!("failed to encode {:?}: {}", event, e);
+					continue;
+				}
+			},
+			Err(RecvTimeoutError::Timeout) => ":\n\n".to_string(),
+			Err(RecvTimeoutError::Disconnected) => return Ok(())
+		};
+		writer.write_all(message.as_bytes())?;
+		writer.flush()?;
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use std::sync::mpsc::channel;
+
+	#[test]
+	fn events_are_sent_as_json_data_lines() {
+		let (tx, rx) = channel();
+		tx.send(Event::IrrigateEvent("beds".to_string())).unwrap();
+		drop(tx);
+
+		let mut sent = vec![];
+		send_all(&mut sent, rx).unwrap();
+		let sent = String::from_utf8(sent).unwrap();
+		assert!(sent.starts_with("HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n"));
+		assert!(sent.ends_with("\r\n\r\ndata: {\"type\":\"irrigate_event\",\"event\":\"beds\"}\n\n"));
+	}
+}
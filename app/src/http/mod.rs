@@ -0,0 +1,176 @@
+mod api;
+mod auth;
+mod events;
+
+use common::auth::Session;
+use serde::Serialize;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread::Builder;
+
+use crate::controller::Broadcast;
+use crate::settings::HttpSettings;
+
+pub use api::Api;
+use auth::Auth;
+
+pub struct Reply {
+	status: u16,
+	content_type: &'static str,
+	headers: Vec<(&'static str, String)>,
+	body: Vec<u8>
+}
+
+impl Reply {
+	fn new(status: u16, content_type: &'static str, body: Vec<u8>) -> Self {
+		Reply { status, content_type, headers: vec![], body }
+	}
+
+	fn json<T: Serialize>(value: &T) -> Self {
+		match serde_json::to_vec(value) {
+			Ok(body) => Reply::new(200, "application/json", body),
+			Err(e) => Reply::text(500, &format!("failed to encode response: {}", e))
+		}
+	}
+
+	fn text(status: u16, text: &str) -> Self {
+		Reply::new(status, "text/plain; charset=utf-8", text.as_bytes().to_vec())
+	}
+
+	fn with_header(mut self, name: &'static str, value: String) -> Self {
+		self.headers.push((name, value));
+		self
+	}
+
+	fn not_found() -> Self {
+		Reply::text(404, "not found")
+	}
+}
+
+// Serves the API and the web UI from a thread of its own. Requests are
+// handled one at a time, which is plenty for a greenhouse; only the event
+// streams, which stay open, are handed off to threads of their own.
+pub fn start(settings: &HttpSettings, api: Api, broadcast: Broadcast) -> Result<(), Box<dyn Error>> {
+	let server = Server::http(&settings.address)
+		.map_err(|e| format!("cannot listen on {}: {}", settings.address, e))?;
+	let ui_path = PathBuf::from(&settings.ui_path);
+	let auth = Auth::new(&settings.tokens);
+	info!("Serving HTTP on {}", settings.address);
+
+	Builder::new()
+		.name("http".to_string())
+		.spawn(move || {
+			for request in server.incoming_requests() {
+				// The query is left out of the logs as it may hold a token
+				let path = request.url().split('?').next().unwrap_or_default().to_string();
+				let session = auth.session(&request);
+				if is_event_stream(request.method(), &path) && session.is_some() {
+					debug!("{} {} -> event stream", request.method(), path);
+					events::stream(request, broadcast.subscribe());
+					continue;
+				}
+				let reply = handle(&api, &ui_path, request.method(), &path, session.as_ref());
+				debug!("{} {} -> {}", request.method(), path, reply.status);
+				respond(request, reply);
+			}
+		})?;
+	Ok(())
+}
+
+fn is_event_stream(method: &Method, path: &str) -> bool {
+	*method == Method::Get && path == "/api/events"
+}
+
+fn handle(api: &Api, ui_path: &Path, method: &Method, path: &str, session: Option<&Session>) -> Reply {
+	let segments = match path.split('/').skip(1).map(percent_decode).collect::<Option<Vec<String>>>() {
+		Some(segments) => segments,
+		None => return Reply::text(400, "malformed path")
+	};
+	let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+
+	match (method, segments.as_slice()) {
+		(_, ["api", route @ ..]) => match session {
+			Some(session) => api.handle(session, method, route),
+			None => Reply::text(401, "a valid API token is needed")
+		},
+		(Method::Get, [""]) => static_file(ui_path, "index.html"),
+		(Method::Get, [file]) => static_file(ui_path, file),
+		_ => Reply::not_found()
+	}
+}
+
+// Only files directly in the UI directory are served
+fn static_file(ui_path: &Path, name: &str) -> Reply {
+	if name.starts_with('.') || name.contains('/') || name.contains('\\') {
+		return Reply::not_found();
+	}
+	match fs::read(ui_path.join(name)) {
+		Ok(body) => Reply::new(200, content_type(name), body),
+		Err(_) => Reply::not_found()
+	}
+}
+
+fn content_type(name: &str) -> &'static str {
+	match name.rsplit('.').next() {
+		Some("html") => "text/html; charset=utf-8",
+		Some("css") => "text/css",
+		Some("js") => "application/javascript",
+		Some("wasm") => "application/wasm",
+		Some("json") => "application/json",
+		Some("svg") => "image/svg+xml",
+		Some("png") => "image/png",
+		Some("ico") => "image/x-icon",
+		_ => "application/octet-stream"
+	}
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+	let bytes = s.as_bytes();
+	let mut decoded = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+	while i < bytes.len() {
+		if bytes[i] == b'%' {
+			let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+			decoded.push(u8::from_str_radix(hex, 16).ok()?);
+			i += 3;
+		} else {
+			decoded.push(bytes[i]);
+			i += 1;
+		}
+	}
+	String::from_utf8(decoded).ok()
+}
+
+fn respond(request: Request, reply: Reply) {
+	let content_type = Header::from_bytes(&b"Content-Type"[..], reply.content_type.as_bytes())
+		.expect("static content type header");
+	let mut response = Response::from_data(reply.body)
+		.with_status_code(reply.status)
+		.with_header(content_type);
+	for (name, value) in reply.headers {
+		match Header::from_bytes(name.as_bytes(), value.as_bytes()) {
+			Ok(header) => response.add_header(header),
+			Err(_) => warn!("dropping malformed {} header", name)
+		}
+	}
+	if let Err(e) = request.respond(response) {
+		warn!("failed to send HTTP response: {}", e);
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn paths_are_decoded_and_confined_to_the_ui_directory() {
+		assert_eq!(percent_decode("tomato%20bed%2F1"), Some("tomato bed/1".to_string()));
+		assert_eq!(percent_decode("bad%2"), None);
+		assert_eq!(static_file(Path::new("/etc"), "..").status, 404);
+		assert_eq!(static_file(Path::new("/"), "etc/passwd").status, 404);
+		assert_eq!(content_type("pirrigator-ui_bg.wasm"), "application/wasm");
+	}
+}
@@ -4,10 +4,17 @@
 mod button;
 mod database;
 mod event;
+mod http;
 mod moisture;
+mod sink;
 mod valve;
 mod weather;
 
+#[cfg(test)]
+mod testing;
+
+pub mod clock;
 pub mod controller;
+pub mod hardware;
 pub mod pirrigator;
 pub mod settings;
@@ -1,16 +1,13 @@
 use std::error::Error;
 use std::thread;
 use std::thread::{JoinHandle, sleep};
-use std::str::FromStr;
 use std::sync::mpsc::Sender;
-use std::time::{SystemTime, Duration};
-
-use chrono::Utc;
-use mcp3xxx::{AnalogIn, MCPDevice, SharedMCPDevice};
-use rustpi_io::gpio::*;
+use std::time::Duration;
 
+use crate::clock::SharedClock;
 use crate::event::Event;
 use crate::event::moisture::{Measurement, MoistureEvent};
+use crate::hardware::{AnalogChannel, AnalogDevice, DigitalOutput, Hardware};
 use crate::settings::{ADCSettings, MoistureSensorSettings};
 
 const CALIBRATED_WET: Measurement = 100;
@@ -26,7 +23,7 @@ pub struct MoistureSensor {
 
 struct Sensor {
 	name: String,
-	channel: AnalogIn,
+	channel: Box<dyn AnalogChannel>,
 	pub min_reading: Measurement,
 	pub max_reading: Measurement
 }
@@ -40,14 +37,13 @@ impl Drop for MoistureSensor {
 }
 
 impl Sensor {
-	fn new(mcp: SharedMCPDevice, settings: &MoistureSensorSettings) -> Result<Self, Box<dyn Error>> {
-		let analog = AnalogIn::single(mcp, settings.channel)?;
-		Ok(Sensor { 
+	fn new(channel: Box<dyn AnalogChannel>, settings: &MoistureSensorSettings) -> Self {
+		Sensor { 
 			name: settings.name.clone(), 
-			channel: analog,
+			channel,
 			min_reading: settings.min_reading,
 			max_reading: settings.max_reading
-		})
+		}
 	}
 }
 
@@ -95,53 +91,55 @@ fn calibrate(m: Measurement, min: Measurement, max: Measurement) -> Measurement
 	}
 }
 
-fn collect(enable: &GPIO, samples: &mut Vec<Sample>, period: Duration) {
-	let until = SystemTime::now() + period;
+fn collect(enable: &mut dyn DigitalOutput, samples: &mut Vec<Sample>, clock: &SharedClock, period: Duration) {
+	let until = clock.now() + chrono::Duration::from_std(period).unwrap();
 
-	while SystemTime::now() < until {
-		enable.set(GPIOData::High).unwrap();
+	while clock.now() < until {
+		enable.set(true).unwrap();
 		sleep(Duration::from_millis(20));
 
 		for ref mut sample in &mut *samples {
 			sample.collect();
 		}
 
-		enable.set(GPIOData::Low).unwrap();
-		sleep(Duration::from_secs(SECONDS_BETWEEN_SAMPLES));
+		enable.set(false).unwrap();
+		clock.sleep(Duration::from_secs(SECONDS_BETWEEN_SAMPLES));
 	}	
 }
 
-fn report(samples: Vec<Sample>, channel: &Sender<Event>) {
+fn report(samples: Vec<Sample>, clock: &SharedClock, channel: &Sender<Event>) {
 	for sample in samples {
 		match sample.mean().map(|m| calibrate(m, sample.sensor.min_reading, sample.sensor.max_reading)) {
 			None => {
 				error!("No samples collected for moisture sensor {}", sample.sensor.name);
 			},
 			Some(value) => {
-				send_event(sample.sensor, value, &channel);
+				send_event(sample.sensor, value, clock, &channel);
 			}
 		}
 	}
 }
 
-fn main(mcp: MCPDevice, enable: GPIO, settings: Vec<MoistureSensorSettings>, channel: Sender<Event>, period: Duration) {
+fn main(adc: Box<dyn AnalogDevice>, mut enable: Box<dyn DigitalOutput>, settings: Vec<MoistureSensorSettings>, clock: SharedClock, channel: Sender<Event>, period: Duration) {
 	info!("Starting {} moisture sensor(s)", settings.len());
-	let shared_mcp = mcp.share();
-	let sensors: Vec<Sensor> = settings.iter()
-		.map(|sensor| Sensor::new(shared_mcp.clone(), &sensor).unwrap())
+	let channels: Vec<u8> = settings.iter().map(|sensor| sensor.channel).collect();
+	let sensors: Vec<Sensor> = adc.channels(&channels).unwrap()
+		.into_iter()
+		.zip(settings.iter())
+		.map(|(analog, sensor)| Sensor::new(analog, &sensor))
 		.collect();
 
 	info!("Started {} moisture sensor(s)", sensors.len());
 	loop {
 		let mut samples: Vec<Sample> = sensors.iter().map(|s| Sample::new(s)).collect();
-		collect(&enable, &mut samples, period);
-		report(samples, &channel);
+		collect(enable.as_mut(), &mut samples, &clock, period);
+		report(samples, &clock, &channel);
 	}
 }
 
-fn send_event(sensor: &Sensor, value: u16, channel: &Sender<Event>) {
+fn send_event(sensor: &Sensor, value: u16, clock: &SharedClock, channel: &Sender<Event>) {
 	let event = MoistureEvent { 
-		time: Utc::now(),
+		time: clock.now(),
 		name: sensor.name.clone(),
 		value
 	};
@@ -152,17 +150,15 @@ fn send_event(sensor: &Sensor, value: u16, channel: &Sender<Event>) {
 }
 
 impl MoistureSensor {
-	pub fn new(adc: &ADCSettings, sensors: &Vec<MoistureSensorSettings>, channel: Sender<Event>) -> Result<MoistureSensor, Box<dyn Error>> {
-		let device = mcp3xxx::device_from_str(&adc.device)?;
-		let device_type = FromStr::from_str(&adc.device_type)?;
-		let mcp = MCPDevice::new(device, device_type, adc.chip_select_gpio)?;
-		let enable = GPIO::new(adc.enable_gpio, GPIOMode::Write)?;
+	pub fn new(adc: &ADCSettings, sensors: &Vec<MoistureSensorSettings>, hardware: &dyn Hardware, clock: SharedClock, channel: Sender<Event>) -> Result<MoistureSensor, Box<dyn Error>> {
+		let device = hardware.analog_device(adc)?;
+		let enable = hardware.digital_output(adc.enable_gpio)?;
 
 		let period = Duration::from_secs(adc.update);
 		let sensors = sensors.to_vec();
 		let thread = thread::Builder::new()
 			.name("moisture".to_string())
-			.spawn(move || { main(mcp, enable, sensors, channel, period); })?;
+			.spawn(move || { main(device, enable, sensors, clock, channel, period); })?;
 		Ok(MoistureSensor { 
 			thread: Some(thread)
 		})
@@ -4,8 +4,12 @@ use std::thread::{JoinHandle, sleep, spawn};
 use std::time::Duration;
 
 use crate::button::Buttons;
-use crate::controller::{Controller, Scheduler};
+use crate::clock;
+use crate::controller::{Broadcast, Controller, History, Journal, Scheduler};
 use crate::database::Database;
+use crate::hardware;
+use crate::http;
+use crate::sink;
 use crate::moisture::MoistureSensor;
 use crate::settings::Settings;
 use crate::valve::Valves;
@@ -34,38 +38,61 @@ impl Drop for Pirrigator {
 impl Pirrigator {
 	pub fn new(s: Settings) -> Result<Pirrigator, Box<dyn Error>> {
 		let (tx, rx) = mpsc::channel();
-		let db = Database::new(&s.database);
+		let database = traverse(&s.database, &|d| Database::new(d))?;
+		let sinks = sink::from_settings(&s);
+		let clock = clock::from_settings(&s.clock);
+		let hardware = hardware::from_settings(&s, clock.clone());
 
 		let weather = traverse(
 			&s.weather,
-			&|w| WeatherSensor::new(&w, tx.clone())
+			&|w| WeatherSensor::new(&w, hardware.as_ref(), clock.clone(), tx.clone())
 		)?;
 
 		let moisture = traverse(
 			&s.adc,
-			&|adc| MoistureSensor::new(&adc, &s.moisture, tx.clone())
+			&|adc| MoistureSensor::new(&adc, &s.moisture, hardware.as_ref(), clock.clone(), tx.clone())
 		)?;
 
-		let buttons = Buttons::new(&s.buttons, tx.clone())?;
+		let buttons = Buttons::new(&s.buttons, hardware.as_ref(), clock.clone(), tx.clone())?;
 		
-		let valves = Valves::new(&s.valves, tx.clone())?;
+		let valves = Valves::new(&s.valves, hardware.as_ref(), clock.clone(), tx.clone())?;
 
 		let scheduler = Scheduler::new(
 			&s.controller.location,
 			&s.controller.zones,
+			clock.clone(),
 			tx.clone()
 		)?;
 
+		let history = History::shared(s.history.capacity);
+		let broadcast = Broadcast::default();
+		if let Some(settings) = &s.http {
+			let api = http::Api::new(s.controller.zones.clone(), history.clone(), valves.status(), sinks.metrics(), clock.clone(), tx.clone());
+			http::start(settings, api, broadcast.clone())?;
+		}
+
+		let journal = Journal::open(&s.history).unwrap_or_else(|e| {
+			warn!("history journal unavailable: {}", e);
+			None
+		});
+
 		let mut controller = Controller {
 			settings: s.controller.clone(),
+			clock,
 			scheduler,
-			database: db,
+			database,
+			sinks,
+			history,
+			broadcast,
+			journal,
 			weather,
 			moisture,
 			buttons,
 			valves
 		};
 
+		controller.replay_journal(s.history.replay_hours);
+
 		let thread = spawn(move || controller.run(rx));
 
 		return Ok(Pirrigator { 
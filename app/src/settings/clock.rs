@@ -0,0 +1,11 @@
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ClockSettings {
+	pub speed: u32
+}
+
+impl Default for ClockSettings {
+	fn default() -> Self {
+		ClockSettings { speed: 1 }
+	}
+}
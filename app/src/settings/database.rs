@@ -1,8 +1,56 @@
-#[derive(Debug, Deserialize, PartialEq, Eq)]
+// What the backlog drops once it reaches `backlog_max_kb`
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BacklogOverflow {
+	#[default]
+	DropOldest,
+	DropNewest,
+	Downsample
+}
+
+// InfluxDB 1.x has databases rather than organisations and buckets; with
+// `version: v1` the bucket names the database, optionally followed by `/`
+// and the retention policy
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DatabaseVersion {
+	V1,
+	#[default]
+	V2
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 pub struct DatabaseSettings {
 	pub url: String,
+    #[serde(default)]
+    pub version: DatabaseVersion,
+    #[serde(default)]
     pub token: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
     pub backlog_path: Option<String>,
+    #[serde(default = "default_backlog_max_kb")]
+    pub backlog_max_kb: Option<u64>,
+    #[serde(default = "default_backlog_max_hours")]
+    pub backlog_max_hours: Option<u64>,
+    #[serde(default)]
+    pub backlog_overflow: BacklogOverflow,
+    #[serde(default)]
     pub organisation: String,
-    pub bucket: String
+    pub bucket: String,
+    #[serde(default)]
+    pub create_bucket: bool,
+    pub bucket_retention_days: Option<u64>,
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    #[serde(default = "default_flush_seconds")]
+    pub flush_seconds: u64
 }
+
+fn default_queue_size() -> usize { 1000 }
+fn default_batch_size() -> usize { 100 }
+fn default_flush_seconds() -> u64 { 10 }
+fn default_backlog_max_kb() -> Option<u64> { Some(16 * 1024) }
+fn default_backlog_max_hours() -> Option<u64> { Some(7 * 24) }
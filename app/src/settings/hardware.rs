@@ -0,0 +1,13 @@
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HardwareBackend {
+	Rpi,
+	Memory,
+	Simulation
+}
+
+impl Default for HardwareBackend {
+	fn default() -> Self {
+		HardwareBackend::Rpi
+	}
+}
@@ -0,0 +1,24 @@
+// Recent events are kept in memory for watering decisions and journalled to
+// `path` so they survive a restart. The journal is rotated into at most
+// `max_files` files of `max_file_kb` each, to go easy on the SD card.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct HistorySettings {
+	pub capacity: usize,
+	pub path: Option<String>,
+	pub max_file_kb: u64,
+	pub max_files: usize,
+	pub replay_hours: u32
+}
+
+impl Default for HistorySettings {
+	fn default() -> Self {
+		HistorySettings {
+			capacity: 1440,
+			path: Some("/var/lib/pirrigator/history".to_string()),
+			max_file_kb: 256,
+			max_files: 4,
+			replay_hours: 24
+		}
+	}
+}
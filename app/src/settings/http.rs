@@ -0,0 +1,26 @@
+use common::auth::Role;
+
+// The HTTP API and the web UI. `ui_path` is the directory holding the built
+// UI: `index.html`, `pirrigator.css` and the wasm-pack output. With no
+// `tokens` anyone who can reach the API can read it, but nobody can irrigate.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct HttpSettings {
+	#[serde(default = "default_address")]
+	pub address: String,
+	#[serde(default = "default_ui_path")]
+	pub ui_path: String,
+	#[serde(default)]
+	pub tokens: Vec<TokenSettings>
+}
+
+// An API token, kept as the hex SHA-256 of the token so the settings file
+// doesn't give it away
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct TokenSettings {
+	pub name: String,
+	pub sha256: String,
+	pub role: Role
+}
+
+fn default_address() -> String { "0.0.0.0:8000".to_string() }
+fn default_ui_path() -> String { "/var/lib/pirrigator/ui".to_string() }
@@ -4,23 +4,45 @@ extern crate serde;
 pub mod controller;
 
 mod button;
+mod clock;
 mod database;
+mod hardware;
+mod history;
+mod http;
 mod moisture;
+mod simulation;
+mod sink;
 mod valve;
 mod weather;
 
 pub use button::ButtonSettings;
-pub use database::DatabaseSettings;
+pub use clock::ClockSettings;
+pub use database::{BacklogOverflow, DatabaseSettings, DatabaseVersion};
+pub use hardware::HardwareBackend;
+pub use history::HistorySettings;
+pub use http::{HttpSettings, TokenSettings};
 pub use moisture::{ADCSettings, MoistureSensorSettings};
+pub use simulation::SimulationSettings;
+pub use sink::{MqttSettings, SinkSettings};
 pub use valve::ValveSettings;
 pub use weather::WeatherSensorSettings;
 
 use config::{Config, ConfigError, File, FileFormat};
 use controller::ControllerSettings;
 
-#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Deserialize, PartialEq)]
 pub struct Settings {
-	pub database: DatabaseSettings,
+	#[serde(default)]
+	pub hardware: HardwareBackend,
+	pub simulation: Option<SimulationSettings>,
+	#[serde(default)]
+	pub clock: ClockSettings,
+	pub database: Option<DatabaseSettings>,
+	#[serde(default = "sink::default_sinks")]
+	pub sinks: Vec<SinkSettings>,
+	#[serde(default)]
+	pub history: HistorySettings,
+	pub http: Option<HttpSettings>,
 	pub controller: ControllerSettings,
 	pub weather: Option<WeatherSensorSettings>,
 	pub adc: Option<ADCSettings>,
@@ -0,0 +1,39 @@
+// Soil water content is volumetric (0.0 - 1.0); depths and rates are in
+// millimetres of water, temperatures in Celsius and humidity in percent.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SimulationSettings {
+	pub root_depth: f64,
+	pub saturation: f64,
+	pub field_capacity: f64,
+	pub wilting_point: f64,
+	pub initial_moisture: f64,
+	pub irrigation_rate: f64,
+	pub drainage_hours: f64,
+	pub evapotranspiration: f64,
+	pub min_temperature: f64,
+	pub max_temperature: f64,
+	pub min_humidity: f64,
+	pub max_humidity: f64,
+	pub pressure: f64
+}
+
+impl Default for SimulationSettings {
+	fn default() -> Self {
+		SimulationSettings {
+			root_depth: 200.0,
+			saturation: 0.50,
+			field_capacity: 0.35,
+			wilting_point: 0.12,
+			initial_moisture: 0.30,
+			irrigation_rate: 5.0,
+			drainage_hours: 1.0,
+			evapotranspiration: 0.25,
+			min_temperature: 12.0,
+			max_temperature: 28.0,
+			min_humidity: 45.0,
+			max_humidity: 90.0,
+			pressure: 1013.25
+		}
+	}
+}
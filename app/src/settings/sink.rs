@@ -0,0 +1,31 @@
+// Where sensor readings and irrigations are sent, see `sink::EventSink`
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SinkSettings {
+	// Writes to the database set up in the `database` section
+	Influxdb,
+	// See `sink::file::FileSink`
+	File { path: String },
+	// See `sink::mqtt::MqttSink`
+	Mqtt(MqttSettings),
+	// See `sink::stdout::StdoutSink`
+	Stdout
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct MqttSettings {
+	pub host: String,
+	#[serde(default = "default_mqtt_port")]
+	pub port: u16,
+	#[serde(default = "default_mqtt_client_id")]
+	pub client_id: String,
+	#[serde(default = "default_mqtt_topic")]
+	pub topic: String,
+	pub username: Option<String>,
+	pub password: Option<String>
+}
+
+pub fn default_sinks() -> Vec<SinkSettings> { vec![SinkSettings::Influxdb] }
+fn default_mqtt_port() -> u16 { 1883 }
+fn default_mqtt_client_id() -> String { "pirrigator".to_string() }
+fn default_mqtt_topic() -> String { "pirrigator".to_string() }
@@ -0,0 +1,33 @@
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::event::Event;
+use super::{EventSink, StoredEvent};
+
+// Appends one JSON object per line
+pub struct FileSink {
+	file: BufWriter<File>
+}
+
+impl FileSink {
+	pub fn open(path: &str) -> Result<Self, Box<dyn Error>> {
+		if let Some(dir) = Path::new(path).parent() {
+			fs::create_dir_all(dir)?;
+		}
+		let file = OpenOptions::new().create(true).append(true).open(path)?;
+		Ok(FileSink { file: BufWriter::new(file) })
+	}
+}
+
+impl EventSink for FileSink {
+	fn write(&mut self, events: &[Event]) -> Result<(), Box<dyn Error>> {
+		for event in events.iter().filter_map(StoredEvent::from) {
+			serde_json::to_writer(&mut self.file, &event)?;
+			self.file.write_all(b"\n")?;
+		}
+		self.file.flush()?;
+		Ok(())
+	}
+}
@@ -0,0 +1,200 @@
+mod file;
+mod mqtt;
+mod stdout;
+mod writer;
+
+use influxc::Dropped;
+use std::error::Error;
+use std::time::Duration;
+
+use crate::database::{BacklogMetrics, InfluxSink};
+use crate::event::Event;
+use crate::event::button::ButtonEvent;
+use crate::event::irrigate::IrrigatedEvent;
+use crate::event::moisture::MoistureEvent;
+use crate::event::weather::WeatherEvent;
+use crate::settings::{Settings, SinkSettings};
+
+pub use writer::WriterMetrics;
+use writer::{Writer, WriterProbe};
+
+use file::FileSink;
+use mqtt::MqttSink;
+use stdout::StdoutSink;
+
+// Sinks other than InfluxDB write each event as it comes
+const QUEUE_SIZE: usize = 1000;
+const BATCH_SIZE: usize = 1;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+// Somewhere sensor readings and irrigations are kept. Each sink is written
+// to from its own `Writer`, so it may block or fail.
+pub trait EventSink: Send {
+	fn write(&mut self, events: &[Event]) -> Result<(), Box<dyn Error>>;
+}
+
+// The events worth keeping, tagged with their type when serialised
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StoredEvent<'a> {
+	Button(&'a ButtonEvent),
+	Weather(&'a WeatherEvent),
+	Moisture(&'a MoistureEvent),
+	Irrigated(&'a IrrigatedEvent)
+}
+
+impl<'a> StoredEvent<'a> {
+	pub fn from(event: &'a Event) -> Option<Self> {
+		match event {
+			Event::ButtonEvent(b) => Some(StoredEvent::Button(b)),
+			Event::WeatherEvent(w) => Some(StoredEvent::Weather(w)),
+			Event::MoistureEvent(m) => Some(StoredEvent::Moisture(m)),
+			Event::IrrigatedEvent(i) => Some(StoredEvent::Irrigated(i)),
+			_ => None
+		}
+	}
+
+	pub fn kind(&self) -> &'static str {
+		match self {
+			StoredEvent::Button(_) => "button",
+			StoredEvent::Weather(_) => "weather",
+			StoredEvent::Moisture(_) => "moisture",
+			StoredEvent::Irrigated(_) => "irrigated"
+		}
+	}
+}
+
+// Fans each event out to the queue of every sink
+#[derive(Default)]
+pub struct Sinks {
+	writers: Vec<Writer>,
+	backlog_dropped: Option<Dropped>
+}
+
+impl Sinks {
+	pub fn add(&mut self, name: &str, queue_size: usize, batch_size: usize, interval: Duration, mut sink: Box<dyn EventSink>) -> Result<(), Box<dyn Error>> {
+		let writer = Writer::new(name, queue_size, batch_size, interval, Box::new(move |events| sink.write(events)))?;
+		self.writers.push(writer);
+		Ok(())
+	}
+
+	pub fn store(&self, event: &Event) {
+		if StoredEvent::from(event).is_some() {
+			self.writers.iter().for_each(|writer| writer.send(event));
+		}
+	}
+
+	pub fn metrics(&self) -> SinkMetrics {
+		SinkMetrics {
+			writers: self.writers.iter()
+				.map(|writer| (writer.name().to_string(), writer.probe()))
+				.collect(),
+			backlog_dropped: self.backlog_dropped.clone()
+		}
+	}
+}
+
+// The queue of each sink and what the InfluxDB backlog has had to drop, read
+// by the API while the controller owns the sinks
+#[derive(Clone, Default)]
+pub struct SinkMetrics {
+	writers: Vec<(String, WriterProbe)>,
+	backlog_dropped: Option<Dropped>
+}
+
+impl SinkMetrics {
+	pub fn writers(&self) -> Vec<(&str, WriterMetrics)> {
+		self.writers.iter()
+			.map(|(name, probe)| (name.as_str(), probe.metrics()))
+			.collect()
+	}
+
+	pub fn backlog(&self) -> BacklogMetrics {
+		BacklogMetrics::of(self.backlog_dropped.as_ref())
+	}
+}
+
+// A sink that can't be set up is left out rather than keeping the
+// greenhouse from starting
+pub fn from_settings(settings: &Settings) -> Sinks {
+	let mut sinks = Sinks::default();
+	for sink in settings.sinks.iter() {
+		if let Err(e) = add_sink(&mut sinks, sink, settings) {
+			error!("Failed to set up {:?} sink: {}", sink, e);
+		}
+	}
+	sinks
+}
+
+fn add_sink(sinks: &mut Sinks, sink: &SinkSettings, settings: &Settings) -> Result<(), Box<dyn Error>> {
+	match sink {
+		SinkSettings::Influxdb => {
+			let database = settings.database.as_ref()
+				.ok_or("the influxdb sink needs a database section")?;
+			let sink = InfluxSink::new(database)?;
+			sinks.backlog_dropped = sink.backlog_dropped();
+			sinks.add(
+				"influxdb",
+				database.queue_size,
+				database.batch_size,
+				Duration::from_secs(database.flush_seconds),
+				Box::new(sink)
+			)
+		}
+		SinkSettings::File { path } =>
+			sinks.add(&format!("file {}", path), QUEUE_SIZE, BATCH_SIZE, FLUSH_INTERVAL, Box::new(FileSink::open(path)?)),
+		SinkSettings::Mqtt(mqtt) =>
+			sinks.add(&format!("mqtt {}", mqtt.host), QUEUE_SIZE, BATCH_SIZE, FLUSH_INTERVAL, Box::new(MqttSink::connect(mqtt)?)),
+		SinkSettings::Stdout =>
+			sinks.add("stdout", QUEUE_SIZE, BATCH_SIZE, FLUSH_INTERVAL, Box::new(StdoutSink))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use chrono::{TimeZone, Utc};
+	use std::sync::{Arc, Mutex};
+
+	struct Failing;
+
+	impl EventSink for Failing {
+		fn write(&mut self, _: &[Event]) -> Result<(), Box<dyn Error>> {
+			Err("unreachable".into())
+		}
+	}
+
+	struct Collecting(Arc<Mutex<Vec<String>>>);
+
+	impl EventSink for Collecting {
+		fn write(&mut self, events: &[Event]) -> Result<(), Box<dyn Error>> {
+			for event in events.iter().filter_map(StoredEvent::from) {
+				self.0.lock().unwrap().push(serde_json::to_string(&event)?);
+			}
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn a_failing_sink_does_not_stop_the_others() {
+		let collected = Arc::new(Mutex::new(vec![]));
+		let mut sinks = Sinks::default();
+		sinks.add("failing", 10, 1, FLUSH_INTERVAL, Box::new(Failing)).unwrap();
+		sinks.add("collecting", 10, 1, FLUSH_INTERVAL, Box::new(Collecting(collected.clone()))).unwrap();
+
+		let time = Utc.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap();
+		sinks.store(&Event::MoistureEvent(MoistureEvent { time, name: "tomato".to_string(), value: 612 }));
+		sinks.store(&Event::IrrigateEvent("tomato".to_string()));
+		let metrics = sinks.metrics();
+		drop(sinks);
+
+		assert_eq!(*collected.lock().unwrap(), vec![
+			"{\"type\":\"moisture\",\"time\":\"2020-06-01T12:00:00Z\",\"name\":\"tomato\",\"value\":612}".to_string()
+		]);
+		assert_eq!(metrics.writers(), vec![
+			("failing", WriterMetrics { queue_depth: 0, written: 0, failed: 1, dropped: 0 }),
+			("collecting", WriterMetrics { queue_depth: 0, written: 1, failed: 0, dropped: 0 })
+		]);
+		assert_eq!(metrics.backlog(), BacklogMetrics::default());
+	}
+}
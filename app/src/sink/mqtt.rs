@@ -0,0 +1,57 @@
+use rumqttc::{Client, MqttOptions, QoS};
+
+use std::error::Error;
+use std::thread::{Builder, sleep};
+use std::time::Duration;
+
+use crate::event::Event;
+use crate::settings::MqttSettings;
+use super::{EventSink, StoredEvent};
+
+const REQUEST_QUEUE: usize = 100;
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+// Publishes each event as JSON to `<topic>/<event type>`
+pub struct MqttSink {
+	client: Client,
+	topic: String
+}
+
+impl MqttSink {
+	pub fn connect(settings: &MqttSettings) -> Result<Self, Box<dyn Error>> {
+		let mut options = MqttOptions::new(&settings.client_id, &settings.host, settings.port);
+		options.set_keep_alive(Duration::from_secs(30));
+		if let Some(username) = &settings.username {
+			options.set_credentials(username, settings.password.clone().unwrap_or_default());
+		}
+
+		// The connection only makes progress, and reconnects, while it is
+		// polled; publishing just queues requests for it
+		let (client, mut connection) = Client::new(options, REQUEST_QUEUE);
+		let host = settings.host.clone();
+		Builder::new()
+			.name("mqtt".to_string())
+			.spawn(move || {
+				for notification in connection.iter() {
+					if let Err(e) = notification {
+						warn!("mqtt connection to {} failed: {}", host, e);
+						sleep(RECONNECT_DELAY);
+					}
+				}
+			})?;
+
+		Ok(MqttSink { client, topic: settings.topic.clone() })
+	}
+}
+
+impl EventSink for MqttSink {
+	// Fails rather than blocks once the broker has been away long enough to
+	// fill the request queue
+	fn write(&mut self, events: &[Event]) -> Result<(), Box<dyn Error>> {
+		for event in events.iter().filter_map(StoredEvent::from) {
+			let topic = format!("{}/{}", self.topic, event.kind());
+			self.client.try_publish(topic, QoS::AtLeastOnce, false, serde_json::to_vec(&event)?)?;
+		}
+		Ok(())
+	}
+}
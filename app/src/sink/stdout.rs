@@ -0,0 +1,21 @@
+use std::error::Error;
+use std::io::{self, Write};
+
+use crate::event::Event;
+use super::{EventSink, StoredEvent};
+
+// One JSON object per line, for piping into other tools
+pub struct StdoutSink;
+
+impl EventSink for StdoutSink {
+	fn write(&mut self, events: &[Event]) -> Result<(), Box<dyn Error>> {
+		let stdout = io::stdout();
+		let mut out = stdout.lock();
+		for event in events.iter().filter_map(StoredEvent::from) {
+			serde_json::to_writer(&mut out, &event)?;
+			out.write_all(b"\n")?;
+		}
+		out.flush()?;
+		Ok(())
+	}
+}
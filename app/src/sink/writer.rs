@@ -0,0 +1,216 @@
+use std::error::Error;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::thread::{Builder, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::event::Event;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct WriterMetrics {
+	pub queue_depth: usize,
+	pub written: usize,
+	pub failed: usize,
+	pub dropped: usize
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+	queue_depth: AtomicUsize,
+	written: AtomicUsize,
+	failed: AtomicUsize,
+	dropped: AtomicUsize
+}
+
+impl Counters {
+	fn snapshot(&self) -> WriterMetrics {
+		WriterMetrics {
+			queue_depth: self.queue_depth.load(Ordering::Relaxed),
+			written: self.written.load(Ordering::Relaxed),
+			failed: self.failed.load(Ordering::Relaxed),
+			dropped: self.dropped.load(Ordering::Relaxed)
+		}
+	}
+}
+
+// Reads a writer's counters from other threads, and after it has stopped
+#[derive(Clone)]
+pub struct WriterProbe(Arc<Counters>);
+
+impl WriterProbe {
+	pub fn metrics(&self) -> WriterMetrics {
+		self.0.snapshot()
+	}
+}
+
+// Hands events to a thread that writes them in batches, so a slow or absent
+// sink never holds up the controller or the other sinks. The queue is
+// bounded; when it is full new events are dropped and counted rather than
+// blocking the sender.
+pub struct Writer {
+	name: String,
+	tx: Option<SyncSender<Event>>,
+	counters: Arc<Counters>,
+	thread: Option<JoinHandle<()>>
+}
+
+pub type WriteBatch = Box<dyn FnMut(&[Event]) -> Result<(), Box<dyn Error>> + Send>;
+
+fn main(name: &str, rx: Receiver<Event>, counters: Arc<Counters>, mut write: WriteBatch, batch_size: usize, interval: Duration) {
+	let mut batch = Vec::with_capacity(batch_size);
+	let mut deadline = Instant::now() + interval;
+	loop {
+		let timeout = deadline.saturating_duration_since(Instant::now());
+		let disconnected = match rx.recv_timeout(timeout) {
+			Ok(event) => {
+				counters.queue_depth.fetch_sub(1, Ordering::Relaxed);
+				batch.push(event);
+				false
+			}
+			Err(RecvTimeoutError::Timeout) => false,
+			Err(RecvTimeoutError::Disconnected) => true
+		};
+
+		if batch.len() >= batch_size || Instant::now() >= deadline || disconnected {
+			if !batch.is_empty() {
+				match write(&batch) {
+					Ok(()) => counters.written.fetch_add(batch.len(), Ordering::Relaxed),
+					Err(e) => {
+						warn!("Failed to write {} events to {}: {}", batch.len(), name, e);
+						counters.failed.fetch_add(batch.len(), Ordering::Relaxed)
+					}
+				};
+				batch.clear();
+				debug!("{} writer {:?}", name, counters.snapshot());
+			}
+			deadline = Instant::now() + interval;
+		}
+
+		if disconnected {
+			break;
+		}
+	}
+}
+
+impl Writer {
+	pub fn new(name: &str, queue_size: usize, batch_size: usize, interval: Duration, write: WriteBatch) -> Result<Self, Box<dyn Error>> {
+		let (tx, rx) = sync_channel(queue_size.max(1));
+		let counters = Arc::new(Counters::default());
+		let thread_counters = counters.clone();
+		let thread_name = name.to_string();
+		let thread = Builder::new()
+			.name(name.to_string())
+			.spawn(move || main(&thread_name, rx, thread_counters, write, batch_size.max(1), interval))?;
+		Ok(Writer {
+			name: name.to_string(),
+			tx: Some(tx),
+			counters,
+			thread: Some(thread)
+		})
+	}
+
+	pub fn send(&self, event: &Event) {
+		if let Some(tx) = &self.tx {
+			self.counters.queue_depth.fetch_add(1, Ordering::Relaxed);
+			match tx.try_send(event.clone()) {
+				Ok(()) => {}
+				Err(TrySendError::Full(_)) => {
+					self.counters.queue_depth.fetch_sub(1, Ordering::Relaxed);
+					let dropped = self.counters.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+					warn!("{} write queue full; {} events dropped", self.name, dropped);
+				}
+				Err(TrySendError::Disconnected(_)) => {
+					self.counters.queue_depth.fetch_sub(1, Ordering::Relaxed);
+					error!("{} writer thread has stopped", self.name);
+				}
+			}
+		}
+	}
+
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	pub fn probe(&self) -> WriterProbe {
+		WriterProbe(self.counters.clone())
+	}
+}
+
+impl Drop for Writer {
+	fn drop(&mut self) {
+		self.tx = None;
+		if let Some(thread) = self.thread.take() {
+			thread.join().unwrap();
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use chrono::Utc;
+	use std::sync::Mutex;
+	use std::sync::mpsc::channel;
+	use crate::event::moisture::MoistureEvent;
+
+	fn moisture(value: u16) -> Event {
+		Event::MoistureEvent(MoistureEvent { time: Utc::now(), name: "tomato".to_string(), value })
+	}
+
+	#[test]
+	fn events_are_written_in_batches() {
+		let batches = Arc::new(Mutex::new(vec![]));
+		let written = batches.clone();
+		let writer = Writer::new("test", 10, 3, Duration::from_secs(3600), Box::new(move |events| {
+			written.lock().unwrap().push(events.len());
+			Ok(())
+		})).unwrap();
+
+		(0..7).for_each(|v| writer.send(&moisture(v)));
+		let counters = writer.counters.clone();
+		drop(writer);
+
+		assert_eq!(*batches.lock().unwrap(), vec![3, 3, 1]);
+		assert_eq!(counters.snapshot(), WriterMetrics { queue_depth: 0, written: 7, failed: 0, dropped: 0 });
+	}
+
+	#[test]
+	fn partial_batch_is_written_after_the_interval() {
+		let (tx, rx) = channel();
+		let tx = Mutex::new(tx);
+		let writer = Writer::new("test", 10, 100, Duration::from_millis(20), Box::new(move |events| {
+			tx.lock().unwrap().send(events.len()).unwrap();
+			Ok(())
+		})).unwrap();
+
+		writer.send(&moisture(1));
+		assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(1));
+		assert_eq!(writer.counters.snapshot().written, 1);
+	}
+
+	#[test]
+	fn full_queue_drops_events_and_failures_are_counted() {
+		let (release_tx, release_rx) = channel::<()>();
+		let release_rx = Mutex::new(release_rx);
+		let writer = Writer::new("test", 2, 1, Duration::from_secs(3600), Box::new(move |_| {
+			release_rx.lock().unwrap().recv().unwrap();
+			Err("unreachable".into())
+		})).unwrap();
+
+		// The first event is taken by the writer thread, which then blocks;
+		// two more fill the queue and the rest are dropped
+		writer.send(&moisture(0));
+		while writer.counters.snapshot().queue_depth > 0 {
+			std::thread::yield_now();
+		}
+		(1..6).for_each(|v| writer.send(&moisture(v)));
+		assert_eq!(writer.counters.snapshot().queue_depth, 2);
+		assert_eq!(writer.counters.snapshot().dropped, 3);
+
+		(0..3).for_each(|_| release_tx.send(()).unwrap());
+		let counters = writer.counters.clone();
+		drop(writer);
+		assert_eq!(counters.snapshot().failed, 3);
+	}
+}
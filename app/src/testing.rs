@@ -0,0 +1,32 @@
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+// Scratch directory for a test, removed when dropped so that it is cleaned
+// up even if the test fails
+pub struct TempDir {
+	path: PathBuf
+}
+
+impl TempDir {
+	pub fn new(name: &str) -> Self {
+		let path = std::env::temp_dir().join(format!("pirrigator-{}-{}", name, std::process::id()));
+		let _ = fs::remove_dir_all(&path);
+		fs::create_dir_all(&path).unwrap();
+		TempDir { path }
+	}
+}
+
+impl Deref for TempDir {
+	type Target = Path;
+
+	fn deref(&self) -> &Path {
+		&self.path
+	}
+}
+
+impl Drop for TempDir {
+	fn drop(&mut self) {
+		let _ = fs::remove_dir_all(&self.path);
+	}
+}
@@ -1,15 +1,19 @@
-use chrono::Utc;
-use rustpi_io::gpio::*;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use common::irrigation::{ValveActivity, ValveEvent};
 
+use std::collections::VecDeque;
 use std::error::Error;
-use std::sync::mpsc;
-use std::thread::{JoinHandle, sleep, spawn};
+use std::sync::{Arc, RwLock, mpsc};
+use std::sync::mpsc::RecvTimeoutError;
+use std::thread::{JoinHandle, spawn};
 use std::time::Duration;
 
+use crate::clock::SharedClock;
 use crate::event::{Event, irrigate::IrrigatedEvent};
+use crate::hardware::{DigitalOutput, Hardware};
 use crate::settings::ValveSettings;
 
-const SECONDS_BETWEEN_EVENTS: u64 = 5;
+const SECONDS_BETWEEN_EVENTS: i64 = 5;
 
 enum ValveState {
 	Closed,
@@ -18,21 +22,23 @@ enum ValveState {
 
 enum Command {
 	IrrigateAll { duration: Duration },
-	Irrigate { name: String, duration: Duration }
+	Irrigate { name: String, duration: Duration },
+	Stop { name: String },
+	StopAll
 }
 
 struct Valve {
 	name: String,
-	gpio: GPIO,
+	output: Box<dyn DigitalOutput>,
 	state: ValveState
 }
 
 impl Valve {
-	fn new(s: &ValveSettings) -> Result<Self, Box<dyn Error>> {
-		let gpio = GPIO::new(s.gpio, GPIOMode::Write)?;
-		Ok(Valve { 
+	fn new(s: &ValveSettings, hardware: &dyn Hardware) -> Result<Self, Box<dyn Error>> {
+		let output = hardware.digital_output(s.gpio)?;
+		Ok(Valve {
 			name: s.name.clone(),
-			gpio,
+			output,
 			state: ValveState::Closed
 		})
 	}
@@ -43,7 +49,7 @@ impl Valve {
 				// already open
 			}
 			ValveState::Closed => {
-				self.gpio.set(GPIOData::High)?;
+				self.output.set(true)?;
 				self.state = ValveState::Open;
 			}
 		}
@@ -56,25 +62,12 @@ impl Valve {
 				// already closed
 			}
 			ValveState::Open => {
-				self.gpio.set(GPIOData::Low)?;
+				self.output.set(false)?;
 				self.state = ValveState::Closed;
 			}
 		}
 		Ok(())
 	}
-
-	fn irrigate_event(&mut self, duration: Duration) -> Result<IrrigatedEvent, Box<dyn Error>> {
-		self.open()?;
-		let opened = Utc::now();
-		sleep(duration);
-		self.close()?;
-
-		Ok(IrrigatedEvent {
-			time: opened,
-			name: self.name.clone(),
-			seconds: duration.as_secs() as u32
-		})
-	}
 }
 
 impl Drop for Valve {
@@ -83,39 +76,198 @@ impl Drop for Valve {
 	}
 }
 
-fn irrigate(valve: &mut Valve, duration: Duration, tx: &mpsc::Sender<Event>) {
-	valve.irrigate_event(duration).into_iter().for_each(
-		|event| tx.send(Event::IrrigatedEvent(event)).unwrap()
-	);
-	sleep(Duration::from_secs(SECONDS_BETWEEN_EVENTS))
+pub type SharedValveStatus = Arc<RwLock<Vec<ValveEvent>>>;
+
+struct Running {
+	valve: usize,
+	opened: DateTime<Utc>,
+	until: DateTime<Utc>
 }
 
-fn main(rx: mpsc::Receiver<Command>, event_tx: mpsc::Sender<Event>, mut valves: Vec<Valve>) {
-	loop {
-		let command = rx.recv().unwrap();
+// One valve is open at a time, with a short rest between irrigations. The
+// thread waits for commands no longer than until the next irrigation is due
+// to end or start, so a stop takes effect as soon as it is asked for.
+struct Irrigations {
+	valves: Vec<Valve>,
+	queue: VecDeque<(usize, Duration)>,
+	running: Option<Running>,
+	resting_until: DateTime<Utc>,
+	status: SharedValveStatus,
+	clock: SharedClock,
+	event_tx: mpsc::Sender<Event>
+}
+
+impl Irrigations {
+	fn new(valves: Vec<Valve>, clock: SharedClock, event_tx: mpsc::Sender<Event>) -> Self {
+		let status = valves.iter()
+			.map(|v| ValveEvent { name: v.name.clone(), activity: ValveActivity::Closed })
+			.collect();
+		Irrigations {
+			valves,
+			queue: VecDeque::new(),
+			running: None,
+			resting_until: clock.now(),
+			status: Arc::new(RwLock::new(status)),
+			clock,
+			event_tx
+		}
+	}
+
+	fn handle(&mut self, command: Command) {
 		match command {
 			Command::IrrigateAll { duration } => {
-				for mut valve in &mut valves {
-					irrigate(&mut valve, duration, &event_tx);
+				for valve in 0..self.valves.len() {
+					self.irrigate(valve, duration);
 				}
-			},
+			}
 			Command::Irrigate { name, duration } => {
-				match valves.iter_mut().find(|ref v| v.name == name) {
-					Some(valve) => irrigate(valve, duration, &event_tx),
+				match self.valve_by_name(&name) {
+					Some(valve) => self.irrigate(valve, duration),
+					None => warn!("no such valve {}", name)
+				}
+			}
+			Command::Stop { name } => {
+				match self.valve_by_name(&name) {
+					Some(valve) => self.stop(valve),
 					None => warn!("no such valve {}", name)
 				}
 			}
+			Command::StopAll => {
+				for valve in 0..self.valves.len() {
+					self.stop(valve);
+				}
+			}
+		}
+		self.start_next();
+	}
+
+	// Ends the running irrigation and starts the next, if they are due
+	fn tick(&mut self) {
+		let now = self.clock.now();
+		if matches!(self.running, Some(ref r) if r.until <= now) {
+			self.finish();
+		}
+		self.start_next();
+	}
+
+	// When the running irrigation ends, or the next may start after the rest
+	fn next_deadline(&self) -> Option<DateTime<Utc>> {
+		match self.running {
+			Some(ref running) => Some(running.until),
+			None if !self.queue.is_empty() => Some(self.resting_until),
+			None => None
+		}
+	}
+
+	fn valve_by_name(&self, name: &str) -> Option<usize> {
+		self.valves.iter().position(|v| v.name == name)
+	}
+
+	// Asking again for the valve that is open restarts its time
+	fn irrigate(&mut self, valve: usize, duration: Duration) {
+		match self.running.as_mut() {
+			Some(running) if running.valve == valve => {
+				running.until = self.clock.now() + to_chrono(duration);
+				let until = running.until;
+				self.publish(valve, ValveActivity::Open { until });
+			}
+			_ => {
+				self.queue.retain(|(v, _)| *v != valve);
+				self.queue.push_back((valve, duration));
+				self.publish(valve, ValveActivity::Queued { seconds: duration.as_secs() as u32 });
+			}
+		}
+	}
+
+	fn stop(&mut self, valve: usize) {
+		let queued = self.queue.len();
+		self.queue.retain(|(v, _)| *v != valve);
+		if self.queue.len() != queued {
+			self.publish(valve, ValveActivity::Closed);
+		}
+		if matches!(self.running, Some(ref r) if r.valve == valve) {
+			self.finish();
+		}
+	}
+
+	fn finish(&mut self) {
+		if let Some(running) = self.running.take() {
+			let now = self.clock.now();
+			let valve = &mut self.valves[running.valve];
+			if let Err(e) = valve.close() {
+				error!("failed to close valve {}: {}", valve.name, e);
+			}
+			let event = IrrigatedEvent {
+				time: running.opened,
+				name: valve.name.clone(),
+				seconds: (now - running.opened).num_seconds().max(0) as u32
+			};
+			self.send(Event::IrrigatedEvent(event));
+			self.publish(running.valve, ValveActivity::Closed);
+			self.resting_until = now + ChronoDuration::seconds(SECONDS_BETWEEN_EVENTS);
+		}
+	}
+
+	fn start_next(&mut self) {
+		let now = self.clock.now();
+		if self.running.is_some() || now < self.resting_until {
+			return;
+		}
+		if let Some((valve, duration)) = self.queue.pop_front() {
+			if let Err(e) = self.valves[valve].open() {
+				error!("failed to open valve {}: {}", self.valves[valve].name, e);
+				self.publish(valve, ValveActivity::Closed);
+				return;
+			}
+			let until = now + to_chrono(duration);
+			self.running = Some(Running { valve, opened: now, until });
+			self.publish(valve, ValveActivity::Open { until });
+		}
+	}
+
+	fn publish(&self, valve: usize, activity: ValveActivity) {
+		let event = ValveEvent { name: self.valves[valve].name.clone(), activity };
+		self.status.write().unwrap()[valve] = event.clone();
+		self.send(Event::ValveEvent(event));
+	}
+
+	// This thread must go on closing valves even with nobody listening
+	fn send(&self, event: Event) {
+		if let Err(e) = self.event_tx.send(event) {
+			error!("failed to send valve event: {}", e);
+		}
+	}
+}
+
+fn to_chrono(duration: Duration) -> ChronoDuration {
+	ChronoDuration::from_std(duration).unwrap_or_else(|_| ChronoDuration::zero())
+}
+
+// Runs until `Valves` is dropped, closing the valves on the way out
+fn main(rx: mpsc::Receiver<Command>, mut irrigations: Irrigations) {
+	loop {
+		let command = match irrigations.next_deadline() {
+			Some(deadline) => rx.recv_timeout(irrigations.clock.real_time_until(deadline)),
+			None => rx.recv().map_err(RecvTimeoutError::from)
+		};
+		match command {
+			Ok(command) => irrigations.handle(command),
+			Err(RecvTimeoutError::Timeout) => irrigations.tick(),
+			Err(RecvTimeoutError::Disconnected) => break
 		}
 	}
 }
 
 pub struct Valves {
 	thread: Option<JoinHandle<()>>,
-	tx: mpsc::Sender<Command>
+	tx: mpsc::Sender<Command>,
+	status: SharedValveStatus
 }
 
 impl Drop for Valves {
+	// Hanging up on the thread is what ends it
 	fn drop(&mut self) {
+		drop(std::mem::replace(&mut self.tx, mpsc::channel().0));
 		if let Some(thread) = self.thread.take() {
 			thread.join().unwrap();
 		}
@@ -123,20 +275,23 @@ impl Drop for Valves {
 }
 
 impl Valves {
-	pub fn new(settings: &Vec<ValveSettings>, event_tx: mpsc::Sender<Event>) -> Result<Self, Box<dyn Error>> {
+	pub fn new(settings: &Vec<ValveSettings>, hardware: &dyn Hardware, clock: SharedClock, event_tx: mpsc::Sender<Event>) -> Result<Self, Box<dyn Error>> {
 		let valves: Vec<Valve> = settings.iter()
-			.map(|v| Valve::new(v).unwrap())
+			.map(|v| Valve::new(v, hardware).unwrap())
 			.collect();
 
 		let (command_tx, command_rx) = mpsc::channel();
 
 		info!("Initialised {} valve(s)", valves.len());
 
-		let thread = spawn(move || main(command_rx, event_tx, valves));
+		let irrigations = Irrigations::new(valves, clock, event_tx);
+		let status = irrigations.status.clone();
+		let thread = spawn(move || main(command_rx, irrigations));
 
-		Ok(Valves { 
+		Ok(Valves {
 			thread: Some(thread),
-			tx: command_tx
+			tx: command_tx,
+			status
 		})
 	}
 
@@ -147,4 +302,116 @@ impl Valves {
 	pub fn irrigate(&self, name: &str, duration: Duration) {
 		self.tx.send(Command::Irrigate { name: name.to_string(), duration }).unwrap();
 	}
-}
\ No newline at end of file
+
+	pub fn stop(&self, name: &str) {
+		self.tx.send(Command::Stop { name: name.to_string() }).unwrap();
+	}
+
+	pub fn stop_all(&self) {
+		self.tx.send(Command::StopAll).unwrap();
+	}
+
+	// What each valve is doing, kept up to date by the valve thread
+	pub fn status(&self) -> SharedValveStatus {
+		self.status.clone()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use chrono::TimeZone;
+	use crate::clock::{AcceleratedClock, Clock, VirtualClock};
+	use crate::hardware::MemoryHardware;
+
+	fn valve(name: &str, gpio: u8) -> ValveSettings {
+		ValveSettings { name: name.to_string(), socket: name.to_string(), gpio }
+	}
+
+	fn irrigated(rx: &mpsc::Receiver<Event>) -> Vec<(String, u32)> {
+		rx.try_iter()
+			.filter_map(|e| match e {
+				Event::IrrigatedEvent(i) => Some((i.name, i.seconds)),
+				_ => None
+			})
+			.collect()
+	}
+
+	#[test]
+	fn stopping_closes_the_valve_at_once_and_starts_the_next() {
+		let hardware = MemoryHardware::new();
+		let clock = Arc::new(VirtualClock::new(Utc.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap()));
+		let (event_tx, event_rx) = mpsc::channel();
+		let valves = [valve("beds", 1), valve("pots", 2)].iter()
+			.map(|v| Valve::new(v, &hardware).unwrap())
+			.collect::<Vec<_>>();
+		let mut irrigations = Irrigations::new(valves, clock.clone(), event_tx);
+		let status = irrigations.status.clone();
+
+		irrigations.handle(Command::Irrigate { name: "beds".to_string(), duration: Duration::from_secs(600) });
+		irrigations.handle(Command::Irrigate { name: "pots".to_string(), duration: Duration::from_secs(60) });
+		assert!(hardware.pin(1) && !hardware.pin(2));
+		assert_eq!(status.read().unwrap()[1].activity, ValveActivity::Queued { seconds: 60 });
+
+		clock.advance(ChronoDuration::seconds(90));
+		irrigations.handle(Command::Stop { name: "beds".to_string() });
+		assert!(!hardware.pin(1) && !hardware.pin(2));
+		assert_eq!(irrigated(&event_rx), vec![("beds".to_string(), 90)]);
+		assert_eq!(irrigations.next_deadline(), Some(clock.now() + ChronoDuration::seconds(SECONDS_BETWEEN_EVENTS)));
+
+		clock.advance(ChronoDuration::seconds(SECONDS_BETWEEN_EVENTS));
+		irrigations.tick();
+		assert!(hardware.pin(2));
+		let until = clock.now() + ChronoDuration::seconds(60);
+		assert_eq!(status.read().unwrap()[1].activity, ValveActivity::Open { until });
+		assert_eq!(irrigations.next_deadline(), Some(until));
+
+		clock.advance(ChronoDuration::seconds(60));
+		irrigations.tick();
+		assert!(!hardware.pin(2));
+		assert_eq!(irrigated(&event_rx), vec![("pots".to_string(), 60)]);
+		assert_eq!(irrigations.next_deadline(), None);
+
+		irrigations.handle(Command::Irrigate { name: "beds".to_string(), duration: Duration::from_secs(600) });
+		irrigations.handle(Command::StopAll);
+		clock.advance(ChronoDuration::seconds(SECONDS_BETWEEN_EVENTS));
+		irrigations.tick();
+		assert!(!hardware.pin(1));
+		assert_eq!(status.read().unwrap()[0].activity, ValveActivity::Closed);
+	}
+
+	#[test]
+	fn valves_close_when_nobody_listens_for_their_events() {
+		let hardware = MemoryHardware::new();
+		let clock = Arc::new(VirtualClock::new(Utc.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap()));
+		let (event_tx, event_rx) = mpsc::channel();
+		let valves = vec![Valve::new(&valve("beds", 1), &hardware).unwrap()];
+		let mut irrigations = Irrigations::new(valves, clock.clone(), event_tx);
+
+		irrigations.handle(Command::Irrigate { name: "beds".to_string(), duration: Duration::from_secs(600) });
+		drop(event_rx);
+		irrigations.handle(Command::StopAll);
+		assert!(!hardware.pin(1));
+	}
+
+	#[test]
+	fn irrigations_end_on_time_and_dropping_closes_the_valves() {
+		let hardware = MemoryHardware::new();
+		let clock = Arc::new(AcceleratedClock::new(Utc.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap(), 600));
+		let (event_tx, event_rx) = mpsc::channel();
+		let valves = Valves::new(&vec![valve("beds", 1)], &hardware, clock.clone(), event_tx).unwrap();
+
+		let mut events = std::iter::from_fn(|| event_rx.recv_timeout(Duration::from_secs(5)).ok());
+
+		valves.irrigate("beds", Duration::from_secs(60));
+		let ended = events.find(|e| matches!(e, Event::IrrigatedEvent(_)));
+		assert!(matches!(ended, Some(Event::IrrigatedEvent(i)) if i.seconds >= 60));
+		assert!(!hardware.pin(1));
+
+		valves.irrigate("beds", Duration::from_secs(3600));
+		assert!(events.any(|e| matches!(e, Event::ValveEvent(v) if matches!(v.activity, ValveActivity::Open { .. }))));
+		assert!(hardware.pin(1));
+		drop(valves);
+		assert!(!hardware.pin(1));
+	}
+}
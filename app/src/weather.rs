@@ -1,12 +1,12 @@
-use bme280::{Bme280Device, Bme280Data};
 use std::error::Error;
 use std::sync::mpsc::Sender;
 use std::thread;
 use std::thread::{JoinHandle};
 use std::time::Duration;
-use chrono::Utc;
 
+use crate::clock::SharedClock;
 use crate::event::{Event, weather::WeatherEvent};
+use crate::hardware::{EnvironmentReading, EnvironmentSensor, Hardware};
 use crate::settings::WeatherSensorSettings;
 
 pub struct WeatherSensor {
@@ -21,20 +21,20 @@ impl Drop for WeatherSensor {
 	}
 }
 
-fn main(mut device: Bme280Device, channel: Sender<Event>, period: Duration) {
+fn main(mut device: Box<dyn EnvironmentSensor>, clock: SharedClock, channel: Sender<Event>, period: Duration) {
 	info!("Started weather sensor");
 	loop {
 		match device.read() {
-			Ok(data) => send_event(data, &channel),
+			Ok(data) => send_event(data, &clock, &channel),
 			Err(e) => error!("ERROR! reading WeatherSensor: {}", e)
 		};
-		thread::park_timeout(period);
+		clock.sleep(period);
 	}
 }
 
-fn send_event(data: Bme280Data, channel: &Sender<Event>) {
+fn send_event(data: EnvironmentReading, clock: &SharedClock, channel: &Sender<Event>) {
 	let event = WeatherEvent {
-		time: Utc::now(),
+		time: clock.now(),
 		temperature: data.temperature,
 		humidity: data.humidity,
 		pressure: data.pressure
@@ -47,12 +47,12 @@ fn send_event(data: Bme280Data, channel: &Sender<Event>) {
 }
 
 impl WeatherSensor {
-	pub fn new(settings: &WeatherSensorSettings, channel: Sender<Event>) -> Result<Self, Box<dyn Error>> {
-		let device = Bme280Device::new(&settings.device, settings.address)?;
+	pub fn new(settings: &WeatherSensorSettings, hardware: &dyn Hardware, clock: SharedClock, channel: Sender<Event>) -> Result<Self, Box<dyn Error>> {
+		let device = hardware.environment_sensor(settings)?;
 		let period = Duration::from_secs(settings.update);
 		let thread = thread::Builder::new()
 			.name("weather".to_string())
-			.spawn(move || { main(device, channel, period) })?;
+			.spawn(move || { main(device, clock, channel, period) })?;
 		Ok(WeatherSensor { 
 			thread: Some(thread)
 		})
@@ -0,0 +1,21 @@
+// What a token lets its holder do: read-only tokens can watch the greenhouse,
+// operator tokens can also start irrigation
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+	ReadOnly,
+	Operator
+}
+
+// Who the API takes the caller to be, as returned by `/api/session`
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Session {
+	pub name: String,
+	pub role: Role
+}
+
+impl Session {
+	pub fn can_operate(&self) -> bool {
+		self.role == Role::Operator
+	}
+}
@@ -0,0 +1,8 @@
+use chrono::{DateTime, Utc};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ButtonEvent {
+	pub time: DateTime<Utc>,
+	pub name: String,
+	pub state: bool
+}
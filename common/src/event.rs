@@ -0,0 +1,21 @@
+use crate::button::ButtonEvent;
+use crate::irrigation::{IrrigatedEvent, IrrigateForEvent, ValveEvent};
+use crate::moisture::MoistureEvent;
+use crate::weather::WeatherEvent;
+
+// Everything that passes through the app's controller, as it is streamed to
+// the UI: `{"type": "moisture_event", "event": {...}}`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", content = "event", rename_all = "snake_case")]
+pub enum Event {
+	WeatherEvent(WeatherEvent),
+	MoistureEvent(MoistureEvent),
+	ButtonEvent(ButtonEvent),
+	ConditionalIrrigateEvent(String),
+	IrrigateEvent(String),
+	IrrigateForEvent(IrrigateForEvent),
+	StopIrrigationEvent(String),
+	StopAllIrrigationEvent,
+	IrrigatedEvent(IrrigatedEvent),
+	ValveEvent(ValveEvent)
+}
@@ -0,0 +1,43 @@
+use chrono::{DateTime, Utc};
+
+use std::time::Duration;
+
+use crate::time::UnixTime;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct IrrigatedEvent {
+	pub time: DateTime<Utc>,
+	pub name: String,
+	pub seconds: u32
+}
+
+// An irrigation of a zone, as the API lists them
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct IrrigationRow {
+	pub start: UnixTime,
+	pub duration: Duration
+}
+
+// A request to irrigate a zone for a time of the caller's choosing
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct IrrigateForEvent {
+	pub zone: String,
+	pub seconds: u32
+}
+
+// What a valve is doing. Valves open one at a time, so the others wait their
+// turn in a queue.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ValveActivity {
+	Closed,
+	Queued { seconds: u32 },
+	Open { until: DateTime<Utc> }
+}
+
+// Sent whenever a valve's activity changes
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ValveEvent {
+	pub name: String,
+	pub activity: ValveActivity
+}
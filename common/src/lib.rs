@@ -0,0 +1,12 @@
+// Types shared by the app and the UI, so that the events the app stores and
+// the API the UI fetches from have their wire format defined in one place
+#[macro_use] extern crate serde_derive;
+
+pub mod auth;
+pub mod button;
+pub mod event;
+pub mod irrigation;
+pub mod moisture;
+pub mod time;
+pub mod weather;
+pub mod zone;
@@ -0,0 +1,15 @@
+use chrono::{DateTime, Utc};
+
+use crate::time::TimeSeries;
+
+pub type Measurement = u16;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MoistureEvent {
+	pub time: DateTime<Utc>,
+	pub name: String,
+	pub value: Measurement
+}
+
+// Readings of each of a zone's sensors, by sensor name
+pub type ZoneMoisture = Vec<(String, TimeSeries<Measurement>)>;
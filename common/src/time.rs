@@ -0,0 +1,91 @@
+use chrono::{DateTime, TimeZone, Utc};
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Whole seconds since the epoch, compact enough for long series of readings
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct UnixTime(i64);
+
+pub type TimeSeries<T> = Vec<(UnixTime, T)>;
+
+// Sent with readings, as a `UnixTime`, when the app no longer holds the start
+// of the range asked for, so the gap before it is missing data rather than
+// no readings
+pub const COVERED_FROM_HEADER: &str = "X-Covered-From";
+
+impl UnixTime {
+	pub fn from_secs(secs: i64) -> Self {
+		UnixTime(secs)
+	}
+
+	pub fn secs(&self) -> i64 {
+		self.0
+	}
+
+	pub fn system_time(&self) -> SystemTime {
+		if self.0 >= 0 {
+			UNIX_EPOCH + Duration::from_secs(self.0 as u64)
+		} else {
+			UNIX_EPOCH - Duration::from_secs(self.0.unsigned_abs())
+		}
+	}
+
+	pub fn date_time(&self) -> DateTime<Utc> {
+		Utc.timestamp_opt(self.0, 0).unwrap()
+	}
+}
+
+impl From<DateTime<Utc>> for UnixTime {
+	fn from(time: DateTime<Utc>) -> Self {
+		UnixTime(time.timestamp())
+	}
+}
+
+// A span of time relative to now, as the API takes it in paths such as
+// `-3600/-0` for the last hour
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeRange {
+	pub start_secs_ago: u32,
+	pub end_secs_ago: u32
+}
+
+impl TimeRange {
+	pub fn last(secs: u32) -> Self {
+		TimeRange { start_secs_ago: secs, end_secs_ago: 0 }
+	}
+
+	pub fn parse(start: &str, end: &str) -> Option<Self> {
+		let secs_ago = |s: &str| s.strip_prefix('-').unwrap_or(s).parse::<u32>().ok();
+		Some(TimeRange { start_secs_ago: secs_ago(start)?, end_secs_ago: secs_ago(end)? })
+	}
+
+	pub fn path(&self) -> String {
+		format!("-{}/-{}", self.start_secs_ago, self.end_secs_ago)
+	}
+
+	pub fn at(&self, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+		(
+			now - chrono::Duration::seconds(self.start_secs_ago as i64),
+			now - chrono::Duration::seconds(self.end_secs_ago as i64)
+		)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn ranges_round_trip_through_paths() {
+		let range = TimeRange::last(3600);
+		assert_eq!(range.path(), "-3600/-0");
+		assert_eq!(TimeRange::parse("-3600", "-0"), Some(range));
+		assert_eq!(TimeRange::parse("an hour", "-0"), None);
+
+		let now = Utc.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap();
+		assert_eq!(range.at(now), (Utc.with_ymd_and_hms(2020, 6, 1, 11, 0, 0).unwrap(), now));
+		assert_eq!(UnixTime::from(now).date_time(), now);
+		assert_eq!(UnixTime::from_secs(-1).system_time(), UNIX_EPOCH - Duration::from_secs(1));
+	}
+}
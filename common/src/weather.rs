@@ -0,0 +1,21 @@
+use chrono::{DateTime, Utc};
+
+use std::time::SystemTime;
+
+pub type Temperature = f64;
+pub type Humidity = f64;
+pub type Pressure = f64;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WeatherEvent {
+	pub time: DateTime<Utc>,
+	pub temperature: Temperature,
+	pub humidity: Humidity,
+	pub pressure: Pressure
+}
+
+impl WeatherEvent {
+	pub fn system_time(&self) -> SystemTime {
+		self.time.into()
+	}
+}
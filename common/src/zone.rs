@@ -0,0 +1,11 @@
+use crate::moisture::Measurement;
+
+// What the UI needs to know about a zone to chart it
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ZoneSummary {
+	pub name: String,
+	pub valve: String,
+	pub sensors: Vec<String>,
+	pub threshold: Measurement,
+	pub irrigate_seconds: u64
+}
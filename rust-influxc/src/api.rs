@@ -0,0 +1,287 @@
+//!
+//! InfluxDB HTTP API Versions
+//!
+//! InfluxDB 1.x has no organizations and buckets but databases and retention policies. In 1.x mode the bucket of a
+//! [Record](struct.Record.html) names the database, optionally followed by `/` and the retention policy, the same
+//! way InfluxDB 1.8 maps buckets for its 2.x compatibility endpoints. The organization is ignored.
+//!
+use crate::Record;
+use crate::Precision;
+
+use crate::InfluxError;
+use crate::InfluxResult;
+
+use crate::ApiDelayError;
+use crate::ApiGenericError;
+use crate::ApiOversizeError;
+use crate::ApiMalformationError;
+
+use crate::json;
+
+use crate::ReqwHeaderMap;
+
+
+/// Version of the InfluxDB HTTP API the [Client](struct.Client.html) talks to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ApiVersion
+{
+    /// InfluxDB 1.x: writes to `/write`, InfluxQL queries to `/query`, and basic credentials passed as query
+    /// parameters rather than signed in with.
+    V1,
+
+    /// InfluxDB 2.x. This is the default.
+    #[default]
+    V2,
+}
+
+
+impl ApiVersion
+{
+    pub(crate) fn write_path(self) -> &'static str
+    {
+        match self
+        {
+            ApiVersion::V1 => { "/write" }
+            ApiVersion::V2 => { "/api/v2/write" }
+        }
+    }
+
+    pub(crate) fn write_query(self, record: &Record) -> Vec<(&'static str, String)>
+    {
+        match self
+        {
+            ApiVersion::V1 => {
+                let (db, rp) = database(&record.bucket);
+
+                let mut query = vec![("db", db.to_owned())];
+
+                if let Some(rp) = rp {
+                    query.push(("rp", rp.to_owned()));
+                }
+
+                query.push(("precision", v1_precision(&record.precision).to_owned()));
+                query
+            }
+
+            ApiVersion::V2 => {
+                vec![
+                    ("org",       record.org.clone()),
+                    ("bucket",    record.bucket.clone()),
+                    ("precision", record.precision.to_string()),
+                ]
+            }
+        }
+    }
+
+    /// Map the reply to a write onto its result, whichever client received it.
+    pub(crate) fn write_result(self, status: u16, retry_after: Option<String>, body: &str) -> InfluxResult<()>
+    {
+        match self
+        {
+            ApiVersion::V1 => { v1_write_result(status, retry_after, body) }
+            ApiVersion::V2 => { v2_write_result(status, retry_after, body) }
+        }
+    }
+}
+
+
+/// Split a bucket into database and retention policy
+pub(crate) fn database(bucket: &str) -> (&str, Option<&str>)
+{
+    match bucket.find('/')
+    {
+        Some(i) => { (&bucket[.. i], Some(&bucket[i + 1 ..]).filter(|rp| ! rp.is_empty())) }
+        None    => { (bucket, None) }
+    }
+}
+
+
+fn v1_precision(precision: &Precision) -> &'static str
+{
+    match precision
+    {
+        Precision::Nanoseconds  => { "n" }
+        Precision::Microseconds => { "u" }
+        Precision::Milliseconds => { "ms" }
+        Precision::Seconds      => { "s" }
+    }
+}
+
+
+/// InfluxDB 1.x answers errors with a body of `{"error": "..."}`
+fn v1_error(status: u16, body: &str) -> String
+{
+    #[derive(Deserialize)]
+    struct Body { error: String }
+
+    json::from_str::<Body>(body)
+        .map(|b| b.error)
+        .unwrap_or_else(|_| format!("HTTP {}: {}", status, body.trim()))
+}
+
+
+/// Map the reply to a 1.x write onto the errors of the 2.x write API.
+pub(crate) fn v1_write_result(status: u16, retry_after: Option<String>, body: &str) -> InfluxResult<()>
+{
+    let code = status.to_string();
+
+    match status
+    {
+        204 => { Ok(()) }
+
+        400 => { Err(InfluxError::WriteMalformed(ApiMalformationError::new(code, v1_error(status, body)))) }
+        401 => { Err(InfluxError::WriteUnauthorized(ApiGenericError::new(code, v1_error(status, body)))) }
+        403 => { Err(InfluxError::WriteUnauthorized(ApiGenericError::new(code, v1_error(status, body)))) }
+        413 => { Err(InfluxError::WriteOversized(ApiOversizeError::new(code, v1_error(status, body)))) }
+        429 => { Err(InfluxError::WriteOverquota(ApiDelayError::new(retry_after, ""))) }
+        503 => { Err(InfluxError::WriteUnready(ApiDelayError::new(retry_after, ""))) }
+
+        _   => { Err(InfluxError::WriteUnknown(ApiGenericError::new(code, v1_error(status, body)))) }
+    }
+}
+
+
+/// Map the reply to a 2.x write onto its errors.
+pub(crate) fn v2_write_result(status: u16, retry_after: Option<String>, body: &str) -> InfluxResult<()>
+{
+    match status
+    {
+        204 => { Ok(()) }
+
+        400 => { Err(InfluxError::WriteMalformed(ApiMalformationError::from_body(status, body))) }
+        401 => { Err(InfluxError::WriteUnauthorized(ApiGenericError::from_body(status, body))) }
+        403 => { Err(InfluxError::WriteUnauthenticated(ApiGenericError::from_body(status, body))) }
+        413 => { Err(InfluxError::WriteOversized(ApiOversizeError::from_body(status, body))) }
+        429 => { Err(InfluxError::WriteOverquota(ApiDelayError::new(retry_after, body))) }
+        503 => { Err(InfluxError::WriteUnready(ApiDelayError::new(retry_after, body))) }
+
+        _   => { Err(InfluxError::WriteUnknown(ApiGenericError::from_body(status, body))) }
+    }
+}
+
+
+/// Map the reply to a 2.x Flux query onto its errors, passing the body on if successful.
+pub(crate) fn v2_query_result(status: u16, retry_after: Option<String>, body: String) -> InfluxResult<String>
+{
+    match status
+    {
+        200 => { Ok(body) }
+
+        400 => { Err(InfluxError::QueryMalformed(ApiGenericError::from_body(status, &body))) }
+        401 => { Err(InfluxError::QueryUnauthorized(ApiGenericError::from_body(status, &body))) }
+        403 => { Err(InfluxError::QueryUnauthorized(ApiGenericError::from_body(status, &body))) }
+        429 => { Err(InfluxError::QueryOverquota(ApiDelayError::new(retry_after, &body))) }
+
+        _   => { Err(InfluxError::QueryUnknown(ApiGenericError::from_body(status, &body))) }
+    }
+}
+
+
+/// Map the reply to a 2.x sign in onto the session cookie it sets.
+pub(crate) fn signin_result(status: u16, headers: &ReqwHeaderMap, body: &str) -> InfluxResult<String>
+{
+    match status
+    {
+        204 => {
+            match headers.get("Set-Cookie").map(|c| c.to_str())
+            {
+                Some(Ok(cookie)) => { Ok(cookie.to_owned()) }
+                Some(Err(_))     => { Err("Failed to extract session cookie string".into()) }
+                None             => { Err("Missing session cookie after successfull basic auth".into()) }
+            }
+        }
+
+        401 => { Err(InfluxError::AuthUnauthorized(ApiGenericError::from_body(status, body))) }
+        403 => { Err(InfluxError::AuthAccountDisabled(ApiGenericError::from_body(status, body))) }
+        _   => { Err(InfluxError::AuthUnknown(ApiGenericError::from_body(status, body))) }
+    }
+}
+
+
+/// Map the reply to a 1.x query onto the errors of the 2.x query API, passing the body on if successful.
+pub(crate) fn v1_query_result(status: u16, retry_after: Option<String>, body: String) -> InfluxResult<String>
+{
+    let code = status.to_string();
+
+    match status
+    {
+        200 => { Ok(body) }
+
+        400 => { Err(InfluxError::QueryMalformed(ApiGenericError::new(code, v1_error(status, &body)))) }
+        401 => { Err(InfluxError::QueryUnauthorized(ApiGenericError::new(code, v1_error(status, &body)))) }
+        403 => { Err(InfluxError::QueryUnauthorized(ApiGenericError::new(code, v1_error(status, &body)))) }
+        429 => { Err(InfluxError::QueryOverquota(ApiDelayError::new(retry_after, ""))) }
+
+        _   => { Err(InfluxError::QueryUnknown(ApiGenericError::new(code, v1_error(status, &body)))) }
+    }
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn v1_writes_name_database_and_retention_policy()
+    {
+        let record = Record::new("ignored", "garden/autogen")
+            .precision(Precision::Milliseconds);
+
+        assert_eq!(ApiVersion::V1.write_query(&record), vec![
+            ("db", "garden".to_owned()), ("rp", "autogen".to_owned()), ("precision", "ms".to_owned()),
+        ]);
+
+        assert_eq!(database("garden"), ("garden", None));
+        assert_eq!(database("garden/"), ("garden", None));
+    }
+
+    #[test]
+    fn v1_errors_map_to_api_errors()
+    {
+        assert!(v1_write_result(204, None, "").is_ok());
+
+        let error = v1_write_result(404, None, "{\"error\":\"database not found: \\\"garden\\\"\"}").unwrap_err();
+        assert_eq!(error.to_string(), "WriteUnknown(code=404, message=database not found: \"garden\")");
+
+        let error = v1_write_result(503, Some("7".into()), "<html>").unwrap_err();
+        assert_eq!(error.retry_after(), Some(std::time::Duration::from_secs(7)));
+
+        let error = v1_query_result(400, None, "bad".into()).unwrap_err();
+        assert_eq!(error.to_string(), "QueryMalformed(code=400, message=HTTP 400: bad)");
+    }
+
+    #[test]
+    fn v2_errors_map_with_or_without_json_bodies()
+    {
+        assert!(ApiVersion::V2.write_result(204, None, "").is_ok());
+
+        let error = ApiVersion::V2.write_result(401, None, "{\"code\":\"unauthorized\",\"message\":\"no token\"}").unwrap_err();
+        assert_eq!(error.to_string(), "WriteUnauthorized(code=unauthorized, message=no token)");
+
+        let error = ApiVersion::V2.write_result(400, None, "<html>Bad Request</html>").unwrap_err();
+        assert!(matches!(error, InfluxError::WriteMalformed(_)));
+
+        let error = v2_write_result(503, None, "{\"delay\": 3}").unwrap_err();
+        assert_eq!(error.retry_after(), Some(std::time::Duration::from_secs(3)));
+
+        assert_eq!(v2_query_result(200, None, "csv".into()).unwrap(), "csv");
+
+        let error = v2_query_result(502, None, "Bad Gateway".into()).unwrap_err();
+        assert_eq!(error.to_string(), "QueryUnknown(code=502, message=Bad Gateway)");
+    }
+
+    #[test]
+    fn signin_takes_the_session_cookie()
+    {
+        let mut headers = ReqwHeaderMap::new();
+
+        assert!(signin_result(204, &headers, "").is_err());
+
+        headers.insert("Set-Cookie", "session=abc".parse().unwrap());
+        assert_eq!(signin_result(204, &headers, "").unwrap(), "session=abc");
+
+        let error = signin_result(401, &headers, "Unauthorized").unwrap_err();
+        assert_eq!(error.to_string(), "AuthUnauthorized(code=401, message=Unauthorized)");
+    }
+}
@@ -0,0 +1,79 @@
+//!
+//! Async Counterpart of the Backlog
+//!
+use crate::Record;
+use crate::Backlog;
+use crate::InfluxResult;
+
+use async_trait::async_trait;
+
+use std::fmt::Debug;
+
+
+/// API definition that any backlog service needs to abide by so the [AsyncClient](struct.AsyncClient.html) can use
+/// it. Same contract as [Backlog](trait.Backlog.html).
+#[async_trait]
+pub trait AsyncBacklog: Debug + Send + Sync
+{
+    /// Whether records are to be written to this backlog before they are submitted, rather than after they failed
+    /// to be. If so, [truncate_pending](#tymethod.truncate_pending) gets called for each record once committed.
+    fn write_ahead(&self) -> bool
+    {
+        false
+    }
+
+    /// Return any pending records that sits in backlog and requires to be commited, in the order they are to be
+    /// commited.
+    async fn read_pending(&mut self) -> InfluxResult<Vec<Record>>;
+
+    /// Write records that could not be commited, so they get written into backlog for future processing.
+    async fn write_pending(&mut self, record: &Record) -> InfluxResult<()>;
+
+    /// Remove a record returned by [read_pending](#tymethod.read_pending) from backlog once it has been commited.
+    async fn truncate_pending(&mut self, record: &Record) -> InfluxResult<()>;
+}
+
+
+/// Adapter to use any blocking [Backlog](trait.Backlog.html), like a [FileBacklog](struct.FileBacklog.html), with the
+/// [AsyncClient](struct.AsyncClient.html). Backlog operations then run on the calling task; the file access of the
+/// backlogs in this crate is brief enough for that.
+#[derive(Debug)]
+pub struct BlockingBacklog<B>
+{
+    backlog: B,
+}
+
+
+impl<B: Backlog> BlockingBacklog<B>
+{
+    /// Wrap a blocking backlog.
+    pub fn new(backlog: B) -> Self
+    {
+        Self {backlog}
+    }
+}
+
+
+#[async_trait]
+impl<B: Backlog> AsyncBacklog for BlockingBacklog<B>
+{
+    fn write_ahead(&self) -> bool
+    {
+        self.backlog.write_ahead()
+    }
+
+    async fn read_pending(&mut self) -> InfluxResult<Vec<Record>>
+    {
+        self.backlog.read_pending()
+    }
+
+    async fn write_pending(&mut self, record: &Record) -> InfluxResult<()>
+    {
+        self.backlog.write_pending(record)
+    }
+
+    async fn truncate_pending(&mut self, record: &Record) -> InfluxResult<()>
+    {
+        self.backlog.truncate_pending(record)
+    }
+}
@@ -0,0 +1,82 @@
+//!
+//! Async Client Builder
+//!
+use super::AsyncClient;
+use super::AsyncBacklog;
+use super::BlockingBacklog;
+
+use crate::Credentials;
+use crate::ApiVersion;
+use crate::NoopBacklog;
+use crate::RetryPolicy;
+
+use crate::InfluxResult;
+
+
+/// Builder to piece by piece assemble an [AsyncClient](struct.AsyncClient.html) instance
+pub struct AsyncClientBuilder
+{
+    url:   String,
+    creds: Credentials,
+
+    version: ApiVersion,
+
+    backlog: Option<Box<dyn AsyncBacklog>>,
+    retry:   Option<Box<dyn RetryPolicy>>,
+}
+
+
+impl AsyncClientBuilder
+{
+    /// Create builder with the most basic information necessary
+    pub fn new(url: String, creds: Credentials) -> Self
+    {
+        Self {
+            url, creds,
+
+            version: ApiVersion::V2,
+
+            backlog: None,
+            retry:   None,
+        }
+    }
+
+    /// Talk to InfluxDB 1.x rather than 2.x. See [ApiVersion](enum.ApiVersion.html) for how records name the database
+    /// and retention policy to write to.
+    pub fn api_version(mut self, version: ApiVersion) -> Self
+    {
+        self.version = version; self
+    }
+
+    /// Add backlog to client, so records and measurements get stored as long as they fail to be committed. Blocking
+    /// backlogs can be used through a [BlockingBacklog](struct.BlockingBacklog.html).
+    pub fn backlog<B: AsyncBacklog + 'static>(mut self, backlog: B) -> Self
+    {
+        self.backlog = Some(Box::new(backlog)); self
+    }
+
+    /// Add a retry policy to attempt failed writes again before giving up on them. Without one, writes are not
+    /// retried.
+    pub fn retry<R: RetryPolicy + 'static>(mut self, policy: R) -> Self
+    {
+        self.retry = Some(Box::new(policy)); self
+    }
+
+    /// Consume this builder to assemble and return the final AsyncClient instance for usage.
+    pub async fn finish(self) -> InfluxResult<AsyncClient>
+    {
+        let backlog = match self.backlog
+        {
+            Some(b) => { b }
+            None    => { Box::new(BlockingBacklog::new(NoopBacklog::new())) }
+        };
+
+        let mut client = AsyncClient::connect(self.url, self.creds, backlog, self.version).await?;
+
+        if let Some(retry) = self.retry {
+            client.retry = retry.into();
+        }
+
+        Ok(client)
+    }
+}
@@ -0,0 +1,378 @@
+//!
+//! Async Client
+//!
+use super::AsyncBacklog;
+use super::AsyncClientBuilder;
+
+use crate::Table;
+use crate::Record;
+use crate::Replay;
+use crate::Credentials;
+use crate::ApiVersion;
+
+use crate::NoRetry;
+use crate::RetryPolicy;
+
+use crate::InfluxError;
+use crate::InfluxResult;
+
+use crate::b64;
+
+use crate::error::retry_after_header;
+
+use crate::query::query_body;
+use crate::query::parse_tables;
+
+use crate::influxql::parse_results;
+
+use crate::api::signin_result;
+use crate::api::v1_query_result;
+use crate::api::v2_query_result;
+
+use crate::ReqwUrl;
+use crate::ReqwMethod;
+use crate::ReqwAsyncClient;
+use crate::ReqwAsyncRequestBuilder;
+
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+
+
+/// Async counterpart of the [Client](struct.Client.html). Clones share the connection pool and backlog, and writes
+/// from any of them are committed one at a time so that the backlog keeps its order.
+#[derive(Debug, Clone)]
+pub struct AsyncClient
+{
+    url:    ReqwUrl,
+    creds:  Arc<Credentials>,
+    client: ReqwAsyncClient,
+
+    backlog: Arc<Mutex<Box<dyn AsyncBacklog>>>,
+
+    version: ApiVersion,
+
+    pub(crate) retry: Arc<dyn RetryPolicy>,
+}
+
+
+impl AsyncClient
+{
+    /// Create a builder to parametrize and construct this [AsyncClient](struct.AsyncClient.html).
+    pub fn build(url: String, creds: Credentials) -> AsyncClientBuilder
+    {
+        AsyncClientBuilder::new(url, creds)
+    }
+
+    /// Directly construct this [AsyncClient](struct.AsyncClient.html).
+    pub async fn new(url: String, creds: Credentials, backlog: Box<dyn AsyncBacklog>) -> InfluxResult<Self>
+    {
+        Self::connect(url, creds, backlog, ApiVersion::V2).await
+    }
+
+    pub(crate) async fn connect(url: String, mut creds: Credentials, backlog: Box<dyn AsyncBacklog>, version: ApiVersion) -> InfluxResult<Self>
+    {
+        let client = ReqwAsyncClient::builder()
+            .build()?;
+
+        let url = match ReqwUrl::parse(&url)
+        {
+            Ok(url) => { url }
+            Err(e)  => { return Err(format!("Failed to parse URL: {} due to {}", url, e).into()) }
+        };
+
+        // InfluxDB 1.x takes basic credentials with every request instead
+        if version == ApiVersion::V2 {
+            authenticate(&client, &url, &mut creds).await?;
+        }
+
+        Ok(Self {
+            url, client, version,
+
+            creds:   Arc::new(creds),
+            backlog: Arc::new(Mutex::new(backlog)),
+            retry:   Arc::new(NoRetry),
+        })
+    }
+
+    /// Submit a [Record](struct.Record.html) to be written to InfluxDB. Or backlogged if you set a backlogger.
+    pub async fn write(&self, record: &Record) -> InfluxResult<()>
+    {
+        let mut backlog = self.backlog.lock().await;
+
+        if let Err(e) = self.write_backlog(&mut **backlog).await {
+            backlog.write_pending(record).await?; Err(e)
+        }
+        else if backlog.write_ahead()
+        {
+            backlog.write_pending(record).await?;
+
+            let result = self.commit_record(record).await;
+
+            if result.is_ok() {
+                backlog.truncate_pending(record).await?;
+            }
+
+            result
+        }
+        else
+        {
+            let result = self.commit_record(record).await;
+
+            if result.is_err() {
+                backlog.write_pending(record).await?;
+            }
+
+            result
+        }
+    }
+
+    /// Submit pending/backlogged [Records](struct.Record.html) to writing, as [Client::flush](struct.Client.html#method.flush)
+    /// does.
+    pub async fn flush(&self) -> InfluxResult<Replay>
+    {
+        let mut backlog = self.backlog.lock().await;
+
+        self.write_backlog(&mut **backlog).await
+    }
+
+    /// Run a Flux query on behalf of the organization and return the [Tables](struct.Table.html) of its result.
+    pub async fn query(&self, org: &str, flux: &str) -> InfluxResult<Vec<Table>>
+    {
+        let mut url = self.url.clone();
+
+        url.set_path("/api/v2/query");
+
+        let mut builder = self.client.request(ReqwMethod::POST, url)
+            .query(&[("org", org)])
+            .header("Accept", "application/csv")
+            .json(&query_body(flux));
+
+        builder = self.inject_credentials(builder)?;
+
+        debug!("Request: {:#?}", builder);
+
+        let reply       = builder.send().await?;
+        let status      = reply.status().as_u16();
+        let retry_after = retry_after_header(reply.headers());
+
+        parse_tables(&v2_query_result(status, retry_after, reply.text().await?)?)
+    }
+
+    /// Run a Flux query and [decode](struct.Table.html#method.decode) the rows of every table in its result.
+    pub async fn query_as<T: serde::de::DeserializeOwned>(&self, org: &str, flux: &str) -> InfluxResult<Vec<T>>
+    {
+        let mut rows = Vec::new();
+
+        for table in self.query(org, flux).await? {
+            rows.extend(table.decode::<T>()?);
+        }
+
+        Ok(rows)
+    }
+
+    /// Run an InfluxQL query against a database of InfluxDB 1.x, or a 2.x bucket mapped to one, and return a
+    /// [Table](struct.Table.html) for each series of its result.
+    pub async fn influxql(&self, db: &str, influxql: &str) -> InfluxResult<Vec<Table>>
+    {
+        let mut url = self.url.clone();
+
+        url.set_path("/query");
+
+        let mut builder = self.client.request(ReqwMethod::POST, url)
+            .query(&[("db", db), ("q", influxql)]);
+
+        builder = self.inject_credentials(builder)?;
+
+        debug!("Request: {:#?}", builder);
+
+        let reply       = builder.send().await?;
+        let status      = reply.status().as_u16();
+        let retry_after = retry_after_header(reply.headers());
+
+        parse_results(&v1_query_result(status, retry_after, reply.text().await?)?)
+    }
+
+    /// Run an InfluxQL query and [decode](struct.Table.html#method.decode) the rows of every series in its result.
+    pub async fn influxql_as<T: serde::de::DeserializeOwned>(&self, db: &str, influxql: &str) -> InfluxResult<Vec<T>>
+    {
+        let mut rows = Vec::new();
+
+        for table in self.influxql(db, influxql).await? {
+            rows.extend(table.decode::<T>()?);
+        }
+
+        Ok(rows)
+    }
+}
+
+
+/// Private interface
+impl AsyncClient
+{
+    async fn write_backlog(&self, backlog: &mut dyn AsyncBacklog) -> InfluxResult<Replay>
+    {
+        let records = backlog.read_pending().await?;
+
+        let mut replay = Replay {
+            replayed:  0,
+            remaining: records.iter().map(|r| r.measurements.len()).sum(),
+        };
+
+        if replay.remaining > 0 {
+            info!("Found {} backlogged entries, attempting to commit", replay.remaining);
+        }
+
+        for record in records.iter()
+        {
+            if let Err(e) = self.commit_record(record).await {
+                warn!("Unable to commit backlogged record, {} of {} entries replayed", replay.replayed, replay.replayed + replay.remaining);
+                return Err(InfluxError::Replay(replay, Box::new(e)));
+            }
+
+            if let Err(e) = backlog.truncate_pending(record).await
+            {
+                let msg = format!("Failed to eliminate/truncate record from backlog: {}", e);
+                error!("{}", msg);
+                panic!("{}", msg);
+            }
+
+            replay.replayed  += record.measurements.len();
+            replay.remaining -= record.measurements.len();
+        }
+
+        if replay.replayed > 0 {
+            info!("Replayed {} backlogged entries", replay.replayed);
+        }
+
+        Ok(replay)
+    }
+
+    /// Write the record, attempting again for as long as the retry policy says so.
+    async fn commit_record(&self, record: &Record) -> InfluxResult<()>
+    {
+        let mut attempt = 1;
+
+        loop
+        {
+            let error = match self.write_record(record).await
+            {
+                Ok(())  => { return Ok(()); }
+                Err(e)  => { e }
+            };
+
+            match self.retry.retry(attempt, &error)
+            {
+                Some(delay) => {
+                    warn!("Write attempt {} failed, retrying in {:?}: {}", attempt, delay, error);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+
+                None => { return Err(error); }
+            }
+        }
+    }
+
+    async fn write_record(&self, record: &Record) -> InfluxResult<()>
+    {
+        let mut url = self.url.clone();
+
+        url.set_path(self.version.write_path());
+
+        let mut builder = self.client.request(ReqwMethod::POST, url);
+
+        builder = record.to_async_write_request(self.version, builder)?;
+        builder = self.inject_credentials(builder)?;
+
+        debug!("Request: {:#?}", builder);
+
+        let reply       = builder.send().await?;
+        let status      = reply.status().as_u16();
+        let retry_after = retry_after_header(reply.headers());
+
+        self.version.write_result(status, retry_after, &reply.text().await?)?;
+
+        info!("Written: {}", record);
+        Ok(())
+    }
+
+    fn inject_credentials(&self, builder: ReqwAsyncRequestBuilder) -> InfluxResult<ReqwAsyncRequestBuilder>
+    {
+        if let (ApiVersion::V1, Credentials::Basic{user, passwd, ..}) = (self.version, &*self.creds) {
+            return Ok(builder.query(&[("u", user), ("p", passwd)]));
+        }
+
+        let (name, value) = self.creds.header()?;
+
+        Ok(builder.header(name, value))
+    }
+}
+
+
+async fn authenticate(client: &ReqwAsyncClient, url: &ReqwUrl, creds: &mut Credentials) -> InfluxResult<()>
+{
+    if let Credentials::Basic{ref user, ref passwd, cookie: None} = creds
+    {
+        let mut url = url.clone();
+
+        url.set_path("/api/v2/signin");
+
+        let b64creds = b64::encode(format!("{}:{}", user, passwd));
+
+        let req = client.request(ReqwMethod::POST, url)
+            .header("Authorization", format!("Basic {}", b64creds));
+
+        debug!("Request: {:#?}", req);
+
+        let rep     = req.send().await?;
+        let status  = rep.status().as_u16();
+        let headers = rep.headers().clone();
+        let session = signin_result(status, &headers, &rep.text().await?)?;
+
+        *creds = Credentials::Basic {user: user.clone(), passwd: passwd.clone(), cookie: Some(session)};
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::FileBacklog;
+    use crate::Precision;
+    use crate::testing::TempDir;
+
+    use super::super::BlockingBacklog;
+
+    #[tokio::test]
+    async fn failed_writes_are_backlogged_and_replay_reports_progress()
+    {
+        let dir = TempDir::new("async");
+
+        // Nothing listens on port 1, so every write fails to connect
+        let client = AsyncClient::build("http://127.0.0.1:1".into(), Credentials::from_token("token"))
+            .backlog(BlockingBacklog::new(FileBacklog::new(&dir).unwrap()))
+            .finish()
+            .await
+            .unwrap();
+
+        let mut record = Record::new("org", "bucket")
+            .precision(Precision::Seconds);
+
+        record.measurement("moisture").field("value", 1);
+        record.measurement("moisture").field("value", 2);
+
+        assert!(matches!(client.write(&record).await, Err(InfluxError::Reqwest(_))));
+        assert!(client.clone().write(&record).await.unwrap_err().is_transient());
+
+        match client.flush().await
+        {
+            Err(InfluxError::Replay(replay, _)) => { assert_eq!(replay, Replay {replayed: 0, remaining: 4}); }
+            other                               => { panic!("unexpected {:?}", other); }
+        }
+    }
+}
@@ -0,0 +1,15 @@
+//!
+//! Async/Await Client. Enabled by the `async` feature.
+//!
+//! Mirrors the blocking [Client](struct.Client.html): same write, flush and query surface, with the backlog behind an
+//! [AsyncBacklog](trait.AsyncBacklog.html). All methods take `&self` and clones share their connection pool and
+//! backlog, so one client can serve many tasks.
+//!
+mod client;
+mod builder;
+mod backlog;
+
+pub use client::AsyncClient;
+pub use builder::AsyncClientBuilder;
+pub use backlog::AsyncBacklog;
+pub use backlog::BlockingBacklog;
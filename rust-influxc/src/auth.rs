@@ -47,4 +47,23 @@ impl Credentials
             token: token.to_owned(),
         }
     }
+
+    /// Name and value of the header authorizing a request
+    pub(crate) fn header(&self) -> Result<(&'static str, String), String>
+    {
+        match self
+        {
+            Credentials::Basic{user: _, passwd: _, cookie: None} => {
+                Err("Missing session cookie from basic auth. This should not have happened!".into())
+            }
+
+            Credentials::Basic{user: _, passwd: _, cookie: Some(session)} => {
+                Ok(("Cookie", session.clone()))
+            }
+
+            Credentials::Token{token} => {
+                Ok(("Authorization", format!("Token {}", token)))
+            }
+        }
+    }
 }
@@ -3,12 +3,27 @@
 //! thus aiming to be more flash friendly on embedded devices where you want to keep writes to a minimum. This
 //! will cause data loss if after failed insert also the writing to flash fails!. For that prefer a WAL approach.
 //!
-//! WAL approach: TODO
+//! WAL approach: see [WalBacklog](struct.WalBacklog.html).
+//!
+//! Archives hold one measurement per line in line protocol at the precision of the archive. Archives written by
+//! earlier versions as one JSON object per line are still read.
+//!
+//! Archives are replayed in chunks of at most `REPLAY_CHUNK` measurements. Committed chunks are counted off the head
+//! of the archive and cut from the file in a single rewrite before the backlog is next read or written to, or when
+//! it is dropped, so a replay that stops part way leaves exactly the uncommitted measurements behind. Should the
+//! process die before the cut, the committed chunks are replayed again, which InfluxDB takes as overwriting the same
+//! points.
 //!
 use super::Backlog;
+use super::Dropped;
+use super::Overflow;
 
 use crate::Record;
 use crate::Precision;
+use crate::Measurement;
+
+use crate::Utc;
+use crate::DateTime;
 
 use crate::InfluxError;
 use crate::InfluxErrorAnnotate;
@@ -30,17 +45,31 @@ use std::io::BufWriter;
 use std::path::Path;
 use std::path::PathBuf;
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+
+use chrono::Duration;
+
+
+/// InfluxDB recommends batches of 5000 lines for optimal write performance.
+const REPLAY_CHUNK: usize = 5000;
 
 
 /// Backlog for the [Client](struct.Client.html) to persist [Record](struct.Record.html)`s that could not be submitted
 /// to the InfluxDB due to conectivity or configuration errors.
+///
+/// Unbounded by default. Set a [max_size](#method.max_size) and/or [max_age](#method.max_age) to keep a long outage
+/// from filling the disk; what gets dropped is counted by [dropped](#method.dropped).
 #[derive(Debug)]
 pub struct FileBacklog
 {
     dir: PathBuf,
 
-    archives: HashMap<PathBuf, Archive>,
+    archives: BTreeMap<PathBuf, Archive>,
+
+    max_size: Option<u64>,
+    max_age:  Option<Duration>,
+    overflow: Overflow,
+    dropped:  Dropped,
 }
 
 
@@ -55,7 +84,7 @@ impl FileBacklog
             return Err(format!("Backlog dir is not a directory: {:#?}", dir).into())
         }
 
-        let mut archives = HashMap::new();
+        let mut archives = BTreeMap::new();
 
         let listing = std::fs::read_dir(&dir)
             .annotate(format!("While opening backlog directory: {:#?}", dir))?;
@@ -64,18 +93,48 @@ impl FileBacklog
         {
             let entry = entry?;
             let path  = entry.path();
+
+            if path.extension().and_then(|e| e.to_str()) != Some("log") {
+                continue;   // e.g. an archive rewrite cut short
+            }
+
             let file  = Archive::open(&path)?;
 
             archives.insert(path, file);
         }
 
-        Ok(Self {dir, archives})
+        Ok(Self {dir, archives, max_size: None, max_age: None, overflow: Overflow::default(), dropped: Dropped::default()})
+    }
+
+    /// Maximum size in bytes of all archives together. Measurements beyond it are dropped according to the
+    /// [overflow](#method.overflow) policy.
+    pub fn max_size(mut self, bytes: u64) -> Self
+    {
+        self.max_size = Some(bytes); self
+    }
+
+    /// Maximum age of backlogged measurements. Older ones are dropped rather than replayed.
+    pub fn max_age(mut self, age: std::time::Duration) -> Self
+    {
+        self.max_age = Duration::from_std(age).ok(); self
+    }
+
+    /// What to drop once the maximum size is reached. Defaults to [DropOldest](enum.Overflow.html#variant.DropOldest).
+    pub fn overflow(mut self, policy: Overflow) -> Self
+    {
+        self.overflow = policy; self
+    }
+
+    /// Counters of the measurements this backlog dropped, shared with it.
+    pub fn dropped(&self) -> Dropped
+    {
+        self.dropped.clone()
     }
 
     fn archive(&mut self, record: &Record) -> InfluxResult<&mut Archive>
     {
         let meta = ArchiveMeta::from_record(record);
-        let path = meta.to_path();
+        let path = self.dir.join(meta.to_path());
 
         if ! self.archives.contains_key(&path) {
             self.archives.insert(path.clone(), Archive::open(&path)?);
@@ -83,6 +142,85 @@ impl FileBacklog
 
         Ok(self.archives.get_mut(&path).unwrap())
     }
+
+    fn size(&self) -> u64
+    {
+        self.archives.values().map(|a| a.size).sum()
+    }
+
+    /// Cut what was committed since the last cut from the archives.
+    fn cut(&mut self) -> InfluxResult<()>
+    {
+        for archive in self.archives.values_mut() {
+            archive.cut()?;
+        }
+
+        Ok(())
+    }
+
+    fn expire(&mut self) -> InfluxResult<()>
+    {
+        if let Some(age) = self.max_age
+        {
+            let cutoff = Utc::now() - age;
+
+            for archive in self.archives.values_mut() {
+                self.dropped.add_expired(archive.expire(cutoff)?);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Make room for `incoming` bytes by evicting or thinning what is backlogged, as far as the policy allows.
+    fn make_room(&mut self, max: u64, incoming: u64) -> InfluxResult<()>
+    {
+        match self.overflow
+        {
+            Overflow::DropNewest => {}
+
+            Overflow::DropOldest => {
+                // Free a tenth more than needed, so a full backlog is not rewritten on every write
+                let target = (max - max / 10).saturating_sub(incoming);
+
+                loop
+                {
+                    let size = self.size();
+
+                    if size <= target {
+                        break;
+                    }
+
+                    let oldest = self.archives.values_mut()
+                        .filter(|a| a.count > 0)
+                        .min_by_key(|a| a.head);
+
+                    match oldest
+                    {
+                        Some(archive) => { self.dropped.add_overflow(archive.evict(size - target)?); }
+                        None          => { break; }
+                    }
+                }
+            }
+
+            Overflow::Downsample => {
+                while self.size() + incoming > max
+                {
+                    let largest = self.archives.values_mut()
+                        .filter(|a| a.count > 1)
+                        .max_by_key(|a| a.size);
+
+                    match largest
+                    {
+                        Some(archive) => { self.dropped.add_overflow(archive.thin()?); }
+                        None          => { break; }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
 
 
@@ -91,13 +229,13 @@ impl Backlog for FileBacklog
     #[inline]
     fn read_pending(&mut self) -> InfluxResult<Vec<Record>>
     {
+        self.cut()?;
+        self.expire()?;
+
         let mut records = Vec::new();
 
-        for archive in self.archives.values_mut()
-        {
-            if let Some(record) = archive.record()? {
-                records.push(record);
-            }
+        for archive in self.archives.values_mut() {
+            records.extend(archive.records(REPLAY_CHUNK)?);
         }
 
         Ok(records)
@@ -106,8 +244,38 @@ impl Backlog for FileBacklog
     #[inline]
     fn write_pending(&mut self, record: &Record) -> InfluxResult<()>
     {
-        self.archive(record)?
-            .append(record)?;
+        self.cut()?;
+        self.expire()?;
+
+        let mut lines = record.to_lines()?;
+
+        if let Some(max) = self.max_size
+        {
+            let incoming = bytes(&lines);
+
+            if self.size() + incoming > max
+            {
+                self.make_room(max, incoming)?;
+
+                // Whatever still does not fit is dropped from the new measurements
+                let mut room = max.saturating_sub(self.size());
+                let fitting  = lines.iter()
+                    .take_while(|l| {
+                        let len = l.len() as u64 + 1;
+                        if len <= room { room -= len; true } else { false }
+                    })
+                    .count();
+
+                self.dropped.add_overflow(lines.len() - fitting);
+                lines.truncate(fitting);
+            }
+        }
+
+        if ! lines.is_empty()
+        {
+            self.archive(record)?
+                .append(&lines)?;
+        }
 
         Ok(())
     }
@@ -116,19 +284,40 @@ impl Backlog for FileBacklog
     fn truncate_pending(&mut self, record: &Record) -> InfluxResult<()>
     {
         self.archive(record)?
-            .truncate()?;
+            .commit(record.measurements.len())
+    }
+}
 
-        Ok(())
+
+impl Drop for FileBacklog
+{
+    fn drop(&mut self)
+    {
+        if let Err(e) = self.cut() {
+            warn!("Failed to cut committed measurements from backlog: {}", e);
+        }
     }
 }
 
 
+fn bytes(lines: &[String]) -> u64
+{
+    lines.iter().map(|l| l.len() as u64 + 1).sum()
+}
+
+
 #[derive(Debug)]
 struct Archive
 {
+    path:   PathBuf,
     meta:   ArchiveMeta,
     handle: Option<File>,
     count:  usize,
+    size:   u64,
+    head:   Option<DateTime>,
+
+    /// Measurements at the head of the file that are committed but not cut yet
+    committed: usize,
 }
 
 
@@ -136,76 +325,101 @@ impl Archive
 {
     pub fn open(path: &Path) -> InfluxResult<Self>
     {
-        let meta   = ArchiveMeta::from_path(&path)?;
-        let handle = open(&path, false)?;
-        let bfrd   = BufReader::new(&handle);
-        let count  = bfrd.lines().count();
+        let meta = ArchiveMeta::from_path(path)?;
+
+        let mut this = Self {path: path.to_owned(), meta, handle: Some(open(path, false)?), count: 0, size: 0, head: None, committed: 0};
 
-        Ok(Self {meta, handle: Some(handle), count})
+        let lines = this.lines()?;
+
+        this.count = lines.len();
+        this.size  = bytes(&lines);
+        this.head  = lines.first().and_then(|l| this.parse(l).ok()).map(|m| m.timestamp);
+
+        Ok(this)
     }
 
-    pub fn record(&mut self) -> InfluxResult<Option<Record>>
+    fn parse(&self, line: &str) -> InfluxResult<Measurement>
     {
-        if self.count == 0 {
-            Ok(None)
+        if line.starts_with('{') {
+            json::from_str(line).map_err(InfluxError::from)
+        } else {
+            Measurement::from_line(line, &self.meta.precision)
         }
-        else
-        {
-            self.prepare_handle(Some(SeekFrom::Start(0)))?;
+    }
 
-            if let Some(handle) = &self.handle
-            {
-                let reader = BufReader::new(handle);
+    fn lines(&mut self) -> InfluxResult<Vec<String>>
+    {
+        self.prepare_handle(Some(SeekFrom::Start(0)))?;
 
-                let mut msrmts = Vec::new();
+        match &self.handle
+        {
+            Some(handle) => { Ok(BufReader::new(handle).lines().skip(self.committed).collect::<Result<Vec<String>, _>>()?) }
+            None         => { panic!("handle preparation should have prevented this case"); }
+        }
+    }
 
-                for (num, line) in reader.lines().enumerate()
-                {
-                    let ln = line?;
+    pub fn records(&mut self, chunk: usize) -> InfluxResult<Vec<Record>>
+    {
+        let mut records = Vec::new();
 
-                    match json::from_str(&ln)
-                    {
-                        Ok(msrmt) => {
-                            msrmts.push(msrmt)
-                        }
-
-                        Err(e) => {
-                            error!("Failed to read line {}", num);
-                            return Err(e.into());
-                        }
-                    }
-                }
+        if self.count == 0 {
+            return Ok(records);
+        }
 
-                let mut record = Record::new(&self.meta.org, &self.meta.bucket)
-                    .precision(self.meta.precision.clone());
+        let mut msrmts = Vec::new();
 
-                record.measurements = msrmts;
+        for (num, ln) in self.lines()?.iter().enumerate()
+        {
+            match self.parse(ln)
+            {
+                Ok(msrmt) => {
+                    msrmts.push(msrmt)
+                }
 
-                Ok(Some(record))
+                Err(e) => {
+                    error!("Failed to read line {}", num);
+                    return Err(e);
+                }
             }
-            else {
-                panic!("handle preparation should have prevented this case");
+
+            if msrmts.len() == chunk {
+                records.push(self.record(std::mem::take(&mut msrmts)));
             }
         }
 
+        if ! msrmts.is_empty() {
+            records.push(self.record(msrmts));
+        }
+
+        Ok(records)
+    }
+
+    fn record(&self, measurements: Vec<Measurement>) -> Record
+    {
+        let mut record = Record::new(&self.meta.org, &self.meta.bucket)
+            .precision(self.meta.precision);
+
+        record.measurements = measurements;
+
+        record
     }
 
-    pub fn append(&mut self, record: &Record) -> InfluxResult<()>
+    pub fn append(&mut self, lines: &[String]) -> InfluxResult<()>
     {
+        if self.count == 0 {
+            self.head = lines.first().and_then(|l| self.parse(l).ok()).map(|m| m.timestamp);
+        }
+
         self.prepare_handle(Some(SeekFrom::End(0)))?;
 
         if let Some(handle) = &self.handle
         {
             let mut writer = BufWriter::new(handle);
 
-            for msrmt in record.measurements.iter()
+            for line in lines.iter()
             {
-                let line = json::to_string(msrmt)?;
-
                 writer.write_all(line.as_bytes())?;
                 writer.write_all(b"\n")?;
-
-                self.count += 1;
             }
 
             writer.flush()?;
@@ -214,15 +428,135 @@ impl Archive
             panic!("handle preparation should have prevented this case");
         }
 
+        self.count += lines.len();
+        self.size  += bytes(lines);
+
         Ok(())
     }
 
-    pub fn truncate(&mut self) -> InfluxResult<()>
+    /// Count `count` measurements off the head of the archive as committed, deleting the archive once nothing is left.
+    pub fn commit(&mut self, count: usize) -> InfluxResult<()>
     {
-        std::fs::remove_file(&self.meta.to_path())?;  // to keep dir as clean as possible from empty backlogs
+        let count = count.min(self.count);
+
+        self.committed += count;
+        self.count     -= count;
+
+        if self.count == 0 {
+            return self.rewrite(Vec::new());
+        }
+
+        Ok(())
+    }
+
+    /// Rewrite the archive without its committed measurements, if there are any.
+    pub fn cut(&mut self) -> InfluxResult<()>
+    {
+        if self.committed == 0 {
+            return Ok(());
+        }
+
+        let lines = self.lines()?;
+
+        self.rewrite(lines)
+    }
+
+    /// Drop measurements older than `cutoff` from the head of the archive and return how many. Measurements are
+    /// backlogged as they come, so the head is where the oldest are.
+    pub fn expire(&mut self, cutoff: DateTime) -> InfluxResult<usize>
+    {
+        if ! matches!(self.head, Some(head) if head < cutoff) {
+            return Ok(0);
+        }
+
+        let mut lines = self.lines()?;
+        let mut count = 0;
+
+        for line in lines.iter()
+        {
+            if self.parse(line)?.timestamp >= cutoff {
+                break;
+            }
+
+            count += 1;
+        }
+
+        let rest = lines.split_off(count);
+        self.rewrite(rest)?;
+
+        Ok(count)
+    }
+
+    /// Drop measurements from the head of the archive until at least `excess` bytes are freed and return how many.
+    pub fn evict(&mut self, excess: u64) -> InfluxResult<usize>
+    {
+        let lines     = self.lines()?;
+        let mut freed = 0;
+
+        let count = lines.iter()
+            .take_while(|l| {
+                let more = freed < excess;
+                freed += l.len() as u64 + 1;
+                more
+            })
+            .count();
+
+        self.rewrite(lines.into_iter().skip(count).collect())?;
+
+        Ok(count)
+    }
+
+    /// Drop every other measurement and return how many.
+    pub fn thin(&mut self) -> InfluxResult<usize>
+    {
+        let lines = self.lines()?;
+        let count = lines.len() / 2;
+
+        self.rewrite(lines.into_iter().step_by(2).collect())?;
+
+        Ok(count)
+    }
+
+    /// Replace the content of the archive, deleting it when nothing is left.
+    pub fn rewrite(&mut self, lines: Vec<String>) -> InfluxResult<()>
+    {
+        self.handle    = None;
+        self.committed = 0;
+
+        if lines.is_empty()
+        {
+            std::fs::remove_file(&self.path)?;  // to keep dir as clean as possible from empty backlogs
+
+            self.count = 0;
+            self.size  = 0;
+            self.head  = None;
+
+            return Ok(());
+        }
+
+        // Rewrite aside and rename over, so a crash leaves either the old or the new archive but never half of one
+        let temp = self.path.with_extension("tmp");
+
+        {
+            let file       = open(&temp, true)?;
+            let mut writer = BufWriter::new(&file);
+
+            for line in lines.iter()
+            {
+                writer.write_all(line.as_bytes())?;
+                writer.write_all(b"\n")?;
+            }
+
+            writer.flush()?;
+            file.sync_all()?;
+        }
+
+        std::fs::rename(&temp, &self.path)
+            .annotate(format!("While replacing archive: {:#?}", self.path))?;
 
-        self.handle = None;
-        self.count  = 0;
+        self.count = lines.len();
+        self.size  = bytes(&lines);
+        self.head  = self.parse(&lines[0]).ok().map(|m| m.timestamp);
 
         Ok(())
     }
@@ -230,7 +564,7 @@ impl Archive
     fn prepare_handle(&mut self, seek: Option<SeekFrom>) -> InfluxResult<()>
     {
         if self.handle.is_none() {
-            self.handle = Some(open(&self.meta.to_path(), false)?);
+            self.handle = Some(open(&self.path, false)?);
         }
 
         let handle = self.handle.as_mut().unwrap();
@@ -272,7 +606,7 @@ impl ArchiveMeta
         Self {
             org:       record.org.clone(),
             bucket:    record.bucket.clone(),
-            precision: record.precision.clone(),
+            precision: record.precision,
         }
     }
 
@@ -303,9 +637,176 @@ impl ArchiveMeta
 
     fn to_path(&self) -> PathBuf
     {
-        let name  = format!("{}_{}_{}", self.org, self.bucket, self.precision.to_string());
+        let name  = format!("{}_{}_{}", self.org, self.bucket, self.precision);
         let enc32 = b32::encode(b32::Alphabet::RFC4648 {padding: false}, name.as_bytes());
 
         PathBuf::from(format!("{}.log", enc32))
     }
 }
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::Value;
+    use crate::testing::TempDir;
+
+    use chrono::TimeZone;
+
+    #[test]
+    fn archives_are_line_protocol_and_legacy_json_is_read()
+    {
+        let dir = TempDir::new("file-backlog");
+
+        let mut record = Record::new("org", "bucket")
+            .precision(Precision::Seconds);
+
+        record.measurement("legacy")
+            .field("value", 1.0);
+
+        let meta = ArchiveMeta::from_record(&record);
+        let path = dir.join(meta.to_path());
+        let json = json::to_string(&record.measurements[0]).unwrap();
+
+        std::fs::write(&path, format!("{}\n", json)).unwrap();
+
+        let mut backlog = FileBacklog::new(&dir).unwrap();
+
+        let mut record = Record::new("org", "bucket")
+            .precision(Precision::Seconds);
+
+        record.measurement("current")
+            .tag("name", "Tomato bed 1")
+            .field("value", 2.0);
+
+        backlog.write_pending(&record).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.lines().nth(1).unwrap().starts_with("current,name=Tomato\\ bed\\ 1 value=2 "));
+
+        let pending = backlog.read_pending().unwrap();
+        let names   = pending[0].measurements.iter().map(|m| m.name.as_str()).collect::<Vec<&str>>();
+        assert_eq!(names, vec!["legacy", "current"]);
+        assert_eq!(pending[0].measurements[1].tags["name"], "Tomato bed 1");
+    }
+
+    #[test]
+    fn committed_chunks_are_cut_from_the_archive_head()
+    {
+        let dir = TempDir::new("file-trim");
+
+        let mut backlog = FileBacklog::new(&dir).unwrap();
+
+        let record = |values: std::ops::Range<i64>| {
+            let mut record = Record::new("org", "bucket")
+                .precision(Precision::Seconds);
+
+            for v in values {
+                record.measurement("moisture").field("value", v).timestamp(chrono::Utc.timestamp_opt(v, 0).unwrap());
+            }
+
+            record
+        };
+
+        backlog.write_pending(&record(0 .. 3)).unwrap();
+
+        let pending = backlog.read_pending().unwrap();
+        assert_eq!(pending.len(), 1);
+
+        backlog.write_pending(&record(3 .. 5)).unwrap();    // arrives while the first three are replayed
+        backlog.truncate_pending(&pending[0]).unwrap();
+
+        drop(backlog);
+
+        let mut backlog = FileBacklog::new(&dir).unwrap();
+        let pending     = backlog.read_pending().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].measurements.len(), 2);
+        assert!(matches!(pending[0].measurements[0].fields["value"], Value::Integer(3)));
+
+        let chunks = backlog.archives.values_mut().next().unwrap().records(1).unwrap();
+        assert_eq!(chunks.len(), 2);
+
+        backlog.truncate_pending(&chunks[0]).unwrap();
+
+        // Not cut from the file until the backlog is read or written to again
+        let path = backlog.archives.keys().next().unwrap().clone();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+
+        assert!(matches!(backlog.read_pending().unwrap()[0].measurements[0].fields["value"], Value::Integer(4)));
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 1);
+
+        backlog.truncate_pending(&chunks[1]).unwrap();
+        assert!(backlog.read_pending().unwrap().is_empty());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+    }
+
+    fn moisture(values: std::ops::Range<i64>, age: chrono::Duration) -> Record
+    {
+        let mut record = Record::new("org", "bucket")
+            .precision(Precision::Seconds);
+
+        for v in values {
+            record.measurement("moisture").field("value", v).timestamp(chrono::Utc::now() - age);
+        }
+
+        record
+    }
+
+    fn values(backlog: &mut FileBacklog) -> Vec<String>
+    {
+        backlog.read_pending().unwrap().iter()
+            .flat_map(|r| r.measurements.iter().map(|m| format!("{:?}", m.fields["value"])))
+            .collect()
+    }
+
+    #[test]
+    fn retention_drops_by_age_and_by_policy_once_full()
+    {
+        let root = TempDir::new("file-retention");
+
+        let open = |name: &str, overflow: Overflow| {
+            let dir = root.join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+
+            let mut backlog = FileBacklog::new(&dir).unwrap()
+                .max_age(std::time::Duration::from_secs(3600))
+                .overflow(overflow);
+
+            backlog.write_pending(&moisture(10 .. 20, chrono::Duration::zero())).unwrap();
+
+            let full = backlog.size();
+            backlog.max_size(full)
+        };
+
+        let mut oldest = open("oldest", Overflow::DropOldest);
+        oldest.write_pending(&moisture(20 .. 21, chrono::Duration::zero())).unwrap();
+        assert_eq!(values(&mut oldest)[0], "Integer(12)");
+        assert_eq!(values(&mut oldest).len(), 9);
+        assert_eq!(oldest.dropped().overflow(), 2);
+
+        let mut newest = open("newest", Overflow::DropNewest);
+        newest.write_pending(&moisture(20 .. 22, chrono::Duration::zero())).unwrap();
+        assert_eq!(values(&mut newest).last().unwrap(), "Integer(19)");
+        assert_eq!(newest.dropped().overflow(), 2);
+
+        let mut sample = open("sample", Overflow::Downsample);
+        sample.write_pending(&moisture(20 .. 21, chrono::Duration::zero())).unwrap();
+        assert_eq!(values(&mut sample), vec!["Integer(10)", "Integer(12)", "Integer(14)", "Integer(16)", "Integer(18)", "Integer(20)"]);
+        assert_eq!(sample.dropped().overflow(), 5);
+
+        let dir = root.join("expired");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut aged = FileBacklog::new(&dir).unwrap();
+        aged.write_pending(&moisture(10 .. 13, chrono::Duration::hours(2))).unwrap();
+        aged.write_pending(&moisture(13 .. 14, chrono::Duration::zero())).unwrap();
+
+        let mut aged = FileBacklog::new(&dir).unwrap().max_age(std::time::Duration::from_secs(3600));
+        let dropped  = aged.dropped();
+        assert_eq!(values(&mut aged), vec!["Integer(13)"]);
+        assert_eq!((dropped.expired(), dropped.total()), (3, 3));
+    }
+}
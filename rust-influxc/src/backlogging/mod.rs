@@ -5,10 +5,15 @@
 //! interface `ClientTrait`.
 //!
 mod file;
+mod wal;
+mod retention;
 
 use std::fmt::Debug;
 
 pub use file::FileBacklog;
+pub use wal::WalBacklog;
+pub use retention::Dropped;
+pub use retention::Overflow;
 
 use crate::Record;
 use crate::InfluxResult;
@@ -17,14 +22,23 @@ use crate::InfluxResult;
 /// API definition that any backlog service needs to abide by so the [Client](struct.Client.html) can use it.
 pub trait Backlog: Debug + Send + Sync
 {
-    /// Return any pending records that sits in backlog and requires to be commited.
+    /// Whether records are to be written to this backlog before they are submitted, rather than after they failed
+    /// to be. If so, [truncate_pending](#tymethod.truncate_pending) gets called for each record once committed.
+    fn write_ahead(&self) -> bool
+    {
+        false
+    }
+
+    /// Return any pending records that sits in backlog and requires to be commited, in the order they are to be
+    /// commited.
     fn read_pending(&mut self) -> InfluxResult<Vec<Record>>;
 
     /// Write records that could not be commited, so they get written into backlog for future processing.
     fn write_pending(&mut self, record: &Record) -> InfluxResult<()>;
 
-    /// Empty backlog from pending records. This gets called once all pending records have been
-    /// successfully commited.
+    /// Remove a record returned by [read_pending](#tymethod.read_pending) from backlog. This gets called for each
+    /// pending record as soon as it has been successfully commited, in the order they were returned, so a replay
+    /// that stops part way leaves the remaining records pending.
     fn truncate_pending(&mut self, record: &Record) -> InfluxResult<()>;
 }
 
@@ -0,0 +1,81 @@
+//!
+//! Retention of Backlogged Measurements
+//!
+//! A backlog that grows without bounds during a long outage can fill the disk it lives on. Bounded backlogs drop
+//! measurements beyond their limits according to an [Overflow](enum.Overflow.html) policy and count what they drop.
+//!
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::sync::atomic::AtomicUsize;
+
+
+/// What a backlog does with measurements once it reached its maximum size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow
+{
+    /// Evict the oldest backlogged measurements to make room for new ones.
+    #[default]
+    DropOldest,
+
+    /// Keep the backlog as it is and drop new measurements that do not fit.
+    DropNewest,
+
+    /// Halve the resolution of the backlog, dropping every other measurement, until new ones fit. Keeps the whole
+    /// time span of an outage at a coarser resolution.
+    Downsample,
+}
+
+
+/// Counts of measurements a bounded backlog has dropped. Clones share their counts, so take one from the backlog
+/// before handing it to the [Client](struct.Client.html).
+#[derive(Debug, Clone, Default)]
+pub struct Dropped
+{
+    counts: Arc<Counts>,
+}
+
+
+#[derive(Debug, Default)]
+struct Counts
+{
+    expired:  AtomicUsize,
+    overflow: AtomicUsize,
+}
+
+
+impl Dropped
+{
+    /// Measurements dropped for being older than the maximum age.
+    pub fn expired(&self) -> usize
+    {
+        self.counts.expired.load(Ordering::Relaxed)
+    }
+
+    /// Measurements dropped to keep the backlog within its maximum size.
+    pub fn overflow(&self) -> usize
+    {
+        self.counts.overflow.load(Ordering::Relaxed)
+    }
+
+    /// All measurements dropped.
+    pub fn total(&self) -> usize
+    {
+        self.expired() + self.overflow()
+    }
+
+    pub(crate) fn add_expired(&self, count: usize)
+    {
+        if count > 0 {
+            warn!("Dropped {} expired measurements from backlog", count);
+            self.counts.expired.fetch_add(count, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn add_overflow(&self, count: usize)
+    {
+        if count > 0 {
+            warn!("Dropped {} measurements from full backlog", count);
+            self.counts.overflow.fetch_add(count, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,685 @@
+//!
+//! Write ahead log. Every record is appended and synced to disk before it is sent, and marked committed once the
+//! database accepted it, so a crash or power loss at any point leaves it either committed or pending for replay.
+//!
+//! Records live in segment files named after the sequence number of their first entry. Each entry is framed as
+//! `length | crc32 | sequence | payload` with the checksum covering sequence and payload, so a write torn by power
+//! loss is detected and cut off when the log is reopened. Committed sequence numbers are appended to a separate
+//! commit log. Whenever the log rolls over to a new segment, the segments that are fully committed are deleted, and
+//! the commit log rewritten without them.
+//!
+//! Only the location of pending entries is kept in memory, their payloads are read back from the segments on replay.
+//!
+use super::Backlog;
+
+use crate::Record;
+use crate::Measurement;
+
+use crate::InfluxErrorAnnotate;
+use crate::InfluxResult;
+
+use std::fs::File;
+use std::fs::OpenOptions;
+
+use std::io::Read;
+use std::io::Seek;
+use std::io::Write;
+use std::io::SeekFrom;
+use std::io::ErrorKind;
+
+use std::ffi::OsStr;
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+
+
+const SEGMENT_EXTENSION:    &str = "wal";
+const COMMIT_LOG:           &str = "commits.log";
+const COMMIT_LOG_TMP:       &str = "commits.tmp";
+const ENTRY_HEADER_LEN:     usize = 16;
+const COMMIT_LEN:           usize = 12;
+const MAX_PAYLOAD_LEN:      usize = 64 * 1024 * 1024;
+const DEFAULT_SEGMENT_SIZE: u64 = 4 * 1024 * 1024;
+
+
+/// Sequence numbers and payload locations of the entries in a segment
+type Entries = Vec<(u64, Location)>;
+
+
+/// Backlog that logs every [Record](struct.Record.html) before it is submitted, rather than only after a failure
+/// like [FileBacklog](struct.FileBacklog.html) does. Costs a synced write per record, in exchange for not losing
+/// records to a crash between a failed submit and the backlog write.
+#[derive(Debug)]
+pub struct WalBacklog
+{
+    dir:          PathBuf,
+    segment_size: u64,
+
+    segments: Vec<Segment>,
+    active:   File,
+    commits:  File,
+
+    pending:  BTreeMap<u64, Location>,
+    next_seq: u64,
+}
+
+
+/// Where the payload of an entry is found: the segment, by its first sequence number, and the byte range within.
+#[derive(Debug, Clone, Copy)]
+struct Location
+{
+    segment: u64,
+    offset:  u64,
+    len:     usize,
+}
+
+
+#[derive(Debug)]
+struct Segment
+{
+    path:  PathBuf,
+    first: u64,
+    last:  Option<u64>,
+    size:  u64,
+}
+
+
+impl WalBacklog
+{
+    /// Open, or create, a write ahead log in the given directory and recover any records pending in it.
+    pub fn new<P: AsRef<Path>>(dir: P) -> InfluxResult<Self>
+    {
+        let dir = PathBuf::from(dir.as_ref());
+
+        std::fs::create_dir_all(&dir)
+            .annotate(format!("While creating WAL directory: {:#?}", dir))?;
+
+        let committed = read_commits(&dir.join(COMMIT_LOG))?;
+
+        let mut segments = Vec::new();
+        let mut pending  = BTreeMap::new();
+        let mut next_seq = committed.iter().max().map(|s| s + 1).unwrap_or(0);
+
+        for (first, path) in list_segments(&dir)?
+        {
+            let (entries, size) = read_segment(&path, first)?;
+
+            let last = entries.last().map(|(seq, _)| *seq);
+
+            for (seq, location) in entries
+            {
+                if ! committed.contains(&seq) {
+                    pending.insert(seq, location);
+                }
+            }
+
+            next_seq = next_seq.max(last.map(|s| s + 1).unwrap_or(first));
+
+            segments.push(Segment {path, first, last, size});
+        }
+
+        if segments.is_empty()
+        {
+            let path = segment_path(&dir, next_seq);
+
+            segments.push(Segment {path, first: next_seq, last: None, size: 0});
+        }
+
+        let active  = open_append(&segments.last().unwrap().path)?;
+        let commits = open_append(&dir.join(COMMIT_LOG))?;
+
+        if ! pending.is_empty() {
+            info!("Recovered {} pending records from write ahead log {:#?}", pending.len(), dir);
+        }
+
+        Ok(Self {dir, segment_size: DEFAULT_SEGMENT_SIZE, segments, active, commits, pending, next_seq})
+    }
+
+    /// Size at which to start a new segment. Defaults to 4MiB.
+    pub fn segment_size(mut self, bytes: u64) -> Self
+    {
+        self.segment_size = bytes; self
+    }
+
+    fn roll(&mut self) -> InfluxResult<()>
+    {
+        let path = segment_path(&self.dir, self.next_seq);
+
+        self.active = open_append(&path)?;
+        self.segments.push(Segment {path, first: self.next_seq, last: None, size: 0});
+
+        self.compact()
+    }
+
+    fn commit(&mut self, seq: u64) -> InfluxResult<()>
+    {
+        let mut buffer = Vec::with_capacity(COMMIT_LEN);
+
+        buffer.extend_from_slice(&seq.to_le_bytes());
+        buffer.extend_from_slice(&crc32fast::hash(&seq.to_le_bytes()).to_le_bytes());
+
+        self.commits.write_all(&buffer)?;
+        self.commits.sync_data()?;
+
+        self.pending.remove(&seq);
+
+        Ok(())
+    }
+
+    /// Delete every segment but the active one that has no pending entries left, then drop their sequence numbers
+    /// from the commit log.
+    fn compact(&mut self) -> InfluxResult<()>
+    {
+        let active = self.segments.len() - 1;
+
+        let mut done = Vec::new();
+        let mut keep = Vec::new();
+
+        for (i, segment) in self.segments.drain(..).enumerate()
+        {
+            let committed = match segment.last
+            {
+                Some(last) => { self.pending.range(segment.first ..= last).next().is_none() }
+                None       => { true }
+            };
+
+            if i != active && committed {
+                done.push(segment);
+            } else {
+                keep.push(segment);
+            }
+        }
+
+        self.segments = keep;
+
+        if done.is_empty() {
+            return Ok(());
+        }
+
+        for segment in done.iter()
+        {
+            debug!("Removing committed WAL segment {:#?}", segment.path);
+            std::fs::remove_file(&segment.path)?;
+        }
+
+        let oldest = self.segments[0].first;
+        let live   = read_commits(&self.dir.join(COMMIT_LOG))?;
+
+        let mut buffer = Vec::new();
+
+        for seq in live.into_iter().filter(|s| *s >= oldest)
+        {
+            buffer.extend_from_slice(&seq.to_le_bytes());
+            buffer.extend_from_slice(&crc32fast::hash(&seq.to_le_bytes()).to_le_bytes());
+        }
+
+        let tmp = self.dir.join(COMMIT_LOG_TMP);
+        {
+            let mut file = File::create(&tmp)?;
+
+            file.write_all(&buffer)?;
+            file.sync_all()?;
+        }
+
+        std::fs::rename(&tmp, self.dir.join(COMMIT_LOG))?;
+
+        self.commits = open_append(&self.dir.join(COMMIT_LOG))?;
+
+        Ok(())
+    }
+}
+
+
+impl Backlog for WalBacklog
+{
+    fn write_ahead(&self) -> bool
+    {
+        true
+    }
+
+    fn read_pending(&mut self) -> InfluxResult<Vec<Record>>
+    {
+        let mut records = Vec::with_capacity(self.pending.len());
+        let mut segment = None;
+
+        for (seq, location) in self.pending.iter()
+        {
+            let file = match segment
+            {
+                Some((first, ref mut file)) if first == location.segment => { file }
+
+                _ => {
+                    let path = segment_path(&self.dir, location.segment);
+                    let file = File::open(&path)
+                        .annotate(format!("While reading WAL segment: {:#?}", path))?;
+
+                    &mut segment.insert((location.segment, file)).1
+                }
+            };
+
+            let mut payload = vec![0u8; location.len];
+
+            file.seek(SeekFrom::Start(location.offset))?;
+            file.read_exact(&mut payload)?;
+
+            let mut record = decode(&payload)?;
+
+            record.seq = Some(*seq);
+            records.push(record);
+        }
+
+        Ok(records)
+    }
+
+    fn write_pending(&mut self, record: &Record) -> InfluxResult<()>
+    {
+        let payload = encode(record)?;
+        let seq     = self.next_seq;
+
+        let mut entry = Vec::with_capacity(ENTRY_HEADER_LEN + payload.len());
+        let mut check = crc32fast::Hasher::new();
+
+        check.update(&seq.to_le_bytes());
+        check.update(&payload);
+
+        entry.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        entry.extend_from_slice(&check.finalize().to_le_bytes());
+        entry.extend_from_slice(&seq.to_le_bytes());
+        entry.extend_from_slice(&payload);
+
+        let size = self.segments.last().unwrap().size;
+
+        if size > 0 && size + entry.len() as u64 > self.segment_size {
+            self.roll()?;
+        }
+
+        self.active.write_all(&entry)?;
+        self.active.sync_data()?;
+
+        let segment = self.segments.last_mut().unwrap();
+
+        let location = Location {
+            segment: segment.first,
+            offset:  segment.size + ENTRY_HEADER_LEN as u64,
+            len:     payload.len(),
+        };
+
+        segment.last  = Some(seq);
+        segment.size += entry.len() as u64;
+
+        self.pending.insert(seq, location);
+        self.next_seq += 1;
+
+        Ok(())
+    }
+
+    /// Commits the entry the record was read from, or for a record that was not read from this log, the entry
+    /// written last, as the client commits a record right after writing it ahead.
+    fn truncate_pending(&mut self, record: &Record) -> InfluxResult<()>
+    {
+        let seq = record.seq
+            .or_else(|| self.next_seq.checked_sub(1));
+
+        match seq.filter(|seq| self.pending.contains_key(seq))
+        {
+            Some(seq) => { self.commit(seq) }
+            None      => { warn!("Committed record was not pending in write ahead log: {}", record); Ok(()) }
+        }
+    }
+}
+
+
+fn segment_path(dir: &Path, first: u64) -> PathBuf
+{
+    dir.join(format!("{:020}.{}", first, SEGMENT_EXTENSION))
+}
+
+
+fn list_segments(dir: &Path) -> InfluxResult<Vec<(u64, PathBuf)>>
+{
+    let mut segments = Vec::new();
+
+    let listing = std::fs::read_dir(dir)
+        .annotate(format!("While opening WAL directory: {:#?}", dir))?;
+
+    for entry in listing
+    {
+        let path = entry?.path();
+
+        if path.extension() == Some(OsStr::new(SEGMENT_EXTENSION))
+        {
+            let first = path.file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.parse::<u64>().ok());
+
+            match first
+            {
+                Some(first) => { segments.push((first, path)); }
+                None        => { warn!("Ignoring unexpected file in WAL directory: {:#?}", path); }
+            }
+        }
+    }
+
+    segments.sort();
+
+    Ok(segments)
+}
+
+
+fn open_append(path: &Path) -> InfluxResult<File>
+{
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .annotate(format!("While opening file: {:#?}", path))
+}
+
+
+fn read_all(path: &Path) -> InfluxResult<Vec<u8>>
+{
+    let mut buffer = Vec::new();
+
+    match File::open(path)
+    {
+        Ok(mut file)                                    => { file.read_to_end(&mut buffer)?; }
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e)                                          => { return Err(e).annotate(format!("While reading {:#?}", path)); }
+    }
+
+    Ok(buffer)
+}
+
+
+/// Cut off whatever follows the last intact entry, left behind by a write that was interrupted.
+fn cut_torn_tail(path: &Path, valid: usize, total: usize) -> InfluxResult<()>
+{
+    if valid < total
+    {
+        warn!("Discarding {} bytes of torn write at the end of {:#?}", total - valid, path);
+
+        OpenOptions::new()
+            .write(true)
+            .open(path)?
+            .set_len(valid as u64)?;
+    }
+
+    Ok(())
+}
+
+
+fn read_segment(path: &Path, first: u64) -> InfluxResult<(Entries, u64)>
+{
+    let buffer = read_all(path)?;
+
+    let mut entries = Vec::new();
+    let mut offset  = 0;
+
+    while buffer.len() - offset >= ENTRY_HEADER_LEN
+    {
+        let header = &buffer[offset .. offset + ENTRY_HEADER_LEN];
+
+        let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let crc = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+
+        let mut seq = [0u8; 8];
+        seq.copy_from_slice(&header[8 .. 16]);
+
+        let end = offset + ENTRY_HEADER_LEN + len;
+
+        if len > MAX_PAYLOAD_LEN || end > buffer.len() {
+            break;
+        }
+
+        let payload = &buffer[offset + ENTRY_HEADER_LEN .. end];
+
+        let mut check = crc32fast::Hasher::new();
+
+        check.update(&seq);
+        check.update(payload);
+
+        if check.finalize() != crc {
+            break;
+        }
+
+        let location = Location {segment: first, offset: (offset + ENTRY_HEADER_LEN) as u64, len};
+
+        entries.push((u64::from_le_bytes(seq), location));
+        offset = end;
+    }
+
+    cut_torn_tail(path, offset, buffer.len())?;
+
+    Ok((entries, offset as u64))
+}
+
+
+fn read_commits(path: &Path) -> InfluxResult<HashSet<u64>>
+{
+    let buffer = read_all(path)?;
+
+    let mut commits = HashSet::new();
+    let mut offset  = 0;
+
+    while buffer.len() - offset >= COMMIT_LEN
+    {
+        let mut seq = [0u8; 8];
+        let mut crc = [0u8; 4];
+
+        seq.copy_from_slice(&buffer[offset .. offset + 8]);
+        crc.copy_from_slice(&buffer[offset + 8 .. offset + COMMIT_LEN]);
+
+        if crc32fast::hash(&seq) != u32::from_le_bytes(crc) {
+            break;
+        }
+
+        commits.insert(u64::from_le_bytes(seq));
+        offset += COMMIT_LEN;
+    }
+
+    cut_torn_tail(path, offset, buffer.len())?;
+
+    Ok(commits)
+}
+
+
+/// Organization, bucket and precision on a line each, followed by the measurements in line protocol.
+fn encode(record: &Record) -> InfluxResult<Vec<u8>>
+{
+    Ok(format!("{}\n{}\n{}\n{}", record.org, record.bucket, record.precision, record.to_line_buffer()?).into_bytes())
+}
+
+
+fn decode(payload: &[u8]) -> InfluxResult<Record>
+{
+    let text = std::str::from_utf8(payload)
+        .map_err(|e| format!("Invalid UTF8 in WAL entry: {}", e))?;
+
+    let mut parts = text.splitn(4, '\n');
+
+    let (org, bucket, precision, lines) = match (parts.next(), parts.next(), parts.next(), parts.next())
+    {
+        (Some(o), Some(b), Some(p), Some(l)) => { (o, b, p.parse()?, l) }
+        _                                    => { return Err("Truncated record header in WAL entry".into()); }
+    };
+
+    let mut record = Record::new(org, bucket)
+        .precision(precision);
+
+    for line in lines.lines().filter(|l| ! l.is_empty()) {
+        record.measurements.push(Measurement::from_line(line, &record.precision)?);
+    }
+
+    Ok(record)
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::Precision;
+    use crate::testing::TempDir;
+
+    use chrono::TimeZone;
+    use chrono::Utc;
+
+    fn record(value: i64) -> Record
+    {
+        let mut record = Record::new("org", "bucket")
+            .precision(Precision::Seconds);
+
+        record.measurement("moisture")
+            .tag("name", "Tomato bed 1")
+            .field("value", value)
+            .timestamp(Utc.timestamp_opt(1590969600 + value, 0).unwrap());
+
+        record
+    }
+
+    fn values(backlog: &mut WalBacklog) -> Vec<String>
+    {
+        backlog.read_pending().unwrap()
+            .iter()
+            .map(|r| r.to_line_buffer().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn pending_records_survive_reopening_in_order()
+    {
+        let dir = TempDir::new("wal-reopen");
+        {
+            let mut wal = WalBacklog::new(&dir).unwrap();
+
+            (1 ..= 3).for_each(|v| wal.write_pending(&record(v)).unwrap());
+
+            let pending = wal.read_pending().unwrap();
+            wal.truncate_pending(&pending[1]).unwrap();
+        }
+
+        let mut wal = WalBacklog::new(&dir).unwrap();
+        assert_eq!(values(&mut wal), vec![record(1).to_line_buffer().unwrap(), record(3).to_line_buffer().unwrap()]);
+
+        let pending = wal.read_pending().unwrap();
+
+        wal.write_pending(&record(4)).unwrap();
+        wal.truncate_pending(&pending[0]).unwrap();
+
+        let mut wal = WalBacklog::new(&dir).unwrap();
+        assert_eq!(values(&mut wal), vec![record(3).to_line_buffer().unwrap(), record(4).to_line_buffer().unwrap()]);
+    }
+
+    #[test]
+    fn torn_writes_are_discarded()
+    {
+        let dir = TempDir::new("wal-torn");
+        {
+            let mut wal = WalBacklog::new(&dir).unwrap();
+
+            (1 ..= 2).for_each(|v| wal.write_pending(&record(v)).unwrap());
+
+            let pending = wal.read_pending().unwrap();
+            wal.truncate_pending(&pending[0]).unwrap();
+        }
+
+        let segment = list_segments(&dir).unwrap().pop().unwrap().1;
+        let intact  = std::fs::metadata(&segment).unwrap().len();
+
+        OpenOptions::new().append(true).open(&segment).unwrap().write_all(&[42, 0, 0, 0, 1, 2]).unwrap();
+        OpenOptions::new().append(true).open(dir.join(COMMIT_LOG)).unwrap().write_all(&[2, 0, 0]).unwrap();
+
+        let mut wal = WalBacklog::new(&dir).unwrap();
+        assert_eq!(std::fs::metadata(&segment).unwrap().len(), intact);
+        assert_eq!(values(&mut wal), vec![record(2).to_line_buffer().unwrap()]);
+
+        wal.write_pending(&record(3)).unwrap();
+
+        let mut wal = WalBacklog::new(&dir).unwrap();
+        assert_eq!(values(&mut wal), vec![record(2).to_line_buffer().unwrap(), record(3).to_line_buffer().unwrap()]);
+    }
+
+    #[test]
+    fn records_written_ahead_are_committed_without_rereading()
+    {
+        let dir = TempDir::new("wal-ahead");
+
+        let mut wal = WalBacklog::new(&dir).unwrap();
+
+        // Two equal records are two entries, committed one at a time
+        wal.write_pending(&record(1)).unwrap();
+        wal.write_pending(&record(1)).unwrap();
+        wal.truncate_pending(&record(1)).unwrap();
+
+        assert_eq!(values(&mut wal), vec![record(1).to_line_buffer().unwrap()]);
+        assert_eq!(wal.read_pending().unwrap()[0].seq, Some(0));
+    }
+
+    #[test]
+    fn corrupted_entries_fail_their_checksum()
+    {
+        let dir = TempDir::new("wal-corrupt");
+        {
+            let mut wal = WalBacklog::new(&dir).unwrap();
+
+            wal.write_pending(&record(1)).unwrap();
+        }
+
+        let segment  = list_segments(&dir).unwrap().pop().unwrap().1;
+        let mut data = std::fs::read(&segment).unwrap();
+        let last     = data.len() - 1;
+
+        data[last] ^= 0xff;
+        std::fs::write(&segment, data).unwrap();
+
+        let mut wal = WalBacklog::new(&dir).unwrap();
+        assert!(values(&mut wal).is_empty());
+    }
+
+    #[test]
+    fn committed_segments_are_compacted()
+    {
+        let dir = TempDir::new("wal-compact");
+
+        let mut wal = WalBacklog::new(&dir).unwrap()
+            .segment_size(256);
+
+        for v in 1 ..= 20
+        {
+            wal.write_pending(&record(v)).unwrap();
+
+            if v != 5 {
+                wal.truncate_pending(&record(v)).unwrap();
+            }
+        }
+
+        let segments = list_segments(&dir).unwrap();
+
+        assert!(segments.len() < 20);
+        assert!(segments.len() >= 2);
+        assert!(segments[0].0 <= 4);
+        assert_eq!(values(&mut wal), vec![record(5).to_line_buffer().unwrap()]);
+
+        let pending = wal.read_pending().unwrap();
+        wal.truncate_pending(&pending[0]).unwrap();
+
+        assert!(list_segments(&dir).unwrap().len() >= 2);
+
+        // Segments are only compacted when the log rolls over
+        for v in 21 ..= 25
+        {
+            wal.write_pending(&record(v)).unwrap();
+            wal.truncate_pending(&record(v)).unwrap();
+        }
+
+        assert_eq!(list_segments(&dir).unwrap().len(), 1);
+        assert!(std::fs::metadata(dir.join(COMMIT_LOG)).unwrap().len() < 20 * COMMIT_LEN as u64);
+
+        let mut wal = WalBacklog::new(&dir).unwrap();
+        assert!(values(&mut wal).is_empty());
+    }
+}
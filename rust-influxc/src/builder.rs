@@ -3,10 +3,13 @@
 //!
 use crate::Client;
 use crate::Credentials;
+use crate::ApiVersion;
 
 use crate::Backlog;
 use crate::NoopBacklog;
 
+use crate::RetryPolicy;
+
 use crate::InfluxResult;
 
 
@@ -16,7 +19,10 @@ pub struct ClientBuilder
     url:   String,
     creds: Credentials,
 
-    backlog: Option<Box<dyn Backlog>>
+    version: ApiVersion,
+
+    backlog: Option<Box<dyn Backlog>>,
+    retry:   Option<Box<dyn RetryPolicy>>,
 }
 
 
@@ -28,10 +34,20 @@ impl ClientBuilder
         Self {
             url, creds,
 
+            version: ApiVersion::V2,
+
             backlog: None,
+            retry:   None,
         }
     }
 
+    /// Talk to InfluxDB 1.x rather than 2.x. See [ApiVersion](enum.ApiVersion.html) for how records name the database
+    /// and retention policy to write to.
+    pub fn api_version(mut self, version: ApiVersion) -> Self
+    {
+        self.version = version; self
+    }
+
     /// Add backlog to client, so records and measurements get stored as log as
     /// they fail to be committed. Either due to conectivity or misconfiguration.
     pub fn backlog<B: Backlog + 'static>(mut self, backlog: B) -> Self
@@ -39,6 +55,13 @@ impl ClientBuilder
         self.backlog = Some(Box::new(backlog)); self
     }
 
+    /// Add a retry policy, e.g. an [ExponentialBackoff](struct.ExponentialBackoff.html), to attempt failed writes
+    /// again before giving up on them. Applies to backlog replays as well. Without one, writes are not retried.
+    pub fn retry<R: RetryPolicy + 'static>(mut self, policy: R) -> Self
+    {
+        self.retry = Some(Box::new(policy)); self
+    }
+
     /// Consume this builder to assemble and return the final Client instance
     /// for usage.
     pub fn finish(self) -> InfluxResult<Client>
@@ -49,6 +72,12 @@ impl ClientBuilder
             None    => { Box::new(NoopBacklog::new()) }
         };
 
-        Client::new(self.url, self.creds, backlog)
+        let mut client = Client::connect(self.url, self.creds, backlog, self.version)?;
+
+        if let Some(retry) = self.retry {
+            client.retry = retry;
+        }
+
+        Ok(client)
     }
 }
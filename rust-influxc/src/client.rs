@@ -1,22 +1,33 @@
 //!
 //! Client Connection and Interface to Database
 //!
+use crate::Table;
 use crate::Record;
 use crate::Credentials;
+use crate::ApiVersion;
 use crate::ClientBuilder;
 
 use crate::Backlog;
 
+use crate::NoRetry;
+use crate::RetryPolicy;
+
 use crate::InfluxError;
 use crate::InfluxResult;
 
-use crate::ApiDelayError;
-use crate::ApiGenericError;
-use crate::ApiOversizeError;
-use crate::ApiMalformationError;
-
 use crate::b64;
 
+use crate::error::retry_after_header;
+
+use crate::query::query_body;
+use crate::query::parse_tables;
+
+use crate::influxql::parse_results;
+
+use crate::api::signin_result;
+use crate::api::v1_query_result;
+use crate::api::v2_query_result;
+
 use crate::ReqwUrl;
 use crate::ReqwClient;
 use crate::ReqwMethod;
@@ -32,6 +43,22 @@ pub struct Client
     client: ReqwClient,
 
     backlog: Box<dyn Backlog>,
+
+    version: ApiVersion,
+
+    pub(crate) retry: Box<dyn RetryPolicy>,
+}
+
+
+/// How far a replay of the backlog got, counted in measurements.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Replay
+{
+    /// Backlogged measurements committed to InfluxDB and removed from the backlog.
+    pub replayed: usize,
+
+    /// Backlogged measurements still pending.
+    pub remaining: usize,
 }
 
 
@@ -45,6 +72,11 @@ impl Client
 
     /// Directly construct this [Client](struct.Client.html).
     pub fn new(url: String, creds: Credentials, backlog: Box<dyn Backlog>) -> InfluxResult<Self>
+    {
+        Self::connect(url, creds, backlog, ApiVersion::V2)
+    }
+
+    pub(crate) fn connect(url: String, creds: Credentials, backlog: Box<dyn Backlog>, version: ApiVersion) -> InfluxResult<Self>
     {
         // let ignore_cert = std::env::var("INFLUX_UNSAFE_TLS").ok()
         //     .unwrap_or_else(|| "false".to_owned())
@@ -60,9 +92,12 @@ impl Client
             Err(e)  => { return Err(format!("Failed to parse URL: {} due to {}", url, e).into()) }
         };
 
-        let mut this = Self {client, url, creds, backlog};
+        let mut this = Self {client, url, creds, backlog, version, retry: Box::new(NoRetry)};
 
-        this.authenticate()?;
+        // InfluxDB 1.x takes basic credentials with every request instead
+        if version == ApiVersion::V2 {
+            this.authenticate()?;
+        }
 
         Ok(this)
     }
@@ -71,89 +106,199 @@ impl Client
     pub fn write(&mut self, record: &Record) -> InfluxResult<()>
     {
         if let Err(e) = self.write_backlog() {
-            self.backlog.write_pending(&record)?; Err(e)
+            self.backlog.write_pending(record)?; Err(e)
+        }
+        else if self.backlog.write_ahead()
+        {
+            self.backlog.write_pending(record)?;
+
+            let result = self.commit_record(record);
+
+            if result.is_ok() {
+                self.backlog.truncate_pending(record)?;
+            }
+
+            result
         }
         else
         {
-            let result = self.write_record(&record);
+            let result = self.commit_record(record);
 
             if result.is_err() {
-                self.backlog.write_pending(&record)?;
+                self.backlog.write_pending(record)?;
             }
 
             result
         }
     }
 
-    /// Submit pending/backlogged [Records](struct.Record.html) to writing. It will attempt to flush them to database.
-    pub fn flush(&mut self) -> InfluxResult<()>
+    /// Submit pending/backlogged [Records](struct.Record.html) to writing. It will attempt to flush all of them to
+    /// database in the order they were backlogged, stopping at the first that fails. On failure the error is an
+    /// [InfluxError::Replay](enum.InfluxError.html#variant.Replay) telling how far the replay got.
+    pub fn flush(&mut self) -> InfluxResult<Replay>
     {
         self.write_backlog()
     }
+
+    /// Run a Flux query on behalf of the organization and return the [Tables](struct.Table.html) of its result.
+    pub fn query(&self, org: &str, flux: &str) -> InfluxResult<Vec<Table>>
+    {
+        let mut url = self.url.clone();
+
+        url.set_path("/api/v2/query");
+
+        let mut builder = self.client.request(ReqwMethod::POST, url)
+            .query(&[("org", org)])
+            .header("Accept", "application/csv")
+            .json(&query_body(flux));
+
+        builder = self.inject_credentials(builder)?;
+
+        debug!("Request: {:#?}", builder);
+
+        let reply       = builder.send()?;
+        let status      = reply.status().as_u16();
+        let retry_after = retry_after_header(reply.headers());
+
+        parse_tables(&v2_query_result(status, retry_after, reply.text()?)?)
+    }
+
+    /// Run a Flux query and [decode](struct.Table.html#method.decode) the rows of every table in its result.
+    pub fn query_as<T: serde::de::DeserializeOwned>(&self, org: &str, flux: &str) -> InfluxResult<Vec<T>>
+    {
+        let mut rows = Vec::new();
+
+        for table in self.query(org, flux)? {
+            rows.extend(table.decode::<T>()?);
+        }
+
+        Ok(rows)
+    }
+
+    /// Run an InfluxQL query against a database of InfluxDB 1.x, or a 2.x bucket mapped to one, and return a
+    /// [Table](struct.Table.html) for each series of its result.
+    pub fn influxql(&self, db: &str, influxql: &str) -> InfluxResult<Vec<Table>>
+    {
+        let mut url = self.url.clone();
+
+        url.set_path("/query");
+
+        let mut builder = self.client.request(ReqwMethod::POST, url)
+            .query(&[("db", db), ("q", influxql)]);
+
+        builder = self.inject_credentials(builder)?;
+
+        debug!("Request: {:#?}", builder);
+
+        let reply       = builder.send()?;
+        let status      = reply.status().as_u16();
+        let retry_after = retry_after_header(reply.headers());
+
+        parse_results(&v1_query_result(status, retry_after, reply.text()?)?)
+    }
+
+    /// Run an InfluxQL query and [decode](struct.Table.html#method.decode) the rows of every series in its result.
+    pub fn influxql_as<T: serde::de::DeserializeOwned>(&self, db: &str, influxql: &str) -> InfluxResult<Vec<T>>
+    {
+        let mut rows = Vec::new();
+
+        for table in self.influxql(db, influxql)? {
+            rows.extend(table.decode::<T>()?);
+        }
+
+        Ok(rows)
+    }
 }
 
 
 /// Private interface
 impl Client
 {
-    fn write_backlog(&mut self) -> InfluxResult<()>
+    fn write_backlog(&mut self) -> InfluxResult<Replay>
     {
         let records = self.backlog.read_pending()?;
 
+        let mut replay = Replay {
+            replayed:  0,
+            remaining: records.iter().map(|r| r.measurements.len()).sum(),
+        };
+
+        if replay.remaining > 0 {
+            info!("Found {} backlogged entries, attempting to commit", replay.remaining);
+        }
+
         for record in records.iter()
         {
-            info!("Found {} backlogged entries, attempting to commit", records.len());
+            if let Err(e) = self.commit_record(record) {
+                warn!("Unable to commit backlogged record, {} of {} entries replayed", replay.replayed, replay.replayed + replay.remaining);
+                return Err(InfluxError::Replay(replay, Box::new(e)));
+            }
 
-            if let Err(e) = self.write_record(&record) {
-                return Err(InfluxError::Error(format!("Unable to commit backlogged record: {}", e)));
+            if let Err(e) = self.backlog.truncate_pending(record)
+            {
+                let msg = format!("Failed to eliminate/truncate record from backlog: {}", e);
+                error!("{}", msg);
+                panic!("{}", msg);
             }
-            else
+
+            replay.replayed  += record.measurements.len();
+            replay.remaining -= record.measurements.len();
+        }
+
+        if replay.replayed > 0 {
+            info!("Replayed {} backlogged entries", replay.replayed);
+        }
+
+        Ok(replay)
+    }
+
+    /// Write the record, attempting again for as long as the retry policy says so.
+    fn commit_record(&self, record: &Record) -> InfluxResult<()>
+    {
+        let mut attempt = 1;
+
+        loop
+        {
+            let error = match self.write_record(record)
             {
-                let result = self.backlog.truncate_pending(&record);
+                Ok(())  => { return Ok(()); }
+                Err(e)  => { e }
+            };
 
-                if let Err(e) = result
-                {
-                    let msg = format!("Failed to eliminate/truncate record from backlog: {}", e);
-                    error!("{}", msg);
-                    panic!("{}", msg);
-                }
-                else {
-                    return Ok(());
+            match self.retry.retry(attempt, &error)
+            {
+                Some(delay) => {
+                    warn!("Write attempt {} failed, retrying in {:?}: {}", attempt, delay, error);
+                    std::thread::sleep(delay);
+                    attempt += 1;
                 }
+
+                None => { return Err(error); }
             }
         }
-
-        Ok(())
     }
 
     fn write_record(&self, record: &Record) -> InfluxResult<()>
     {
         let mut url = self.url.clone();
 
-        url.set_path("/api/v2/write");
+        url.set_path(self.version.write_path());
 
         let mut builder = self.client.request(ReqwMethod::POST, url);
 
-        builder = record.to_write_request(builder)?;
+        builder = record.to_write_request(self.version, builder)?;
         builder = self.inject_credentials(builder)?;
 
         debug!("Request: {:#?}", builder);
 
-        let reply = builder.send()?;
+        let reply       = builder.send()?;
+        let status      = reply.status().as_u16();
+        let retry_after = retry_after_header(reply.headers());
 
-        match reply.status().as_u16()
-        {
-            204 => { info!("Written: {}", record); Ok(()) }
+        self.version.write_result(status, retry_after, &reply.text()?)?;
 
-            400 => { Err(InfluxError::WriteMalformed(reply.json::<ApiMalformationError>()?)) }
-            401 => { Err(InfluxError::WriteUnauthorized(reply.json::<ApiGenericError>()?)) }
-            403 => { Err(InfluxError::WriteUnauthenticated(reply.json::<ApiGenericError>()?)) }
-            413 => { Err(InfluxError::WriteOversized(reply.json::<ApiOversizeError>()?)) }
-            429 => { Err(InfluxError::WriteOverquota(reply.json::<ApiDelayError>()?)) }
-            503 => { Err(InfluxError::WriteUnready(reply.json::<ApiDelayError>()?)) }
-
-            _   => { Err(InfluxError::WriteUnknown(reply.json::<ApiGenericError>()?)) }
-        }
+        info!("Written: {}", record);
+        Ok(())
     }
 
     fn authenticate(&mut self) -> InfluxResult<()>
@@ -171,52 +316,40 @@ impl Client
 
             debug!("Request: {:#?}", req);
 
-            let rep = req.send()?;
+            let rep     = req.send()?;
+            let status  = rep.status().as_u16();
+            let headers = rep.headers().clone();
+            let session = signin_result(status, &headers, &rep.text()?)?;
 
-            match rep.status().as_u16()
-            {
-                204 => {
-                    if let Some(cookie) = rep.headers().get("Set-Cookie")
-                    {
-                        let session = {
-                            if let Ok(s) = cookie.to_str() {
-                                s.to_owned()
-                            } else {
-                                return Err(format!("Failed to extract session cookie string: {:#?}", cookie).into());
-                            }
-                        };
-
-                        self.creds = Credentials::Basic {user: user.clone(), passwd: passwd.clone(), cookie: Some(session)};
-                    }
-                    else {
-                        return Err("Missing session cookie after successfull basic auth".into());
-                    }
-                }
-
-                401 => { return Err(InfluxError::AuthUnauthorized(rep.json::<ApiGenericError>()?)); }
-                403 => { return Err(InfluxError::AuthAccountDisabled(rep.json::<ApiGenericError>()?)); }
-                _   => { return Err(InfluxError::AuthUnknown(rep.json::<ApiGenericError>()?)); }
-            }
+            self.creds = Credentials::Basic {user: user.clone(), passwd: passwd.clone(), cookie: Some(session)};
         }
 
         Ok(())
     }
 
-    fn inject_credentials(&self, builder: ReqwRequestBuilder) -> InfluxResult<ReqwRequestBuilder>
+    /// Request to the given path of the server, with credentials attached.
+    pub(crate) fn request(&self, method: ReqwMethod, path: &str) -> InfluxResult<ReqwRequestBuilder>
     {
-        match &self.creds
-        {
-            Credentials::Basic{user: _, passwd: _, cookie: None} => {
-                Err("Missing session cookie from basic auth. This should not have happened!".into())
-            }
+        let mut url = self.url.clone();
 
-            Credentials::Basic{user: _, passwd: _, cookie: Some(session)} => {
-                Ok(builder.header("Cookie", session))
-            }
+        url.set_path(path);
 
-            Credentials::Token{token} => {
-                Ok(builder.header("Authorization", format!("Token {}", token)))
-            }
+        self.inject_credentials(self.client.request(method, url))
+    }
+
+    pub(crate) fn version(&self) -> ApiVersion
+    {
+        self.version
+    }
+
+    fn inject_credentials(&self, builder: ReqwRequestBuilder) -> InfluxResult<ReqwRequestBuilder>
+    {
+        if let (ApiVersion::V1, Credentials::Basic{user, passwd, ..}) = (self.version, &self.creds) {
+            return Ok(builder.query(&[("u", user), ("p", passwd)]));
         }
+
+        let (name, value) = self.creds.header()?;
+
+        Ok(builder.header(name, value))
     }
 }
@@ -4,9 +4,19 @@
 use crate::JsonError;
 
 use crate::ReqwError;
+use crate::ReqwResponse;
+use crate::ReqwHeaderMap;
+
+use crate::json;
+
+use crate::Utc;
 
 use crate::Deserialize;
 
+use crate::client::Replay;
+
+use std::time::Duration;
+
 
 pub(crate) type InfluxResult<T> = Result<T, InfluxError>;
 
@@ -74,6 +84,9 @@ pub enum InfluxError
     /// Chaining of [ReqwestError](reqwest::error::Error)
     Reqwest(ReqwError),
 
+    /// Backlog replay stopped at a record that could not be committed. Tells how far it got and why it stopped.
+    Replay(Replay, Box<InfluxError>),
+
     /// Authentication API: Not authorized (log-in missing) error.
     AuthUnauthorized(ApiGenericError),
 
@@ -103,6 +116,30 @@ pub enum InfluxError
 
     /// Write API: InfluxDB server side error. Investigate.
     WriteUnknown(ApiGenericError),
+
+    /// Query API: Malformed query. Check the Flux syntax.
+    QueryMalformed(ApiGenericError),
+
+    /// Query API: Not authorized to read from that bucket. Check permissions in InfluxDB GUI.
+    QueryUnauthorized(ApiGenericError),
+
+    /// Query API: Request limit reached. Try again later.
+    QueryOverquota(ApiDelayError),
+
+    /// Query API: InfluxDB server side error. Investigate.
+    QueryUnknown(ApiGenericError),
+
+    /// Management API: Malformed request, e.g. an invalid bucket name or retention rule.
+    ManageMalformed(ApiGenericError),
+
+    /// Management API: Not authorized to read or create buckets or organizations. Check the token permissions.
+    ManageUnauthorized(ApiGenericError),
+
+    /// Management API: A bucket of that name already exists.
+    ManageConflict(ApiGenericError),
+
+    /// Management API: InfluxDB server side error. Investigate.
+    ManageUnknown(ApiGenericError),
 }
 
 
@@ -144,6 +181,141 @@ pub struct ApiOversizeError
 }
 
 
+impl InfluxError
+{
+    /// Whether the error may clear up by itself, like a lost connection or an overloaded server, making the same
+    /// request worth another attempt.
+    pub fn is_transient(&self) -> bool
+    {
+        match *self
+        {
+            Self::Annotated(_, ref err) => { err.is_transient() }
+            Self::Replay(_, ref err)    => { err.is_transient() }
+
+            Self::Reqwest(ref err) => { ! err.is_builder() }
+
+            Self::WriteOverquota(_) => { true }
+            Self::WriteUnready(_)   => { true }
+            Self::QueryOverquota(_) => { true }
+
+            _ => { false }
+        }
+    }
+
+    /// Delay the server asked for before attempting again, if any.
+    pub fn retry_after(&self) -> Option<Duration>
+    {
+        match *self
+        {
+            Self::Annotated(_, ref err) => { err.retry_after() }
+            Self::Replay(_, ref err)    => { err.retry_after() }
+
+            Self::WriteOverquota(ref inner) => { inner.delay() }
+            Self::WriteUnready(ref inner)   => { inner.delay() }
+            Self::QueryOverquota(ref inner) => { inner.delay() }
+
+            _ => { None }
+        }
+    }
+}
+
+
+impl ApiGenericError
+{
+    pub(crate) fn new(code: String, message: String) -> Self
+    {
+        Self {code, message}
+    }
+
+    /// Read the error from the JSON body, or fall back to the status and raw body if it is not JSON.
+    pub(crate) fn from_reply(reply: ReqwResponse) -> Self
+    {
+        let status = reply.status().as_u16();
+
+        Self::from_body(status, &reply.text().unwrap_or_default())
+    }
+
+    /// Read the error from a JSON body, such as a proxy's HTML error page would not be.
+    pub(crate) fn from_body(status: u16, body: &str) -> Self
+    {
+        json::from_str(body).unwrap_or_else(|_| Self::new(status.to_string(), body.trim().to_owned()))
+    }
+}
+
+
+impl ApiMalformationError
+{
+    pub(crate) fn new(code: String, message: String) -> Self
+    {
+        Self {code, err: None, line: None, message, op: None}
+    }
+
+    pub(crate) fn from_body(status: u16, body: &str) -> Self
+    {
+        json::from_str(body).unwrap_or_else(|_| Self::new(status.to_string(), body.trim().to_owned()))
+    }
+}
+
+
+impl ApiOversizeError
+{
+    pub(crate) fn new(code: String, message: String) -> Self
+    {
+        Self {code, maxlen: 0, message}
+    }
+
+    pub(crate) fn from_body(status: u16, body: &str) -> Self
+    {
+        json::from_str(body).unwrap_or_else(|_| Self::new(status.to_string(), body.trim().to_owned()))
+    }
+}
+
+
+impl ApiDelayError
+{
+    /// Read the delay from the `Retry-After` header, given in seconds or as a date, or else from the JSON body.
+    pub(crate) fn new(retry_after: Option<String>, body: &str) -> Self
+    {
+        let header = retry_after.as_deref()
+            .and_then(parse_retry_after);
+
+        let body = json::from_str::<ApiDelayError>(body)
+            .map(|e| e.delay)
+            .unwrap_or(0);
+
+        Self {delay: header.unwrap_or(0).max(body)}
+    }
+
+    fn delay(&self) -> Option<Duration>
+    {
+        if self.delay > 0 {
+            Some(Duration::from_secs(self.delay as u64))
+        } else {
+            None
+        }
+    }
+}
+
+
+pub(crate) fn retry_after_header(headers: &ReqwHeaderMap) -> Option<String>
+{
+    headers.get("Retry-After")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_owned())
+}
+
+
+fn parse_retry_after(value: &str) -> Option<i64>
+{
+    let value = value.trim();
+
+    value.parse::<i64>().ok().or_else(|| {
+        chrono::DateTime::parse_from_rfc2822(value).ok()
+            .map(|at| (at.timestamp() - Utc::now().timestamp()).max(0))
+    })
+}
+
+
 impl<T, E> InfluxErrorAnnotate<T> for Result<T, E>
     where E: Into<InfluxError> + std::error::Error
 {
@@ -181,6 +353,10 @@ impl std::fmt::Display for InfluxError
             Self::Json(ref err)       => { write!(f, "Json Error: {}",    err) }
             Self::Reqwest(ref err)    => { write!(f, "Reqwest Error: {}", err) }
 
+            Self::Replay(ref replay, ref err) => {
+                write!(f, "Unable to commit backlogged record ({} replayed, {} remaining): {}", replay.replayed, replay.remaining, err)
+            }
+
             Self::AuthUnauthorized(ref inner)     => { write!(f, "AuthUnauthorized({})",     inner) }
             Self::AuthAccountDisabled(ref inner)  => { write!(f, "AuthAccountDisabled({})",  inner) }
             Self::AuthUnknown(ref inner)          => { write!(f, "AuthUnknown({})",          inner) }
@@ -191,6 +367,14 @@ impl std::fmt::Display for InfluxError
             Self::WriteOverquota(ref inner)       => { write!(f, "WriteOverquota({})",       inner) }
             Self::WriteUnready(ref inner)         => { write!(f, "WriteUnready({})",         inner) }
             Self::WriteUnknown(ref inner)         => { write!(f, "WriteUnknown({})",         inner) }
+            Self::QueryMalformed(ref inner)       => { write!(f, "QueryMalformed({})",       inner) }
+            Self::QueryUnauthorized(ref inner)    => { write!(f, "QueryUnauthorized({})",    inner) }
+            Self::QueryOverquota(ref inner)       => { write!(f, "QueryOverquota({})",       inner) }
+            Self::QueryUnknown(ref inner)         => { write!(f, "QueryUnknown({})",         inner) }
+            Self::ManageMalformed(ref inner)      => { write!(f, "ManageMalformed({})",      inner) }
+            Self::ManageUnauthorized(ref inner)   => { write!(f, "ManageUnauthorized({})",   inner) }
+            Self::ManageConflict(ref inner)       => { write!(f, "ManageConflict({})",       inner) }
+            Self::ManageUnknown(ref inner)        => { write!(f, "ManageUnknown({})",        inner) }
         }
     }
 }
@@ -253,6 +437,8 @@ impl std::error::Error for InfluxError
             InfluxError::Json(ref err)    => { Some(err) }
             InfluxError::Reqwest(ref err) => { Some(err) }
 
+            InfluxError::Replay(_, ref err) => { Some(err) }
+
             InfluxError::AuthUnauthorized(_)     => { None }
             InfluxError::AuthAccountDisabled(_)  => { None }
             InfluxError::AuthUnknown(_)          => { None }
@@ -263,6 +449,43 @@ impl std::error::Error for InfluxError
             InfluxError::WriteOverquota(_)       => { None }
             InfluxError::WriteUnready(_)         => { None }
             InfluxError::WriteUnknown(_)         => { None }
+            InfluxError::QueryMalformed(_)       => { None }
+            InfluxError::QueryUnauthorized(_)    => { None }
+            InfluxError::QueryOverquota(_)       => { None }
+            InfluxError::QueryUnknown(_)         => { None }
+            InfluxError::ManageMalformed(_)      => { None }
+            InfluxError::ManageUnauthorized(_)   => { None }
+            InfluxError::ManageConflict(_)       => { None }
+            InfluxError::ManageUnknown(_)        => { None }
         }
     }
 }
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn retry_after_in_seconds_or_as_a_date()
+    {
+        assert_eq!(parse_retry_after(" 120 "), Some(120));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(0));
+
+        let later = (Utc::now() + chrono::Duration::seconds(600)).to_rfc2822();
+        assert!(matches!(parse_retry_after(&later), Some(d) if d > 590 && d <= 600));
+
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn error_bodies_that_are_not_json_keep_status_and_text()
+    {
+        let error = ApiGenericError::from_body(502, "<html>Bad Gateway</html>\n");
+        assert_eq!(error.to_string(), "code=502, message=<html>Bad Gateway</html>");
+
+        let error = ApiGenericError::from_body(401, "{\"code\":\"unauthorized\",\"message\":\"no token\"}");
+        assert_eq!(error.to_string(), "code=unauthorized, message=no token");
+    }
+}
@@ -0,0 +1,191 @@
+//!
+//! InfluxQL Query Responses of InfluxDB 1.x
+//!
+//! Each series of the JSON response becomes a [Table](struct.Table.html), with the tags of the series as columns of
+//! its group key, so that 1.x results decode the same way Flux results do.
+//!
+use crate::Table;
+use crate::Column;
+
+use crate::InfluxResult;
+
+use crate::json;
+
+use std::collections::BTreeMap;
+
+
+#[derive(Deserialize)]
+struct Response
+{
+    #[serde(default)]
+    results: Vec<Statement>,
+
+    error: Option<String>,
+}
+
+
+#[derive(Deserialize)]
+struct Statement
+{
+    #[serde(default)]
+    series: Vec<Series>,
+
+    error: Option<String>,
+}
+
+
+#[derive(Deserialize)]
+struct Series
+{
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+
+    columns: Vec<String>,
+
+    #[serde(default)]
+    values: Vec<Vec<json::Value>>,
+}
+
+
+pub(crate) fn parse_results(body: &str) -> InfluxResult<Vec<Table>>
+{
+    let response = json::from_str::<Response>(body)?;
+
+    if let Some(error) = response.error {
+        return Err(format!("Query failed: {}", error).into());
+    }
+
+    let mut tables = Vec::new();
+
+    for statement in response.results
+    {
+        if let Some(error) = statement.error {
+            return Err(format!("Query failed: {}", error).into());
+        }
+
+        tables.extend(statement.series.into_iter().map(table));
+    }
+
+    Ok(tables)
+}
+
+
+fn table(series: Series) -> Table
+{
+    let mut columns = series.columns.iter()
+        .enumerate()
+        .map(|(i, name)| Column {
+            name:     name.clone(),
+            datatype: datatype(name, series.values.iter().filter_map(|row| row.get(i))),
+            group:    false,
+            default:  String::new(),
+        })
+        .collect::<Vec<Column>>();
+
+    columns.extend(series.tags.keys().map(|name| Column {
+        name:     name.clone(),
+        datatype: "string".to_owned(),
+        group:    true,
+        default:  String::new(),
+    }));
+
+    let rows = series.values.iter()
+        .map(|row| {
+            row.iter()
+                .map(cell)
+                .chain(series.tags.values().cloned())
+                .collect()
+        })
+        .collect();
+
+    Table {columns, rows}
+}
+
+
+/// Flux data type of a column, from the JSON values it holds. A column holding any fractional number is a `double`.
+fn datatype<'v, I: Iterator<Item=&'v json::Value>>(name: &str, values: I) -> String
+{
+    let mut datatype = "string";
+
+    for value in values
+    {
+        datatype = match value
+        {
+            json::Value::Null                        => { continue; }
+            json::Value::Bool(_)                     => { "boolean" }
+            json::Value::Number(n) if n.is_f64()     => { return "double".to_owned(); }
+            json::Value::Number(n) if n.is_i64()     => { "long" }
+            json::Value::Number(_)                   => { "unsignedLong" }
+            json::Value::String(_) if name == "time" => { "dateTime:RFC3339" }
+            _                                        => { "string" }
+        };
+    }
+
+    datatype.to_owned()
+}
+
+
+fn cell(value: &json::Value) -> String
+{
+    match value
+    {
+        json::Value::Null      => { String::new() }
+        json::Value::String(s) => { s.clone() }
+        other                  => { other.to_string() }
+    }
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::DateTime;
+
+    const RESPONSE: &str = r#"{"results":[{"statement_id":0,"series":[
+        {"name":"moisture","tags":{"name":"tomato"},"columns":["time","min","count"],
+         "values":[["2020-06-01T11:00:00Z",612,3],["2020-06-01T12:00:00Z",587.5,2],["2020-06-01T13:00:00Z",null,0]]},
+        {"name":"moisture","tags":{"name":"potato"},"columns":["time","min","count"],
+         "values":[["2020-06-01T11:00:00Z",400,1]]}
+    ]}]}"#;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Min
+    {
+        time:  DateTime,
+        min:   Option<f64>,
+        count: i64,
+        name:  String,
+    }
+
+    #[test]
+    fn series_become_tables_with_tags_as_group_columns()
+    {
+        let tables = parse_results(RESPONSE).unwrap();
+
+        assert_eq!(tables.len(), 2);
+        assert_eq!(tables[0].columns()[1].datatype, "double");
+        assert_eq!(tables[0].columns()[2].datatype, "long");
+        assert!(tables[0].columns()[3].group);
+
+        let rows = tables[0].decode::<Min>().unwrap();
+        assert_eq!(rows[1], Min {
+            time: "2020-06-01T12:00:00Z".parse().unwrap(), min: Some(587.5), count: 2, name: "tomato".to_owned()
+        });
+        assert_eq!(rows[2].min, None);
+        assert_eq!(tables[1].value(0, "name"), Some("potato"));
+    }
+
+    #[test]
+    fn statement_errors_fail_the_query()
+    {
+        assert_eq!(parse_results(r#"{"results":[{"statement_id":0}]}"#).unwrap(), vec![]);
+
+        let error = parse_results(r#"{"results":[{"statement_id":0,"error":"database not found: garden"}]}"#);
+        assert_eq!(error.unwrap_err().to_string(), "Query failed: database not found: garden");
+
+        let error = parse_results(r#"{"error":"error parsing query"}"#);
+        assert_eq!(error.unwrap_err().to_string(), "Query failed: error parsing query");
+    }
+}
@@ -5,24 +5,24 @@
 
 ### What this crate provides
 
-- Support for InfluxDB 2.x.
+- Support for InfluxDB 2.x, and for 1.x writes and InfluxQL queries.
 - Backlog storage of Record's on failure to commit due to connectivity or configuration issues.
+- Write ahead log backlog, so that records also survive a crash or power loss while being committed.
+- Limits on backlog size and age, dropping the oldest or newest measurements or downsampling once full.
+- Retries of failed writes with exponential backoff and jitter, honouring delays asked for by the server.
+- An `AsyncClient` for async/await behind the `async` feature, with the same write, flush and query surface.
 - Build-in compression of requests.
-
-### What it does not provide
-
-- Support for InfluxDB 1.x
+- Parsing of line protocol, e.g. to import data exported from a bucket.
+- Flux queries, with results decoded into your own types through serde.
+- Listing and creating buckets with retention rules, looking up organizations, and health and readiness checks.
 
 ### What is on the roadmap
 
-- Support for async/await as a feature. [#3](https://github.com/voipir/rust-influxc/issues/3)
 - Reduction of dependencies by switching the underlying reqwest library with hyper. [#4](https://github.com/voipir/rust-influxc/issues/4)
-- Support for sending, processing responses to queries. [#5](https://github.com/voipir/rust-influxc/issues/5)
-- Support for mapping native types to query response data like sqlx. [#6](https://github.com/voipir/rust-influxc/issues/6)
 
 ## Basic Usage
 
-```rust
+```rust,no_run
 use influxc::Client;
 use influxc::FileBacklog;
 
@@ -69,6 +69,39 @@ fn main() -> Result<(), InfluxError>
 }
 ```
 
+## Querying
+
+```rust,no_run
+use influxc::Client;
+use influxc::Credentials;
+use influxc::InfluxError;
+
+#[derive(serde::Deserialize)]
+struct Temperature
+{
+    #[serde(rename="_time")]  time:  chrono::DateTime<chrono::Utc>,
+    #[serde(rename="_value")] value: f64,
+}
+
+fn main() -> Result<(), InfluxError>
+{
+    let creds  = Credentials::from_token("token");
+    let client = Client::build("http://127.0.0.1:8086".into(), creds).finish()?;
+
+    let rows = client.query_as::<Temperature>("org", r#"
+        from(bucket: "bucket")
+            |> range(start: -1h)
+            |> filter(fn: (r) => r._measurement == "sensor1" and r._field == "temp")
+    "#)?;
+
+    for row in rows {
+        println!("{} {}", row.time, row.value);
+    }
+
+    Ok(())
+}
+```
+
 */
 #![deny(missing_docs)]
 
@@ -95,22 +128,40 @@ use reqwest::Error  as ReqwError;
 use reqwest::Method as ReqwMethod;
 
 use reqwest::blocking::Client         as ReqwClient;
+use reqwest::header::HeaderMap        as ReqwHeaderMap;
+
+use reqwest::blocking::Response       as ReqwResponse;
 use reqwest::blocking::RequestBuilder as ReqwRequestBuilder;
 
+#[cfg(feature = "async")] use reqwest::Client         as ReqwAsyncClient;
+#[cfg(feature = "async")] use reqwest::RequestBuilder as ReqwAsyncRequestBuilder;
+
 type Utc      = chrono::Utc;
 type DateTime = chrono::DateTime<chrono::Utc>;
 
 // Internals/Exports
+mod api;
 mod auth;
 mod error;
 mod value;
 mod client;
+mod query;
+mod influxql;
+mod management;
 mod record;
 mod builder;
 mod precision;
 mod backlogging;
+mod retry;
+mod line;
+
+#[cfg(feature = "async")]
+mod asynchronous;
 mod measurement;
 
+#[cfg(test)]
+mod testing;
+
 use error::InfluxResult;
 use error::InfluxErrorAnnotate;
 
@@ -119,6 +170,8 @@ use error::ApiGenericError;
 use error::ApiOversizeError;
 use error::ApiMalformationError;
 
+pub use api::ApiVersion;
+
 pub use auth::Credentials;
 
 pub use error::InfluxError;
@@ -126,15 +179,36 @@ pub use error::InfluxError;
 pub use value::Value;
 
 pub use client::Client;
+pub use client::Replay;
 
 pub use record::Record;
 
+pub use query::Table;
+pub use query::Column;
+
+pub use management::Bucket;
+pub use management::Health;
+pub use management::Organization;
+pub use management::RetentionRule;
+
 pub use builder::ClientBuilder;
 
 pub use precision::Precision;
 
 pub use backlogging::Backlog;
 pub use backlogging::FileBacklog;
+pub use backlogging::WalBacklog;
 pub use backlogging::NoopBacklog;
+pub use backlogging::Dropped;
+pub use backlogging::Overflow;
 
 pub use measurement::Measurement;
+
+pub use retry::RetryPolicy;
+pub use retry::NoRetry;
+pub use retry::ExponentialBackoff;
+
+#[cfg(feature = "async")] pub use asynchronous::AsyncClient;
+#[cfg(feature = "async")] pub use asynchronous::AsyncClientBuilder;
+#[cfg(feature = "async")] pub use asynchronous::AsyncBacklog;
+#[cfg(feature = "async")] pub use asynchronous::BlockingBacklog;
@@ -1,80 +1,358 @@
 //!
-//! Snippet Archive for Line Protocol Formatting/Parsing
+//! Line Protocol Formatting and Parsing
 //!
+use crate::Value;
+use crate::Precision;
+use crate::Measurement;
 
+use crate::InfluxError;
+use crate::InfluxResult;
 
-// pub fn from_line(line: &str) -> InfluxResult<Self>
-// {
-//     let parts = line.split(" ")
-//         .collect::<Vec<&str>>();
 
-//     if parts.len() != 3 {
-//         return Err(format!("Invalid measurement line: '{}'. Please consult the InfluxDB line protocol documentation", line).into())
-//     }
 
-//     let part_msrmt  = parse_measurement(parts[0])?;
-//     let part_fields = parse_fields(parts[1])?;
-//     let part_tstamp = parts[2];
+/// Escape a measurement name, where commas and spaces would otherwise end it.
+pub(crate) fn escape_measurement(name: &str) -> InfluxResult<String>
+{
+    single_line(name)?;
+    Ok(escape(name, &[',', ' ']))
+}
 
-//     let mut this = Self::new(part_msrmt.0);
 
-//     this.tags      = part_msrmt.1;
-//     this.fields    = part_fields;
-//     this.timestamp = Some(part_tstamp);
+/// Escape a tag key, tag value or field key. Equal signs separate these from their values.
+pub(crate) fn escape_key(key: &str) -> InfluxResult<String>
+{
+    single_line(key)?;
+    Ok(escape(key, &[',', '=', ' ']))
+}
 
-//     Ok(this)
-// }
 
+/// Format a field value. Integers carry an `i` suffix to keep them distinct from floats, strings are quoted.
+/// The server has no way to store NaN or infinity, so those are refused rather than written as a line it rejects.
+pub(crate) fn format_value(value: &Value) -> InfluxResult<String>
+{
+    match value
+    {
+        Value::Integer(v)                  => { Ok(format!("{}i", v)) }
+        Value::Float(v) if v.is_finite()   => { Ok(v.to_string()) }
+        Value::Float(v)                    => { Err(format!("Field value {} is not a finite number", v).into()) }
+        Value::Boolean(v)                  => { Ok(v.to_string()) }
+        Value::String(v)                   => { Ok(format!("\"{}\"", escape(v, &['\\', '"']))) }
+    }
+}
 
-// fn parse_measurement<'m>(fragment: &'m str) -> InfluxResult<(&'m str, BTreeMap<String, String>)>
-// {
-//     let mut tags  = BTreeMap::new();
-//     let mut parts = fragment.split(",");
 
-//     if let Some(msrmt) = parts.next()
-//     {
-//         while let Some(tag) = parts.next()
-//         {
-//             let tag_parts = tag.split("=")
-//                 .collect::<Vec<&str>>();
+/// Line breaks end a line whether escaped or not, so names and tag values cannot carry them.
+fn single_line(s: &str) -> InfluxResult<()>
+{
+    if s.contains(&['\n', '\r'][..]) {
+        return Err(format!("Line break in '{}'", s.escape_debug()).into());
+    }
 
-//             if tag_parts.len() != 2 {
-//                 return Err("All tags must have a key=value format".into());
-//             }
+    Ok(())
+}
 
-//             let key   = tag_parts[0].to_owned();
-//             let value = tag_parts[1].to_owned();
 
-//             tags.insert(key, value);
-//         }
+fn escape(s: &str, special: &[char]) -> String
+{
+    let mut escaped = String::with_capacity(s.len());
 
-//         Ok((msrmt, tags))
-//     }
-//     else {
-//         Err("Measurement is missing in line".into())
-//     }
-// }
+    for c in s.chars()
+    {
+        if special.contains(&c) {
+            escaped.push('\\');
+        }
 
+        escaped.push(c);
+    }
 
-// fn parse_fields(fragment: &str) -> InfluxResult<BTreeMap<String, String>>
-// {
-//     let mut fields = BTreeMap::new();
-//     let mut parts  = fragment.split(",");
+    escaped
+}
 
-//     while let Some(field) = parts.next()
-//     {
-//         let field_parts = field.split("=")
-//             .collect::<Vec<&str>>();
 
-//         if field_parts.len() != 2 {
-//             return Err("All fields must have a key=value format".into());
-//         }
 
-//         let key   = field_parts[0].to_owned();
-//         let value = field_parts[1].to_owned();
+/// Parse one line of line protocol. A missing timestamp means "now", as it does for the server.
+pub(crate) fn parse_line(line: &str, precision: &Precision) -> InfluxResult<Measurement>
+{
+    let (head, rest) = split_once_unescaped(line.trim(), b' ', false)
+        .ok_or_else::<InfluxError, _>(|| format!("Missing field set in line: '{}'", line).into())?;
 
-//         fields.insert(key, value);
-//     }
+    let (fieldset, stamp) = match split_once_unescaped(rest.trim_start(), b' ', true)
+    {
+        Some((fieldset, stamp)) => { (fieldset, Some(stamp.trim())) }
+        None                    => { (rest.trim_start(), None) }
+    };
 
-//     Ok(fields)
-// }
+    let mut parts = split_unescaped(head, b',', false).into_iter();
+
+    let name = parts.next()
+        .filter(|name| ! name.is_empty())
+        .ok_or_else::<InfluxError, _>(|| format!("Missing measurement name in line: '{}'", line).into())?;
+
+    let mut msrmt = Measurement::new(&unescape(name, &[',', ' ']));
+
+    for tag in parts
+    {
+        let (key, value) = split_once_unescaped(tag, b'=', false)
+            .ok_or_else::<InfluxError, _>(|| format!("Tag '{}' is not in key=value format in line: '{}'", tag, line).into())?;
+
+        msrmt.tag(&unescape(key, &[',', '=', ' ']), &unescape(value, &[',', '=', ' ']));
+    }
+
+    for field in split_unescaped(fieldset, b',', true)
+    {
+        let (key, value) = split_once_unescaped(field, b'=', false)
+            .ok_or_else::<InfluxError, _>(|| format!("Field '{}' is not in key=value format in line: '{}'", field, line).into())?;
+
+        msrmt.field(&unescape(key, &[',', '=', ' ']), parse_value(value)?);
+    }
+
+    if msrmt.fields.is_empty() {
+        return Err(format!("Missing field set in line: '{}'", line).into());
+    }
+
+    if let Some(stamp) = stamp.filter(|s| ! s.is_empty())
+    {
+        let stamp = stamp.parse::<i64>()
+            .map_err(|e| InfluxError::Error(format!("Invalid timestamp '{}' in line '{}': {}", stamp, line, e)))?;
+
+        msrmt.timestamp(precision.datetime(stamp)?);
+    }
+
+    Ok(msrmt)
+}
+
+
+fn parse_value(value: &str) -> InfluxResult<Value>
+{
+    let invalid = |e: &dyn std::fmt::Display| InfluxError::Error(format!("Invalid field value '{}': {}", value, e));
+
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        return Ok(Value::String(unescape(&value[1 .. value.len() - 1], &['\\', '"'])));
+    }
+
+    match value
+    {
+        "t" | "T" | "true"  | "True"  | "TRUE"  => { Ok(Value::Boolean(true)) }
+        "f" | "F" | "false" | "False" | "FALSE" => { Ok(Value::Boolean(false)) }
+
+        v if v.ends_with('i') || v.ends_with('u') => {
+            v[.. v.len() - 1].parse::<i64>()
+                .map(Value::Integer)
+                .map_err(|e| invalid(&e))
+        }
+
+        v => {
+            match v.parse::<f64>()
+            {
+                Ok(v) if v.is_finite() => { Ok(Value::Float(v)) }
+                Ok(_)                  => { Err(invalid(&"not a finite number")) }
+                Err(e)                 => { Err(invalid(&e)) }
+            }
+        }
+    }
+}
+
+
+/// Byte offsets of separators that are neither backslash escaped nor, if asked, inside a quoted string.
+fn separators(s: &str, sep: u8, quoted: bool) -> Vec<usize>
+{
+    let mut offsets  = Vec::new();
+    let mut escaped  = false;
+    let mut in_quote = false;
+
+    for (i, b) in s.bytes().enumerate()
+    {
+        if escaped {
+            escaped = false;
+        }
+        else if b == b'\\' {
+            escaped = true;
+        }
+        else if quoted && b == b'"' {
+            in_quote = ! in_quote;
+        }
+        else if b == sep && ! in_quote {
+            offsets.push(i);
+        }
+    }
+
+    offsets
+}
+
+
+fn split_unescaped(s: &str, sep: u8, quoted: bool) -> Vec<&str>
+{
+    let mut parts = Vec::new();
+    let mut start = 0;
+
+    for i in separators(s, sep, quoted)
+    {
+        parts.push(&s[start .. i]);
+        start = i + 1;
+    }
+
+    parts.push(&s[start ..]);
+    parts
+}
+
+
+fn split_once_unescaped(s: &str, sep: u8, quoted: bool) -> Option<(&str, &str)>
+{
+    separators(s, sep, quoted)
+        .first()
+        .map(|i| (&s[.. *i], &s[*i + 1 ..]))
+}
+
+
+fn unescape(s: &str, special: &[char]) -> String
+{
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars     = s.chars().peekable();
+
+    while let Some(c) = chars.next()
+    {
+        match chars.peek()
+        {
+            Some(next) if c == '\\' && special.contains(next) => {
+                unescaped.push(*next);
+                chars.next();
+            }
+
+            _ => { unescaped.push(c); }
+        }
+    }
+
+    unescaped
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use chrono::Utc;
+    use chrono::TimeZone;
+
+    #[test]
+    fn measurement_names_escape_commas_and_spaces()
+    {
+        assert_eq!(escape_measurement("moisture").unwrap(),        "moisture");
+        assert_eq!(escape_measurement("soil moisture,1").unwrap(), "soil\\ moisture\\,1");
+        assert_eq!(escape_measurement("a=b").unwrap(),             "a=b");
+    }
+
+    #[test]
+    fn keys_escape_commas_equals_and_spaces()
+    {
+        assert_eq!(escape_key("Tomato bed 1").unwrap(), "Tomato\\ bed\\ 1");
+        assert_eq!(escape_key("beds,east=1").unwrap(),  "beds\\,east\\=1");
+        assert_eq!(escape_key("C:\\temp").unwrap(),     "C:\\temp");
+    }
+
+    #[test]
+    fn values_are_typed()
+    {
+        assert_eq!(format_value(&Value::Integer(-42)).unwrap(), "-42i");
+        assert_eq!(format_value(&Value::Float(2.5)).unwrap(),   "2.5");
+        assert_eq!(format_value(&Value::Boolean(true)).unwrap(), "true");
+        assert_eq!(format_value(&Value::String("say \"hi\" \\ bye".to_owned())).unwrap(), "\"say \\\"hi\\\" \\\\ bye\"");
+    }
+
+    #[test]
+    fn non_finite_floats_and_line_breaks_are_refused()
+    {
+        assert!(format_value(&Value::Float(f64::NAN)).is_err());
+        assert!(format_value(&Value::Float(f64::NEG_INFINITY)).is_err());
+        assert!(escape_key("bed\n1").is_err());
+        assert!(escape_measurement("soil\r\nmoisture").is_err());
+    }
+
+    #[test]
+    fn escaped_identifiers_and_values_round_trip()
+    {
+        let mut msrmt = Measurement::new("soil moisture,1");
+
+        msrmt.tag("zone=east", "Tomato bed, 1")
+            .field("raw value", 612i64)
+            .field("note", "say \"hi\" \\ bye".to_owned())
+            .timestamp(Utc.timestamp_opt(1590969600, 0).unwrap());
+
+        let parsed = parse_line(&msrmt.to_line(&Precision::Seconds).unwrap(), &Precision::Seconds).unwrap();
+
+        assert_eq!(parsed.name, msrmt.name);
+        assert_eq!(parsed.tags, msrmt.tags);
+        assert_eq!(parsed.timestamp, msrmt.timestamp);
+        assert!(matches!(parsed.fields["raw value"], Value::Integer(612)));
+        assert!(matches!(parsed.fields["note"], Value::String(ref v) if v == "say \"hi\" \\ bye"));
+    }
+
+    #[test]
+    fn parses_escaped_identifiers_and_typed_values()
+    {
+        let msrmt = parse_line(
+            r#"soil\ moisture,name=Tomato\ bed\ 1,zone=east\,west\=1 count=3i,level=0.5,wet=f,note="dry, \"ish\" \\ ok" 1590969600"#,
+            &Precision::Seconds
+        ).unwrap();
+
+        assert_eq!(msrmt.name, "soil moisture");
+        assert_eq!(msrmt.tags["name"], "Tomato bed 1");
+        assert_eq!(msrmt.tags["zone"], "east,west=1");
+        assert!(matches!(msrmt.fields["count"], Value::Integer(3)));
+        assert!(matches!(msrmt.fields["level"], Value::Float(v) if (v - 0.5).abs() < f64::EPSILON));
+        assert!(matches!(msrmt.fields["wet"],   Value::Boolean(false)));
+        assert!(matches!(msrmt.fields["note"],  Value::String(ref v) if v == r#"dry, "ish" \ ok"#));
+        assert_eq!(msrmt.timestamp, Utc.timestamp_opt(1590969600, 0).unwrap());
+    }
+
+    #[test]
+    fn timestamps_follow_precision_and_are_optional()
+    {
+        let stamp = Utc.timestamp_opt(1590969600, 123_456_789).unwrap();
+
+        let ns = parse_line("m v=1 1590969600123456789", &Precision::Nanoseconds).unwrap();
+        let us = parse_line("m v=1 1590969600123456",    &Precision::Microseconds).unwrap();
+        let ms = parse_line("m v=1 1590969600123",       &Precision::Milliseconds).unwrap();
+        let s  = parse_line("m v=1 -1",                  &Precision::Seconds).unwrap();
+
+        assert_eq!(ns.timestamp, stamp);
+        assert_eq!(us.timestamp, Utc.timestamp_opt(1590969600, 123_456_000).unwrap());
+        assert_eq!(ms.timestamp, Utc.timestamp_opt(1590969600, 123_000_000).unwrap());
+        assert_eq!(s.timestamp,  Utc.timestamp_opt(-1, 0).unwrap());
+
+        let before = Utc::now();
+        let now    = parse_line("m,t=1 v=1", &Precision::Seconds).unwrap();
+        assert!(now.timestamp >= before);
+    }
+
+    #[test]
+    fn malformed_lines_are_rejected()
+    {
+        for line in &["", "m", "m,t v=1", "m v", "m v=1 soon", "m v=abc", ",t=1 v=1", "m v=1,", "m v=NaN", "m v=inf"] {
+            assert!(parse_line(line, &Precision::Seconds).is_err(), "accepted '{}'", line);
+        }
+    }
+
+    #[test]
+    fn lines_round_trip()
+    {
+        let mut msrmt = Measurement::new("weather, outside");
+
+        msrmt.tag("site=a", "Tomato bed 1")
+            .field("temperature", 21.5)
+            .field("count", -7i64)
+            .field("ok", true)
+            .field("note", "a \"quoted\", \\ spaced = string".to_owned())
+            .timestamp(Utc.timestamp_opt(1590969600, 987_000_000).unwrap());
+
+        for precision in &[Precision::Nanoseconds, Precision::Microseconds, Precision::Milliseconds, Precision::Seconds]
+        {
+            let line   = msrmt.to_line(precision).unwrap();
+            let parsed = parse_line(&line, precision).unwrap();
+
+            assert_eq!(parsed.to_line(precision).unwrap(), line);
+            assert_eq!(parsed.name, msrmt.name);
+            assert_eq!(parsed.tags, msrmt.tags);
+        }
+    }
+}
@@ -0,0 +1,319 @@
+//!
+//! Bucket and Organization Management, Health and Readiness
+//!
+//! Enough of the InfluxDB 2.x management API to set up a bucket from code rather than from the InfluxDB UI. Health
+//! and readiness checks also work against InfluxDB 1.x.
+//!
+use crate::Client;
+use crate::ApiVersion;
+
+use crate::InfluxError;
+use crate::InfluxResult;
+
+use crate::ApiGenericError;
+
+use crate::json;
+
+use crate::ReqwMethod;
+use crate::ReqwResponse;
+
+use serde::de::DeserializeOwned;
+
+use std::time::Duration;
+
+
+/// Number of buckets requested per page when listing them.
+const PAGE_SIZE: usize = 100;
+
+
+/// An organization, the owner of buckets in InfluxDB 2.x.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Organization
+{
+    /// Identifier to create buckets with.
+    pub id: String,
+
+    /// Name that records and queries refer to.
+    pub name: String,
+}
+
+
+/// A bucket and its retention.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Bucket
+{
+    /// Identifier of the bucket.
+    pub id: String,
+
+    /// Identifier of the owning organization.
+    #[serde(rename="orgID")]
+    pub org_id: String,
+
+    /// Name that records and queries refer to.
+    pub name: String,
+
+    /// How long data is kept. No rules keeps it forever.
+    #[serde(rename="retentionRules", default)]
+    pub retention_rules: Vec<RetentionRule>,
+}
+
+
+/// Expiry of the data in a bucket.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionRule
+{
+    #[serde(rename="type")]
+    kind: String,
+
+    #[serde(rename="everySeconds")]
+    every: u64,
+
+    #[serde(rename="shardGroupDurationSeconds", default, skip_serializing_if="Option::is_none")]
+    shard_group: Option<u64>,
+}
+
+
+/// Outcome of the health check of the server.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Health
+{
+    /// `pass` or `fail`.
+    pub status: String,
+
+    /// Explanation of the status.
+    #[serde(default)]
+    pub message: Option<String>,
+
+    /// Version of InfluxDB.
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+
+#[derive(Deserialize)]
+struct Organizations
+{
+    #[serde(default)]
+    orgs: Vec<Organization>,
+}
+
+
+#[derive(Deserialize)]
+struct Buckets
+{
+    #[serde(default)]
+    buckets: Vec<Bucket>,
+}
+
+
+impl RetentionRule
+{
+    /// Expire data older than the given duration, in whole seconds.
+    pub fn expire(after: Duration) -> Self
+    {
+        Self {kind: "expire".to_owned(), every: after.as_secs(), shard_group: None}
+    }
+
+    /// Span of time each shard group covers. Chosen by the server from the retention if not given.
+    pub fn shard_group_duration(mut self, duration: Duration) -> Self
+    {
+        self.shard_group = Some(duration.as_secs()); self
+    }
+
+    /// Age after which data expires. Zero means never.
+    pub fn every(&self) -> Duration
+    {
+        Duration::from_secs(self.every)
+    }
+}
+
+
+impl Health
+{
+    /// Whether the server reported itself as healthy.
+    pub fn is_pass(&self) -> bool
+    {
+        self.status == "pass"
+    }
+}
+
+
+impl Client
+{
+    /// Check the health of the server. An unhealthy server answers with a [Health](struct.Health.html) too, rather
+    /// than an error.
+    pub fn health(&self) -> InfluxResult<Health>
+    {
+        let reply = self.request(ReqwMethod::GET, "/health")?.send()?;
+
+        match reply.status().as_u16()
+        {
+            200 | 503 => { Ok(reply.json::<Health>()?) }
+            status    => { Err(manage_error(status, ApiGenericError::from_reply(reply))) }
+        }
+    }
+
+    /// Whether the server has started up and accepts writes and queries. Against InfluxDB 1.x this pings it.
+    pub fn ready(&self) -> InfluxResult<bool>
+    {
+        let path = match self.version()
+        {
+            ApiVersion::V1 => { "/ping" }
+            ApiVersion::V2 => { "/ready" }
+        };
+
+        let reply = self.request(ReqwMethod::GET, path)?.send()?;
+
+        Ok(reply.status().is_success())
+    }
+
+    /// List the organizations visible with the credentials.
+    pub fn organizations(&self) -> InfluxResult<Vec<Organization>>
+    {
+        let reply = self.request(ReqwMethod::GET, "/api/v2/orgs")?.send()?;
+
+        Ok(manage_reply::<Organizations>(reply, 200)?.orgs)
+    }
+
+    /// Look up the identifier of the named organization, if it exists.
+    pub fn organization_id(&self, org: &str) -> InfluxResult<Option<String>>
+    {
+        let reply = self.request(ReqwMethod::GET, "/api/v2/orgs")?
+            .query(&[("org", org)])
+            .send()?;
+
+        if reply.status().as_u16() == 404 {
+            return Ok(None);
+        }
+
+        let orgs = manage_reply::<Organizations>(reply, 200)?.orgs;
+
+        Ok(orgs.into_iter().find(|o| o.name == org).map(|o| o.id))
+    }
+
+    /// List all buckets of the named organization.
+    pub fn buckets(&self, org: &str) -> InfluxResult<Vec<Bucket>>
+    {
+        let mut buckets = Vec::new();
+
+        loop
+        {
+            let offset = buckets.len().to_string();
+            let limit  = PAGE_SIZE.to_string();
+
+            let reply = self.request(ReqwMethod::GET, "/api/v2/buckets")?
+                .query(&[("org", org), ("offset", &offset), ("limit", &limit)])
+                .send()?;
+
+            let page = manage_reply::<Buckets>(reply, 200)?.buckets;
+            let last = page.len() < PAGE_SIZE;
+
+            buckets.extend(page);
+
+            if last {
+                return Ok(buckets);
+            }
+        }
+    }
+
+    /// Look up a bucket of the named organization by its name, if it exists.
+    pub fn bucket(&self, org: &str, name: &str) -> InfluxResult<Option<Bucket>>
+    {
+        let reply = self.request(ReqwMethod::GET, "/api/v2/buckets")?
+            .query(&[("org", org), ("name", name)])
+            .send()?;
+
+        if reply.status().as_u16() == 404 {
+            return Ok(None);
+        }
+
+        let buckets = manage_reply::<Buckets>(reply, 200)?.buckets;
+
+        Ok(buckets.into_iter().find(|b| b.name == name))
+    }
+
+    /// Create a bucket in the organization of the given identifier. Without retention rules data is kept forever.
+    pub fn create_bucket(&self, org_id: &str, name: &str, rules: &[RetentionRule]) -> InfluxResult<Bucket>
+    {
+        let builder = self.request(ReqwMethod::POST, "/api/v2/buckets")?
+            .json(&bucket_body(org_id, name, rules));
+
+        debug!("Request: {:#?}", builder);
+
+        manage_reply::<Bucket>(builder.send()?, 201)
+    }
+}
+
+
+fn bucket_body(org_id: &str, name: &str, rules: &[RetentionRule]) -> json::Value
+{
+    json::json!({
+        "orgID":          org_id,
+        "name":           name,
+        "retentionRules": rules,
+    })
+}
+
+
+/// Decode the body of the expected status, or map any other onto an error.
+fn manage_reply<T: DeserializeOwned>(reply: ReqwResponse, expected: u16) -> InfluxResult<T>
+{
+    match reply.status().as_u16()
+    {
+        status if status == expected => { Ok(reply.json::<T>()?) }
+        status                       => { Err(manage_error(status, ApiGenericError::from_reply(reply))) }
+    }
+}
+
+
+fn manage_error(status: u16, error: ApiGenericError) -> InfluxError
+{
+    match status
+    {
+        400       => { InfluxError::ManageMalformed(error) }
+        401 | 403 => { InfluxError::ManageUnauthorized(error) }
+        409 | 422 => { InfluxError::ManageConflict(error) }
+        _         => { InfluxError::ManageUnknown(error) }
+    }
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn buckets_decode_and_encode_retention_rules()
+    {
+        let rules = [RetentionRule::expire(Duration::from_secs(30 * 86400)).shard_group_duration(Duration::from_secs(86400))];
+
+        assert_eq!(bucket_body("0261d8287f4d6000", "garden", &rules), json::json!({
+            "orgID": "0261d8287f4d6000",
+            "name":  "garden",
+            "retentionRules": [{"type": "expire", "everySeconds": 2592000, "shardGroupDurationSeconds": 86400}],
+        }));
+
+        let buckets = json::from_str::<Buckets>(r#"{"links":{"self":"/api/v2/buckets"},"buckets":[
+            {"id":"a1","orgID":"o1","name":"garden","type":"user","retentionRules":[{"type":"expire","everySeconds":3600}]},
+            {"id":"a2","orgID":"o1","name":"_monitoring","type":"system"}
+        ]}"#).unwrap().buckets;
+
+        assert_eq!(buckets[0].retention_rules, vec![RetentionRule::expire(Duration::from_secs(3600))]);
+        assert_eq!(buckets[0].retention_rules[0].every(), Duration::from_secs(3600));
+        assert!(buckets[1].retention_rules.is_empty());
+    }
+
+    #[test]
+    fn management_errors_by_status()
+    {
+        let error = || ApiGenericError::new("conflict".into(), "bucket with name garden already exists".into());
+
+        assert!(matches!(manage_error(422, error()), InfluxError::ManageConflict(_)));
+        assert!(matches!(manage_error(403, error()), InfluxError::ManageUnauthorized(_)));
+        assert!(matches!(manage_error(500, error()), InfluxError::ManageUnknown(_)));
+
+        let health = json::from_str::<Health>(r#"{"name":"influxdb","message":"ready for queries and writes","status":"pass","checks":[],"version":"v2.7.1"}"#);
+        assert!(health.unwrap().is_pass());
+    }
+}
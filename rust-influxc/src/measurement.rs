@@ -4,6 +4,13 @@
 use crate::Value;
 use crate::Precision;
 
+use crate::line::escape_key;
+use crate::line::parse_line;
+use crate::line::format_value;
+use crate::line::escape_measurement;
+
+use crate::InfluxResult;
+
 use crate::Utc;
 use crate::DateTime;
 
@@ -39,6 +46,12 @@ impl Measurement
         }
     }
 
+    /// Parse a Measurement from one line of line protocol, reading its timestamp at the given precision.
+    pub fn from_line(line: &str, precision: &Precision) -> InfluxResult<Self>
+    {
+        parse_line(line, precision)
+    }
+
     /// Set datetime of this Measurement
     pub fn timestamp(&mut self, timestamp: DateTime) -> &mut Self
     {
@@ -59,15 +72,15 @@ impl Measurement
         self
     }
 
-    pub(crate) fn to_line(&self, precision: &Precision) -> String
+    pub(crate) fn to_line(&self, precision: &Precision) -> InfluxResult<String>
     {
-        let mut line = self.name.to_owned();
+        let mut line = escape_measurement(&self.name)?;
 
         if ! self.tags.is_empty()
         {
             let tagline = self.tags.iter()
-                .map(|(k, v)| format!("{}={}", k, v))
-                .collect::<Vec<String>>()
+                .map(|(k, v)| Ok(format!("{}={}", escape_key(k)?, escape_key(v)?)))
+                .collect::<InfluxResult<Vec<String>>>()?
                 .join(",");
 
             line += ",";
@@ -77,8 +90,8 @@ impl Measurement
         if ! self.fields.is_empty()
         {
             let fieldline = self.fields.iter()
-                .map(|(k, v)| format!("{}={}", k, v))
-                .collect::<Vec<String>>()
+                .map(|(k, v)| Ok(format!("{}={}", escape_key(k)?, format_value(v)?)))
+                .collect::<InfluxResult<Vec<String>>>()?
                 .join(",");
 
             line += " ";
@@ -86,15 +99,37 @@ impl Measurement
         }
 
         line += " ";
+        line += &precision.timestamp(&self.timestamp)?.to_string();
 
-        match precision
-        {
-            Precision::Nanoseconds  => { line += &self.timestamp.timestamp_nanos().to_string();  }
-            Precision::Microseconds => { line += &(self.timestamp.timestamp_nanos() * 1000).to_string(); }
-            Precision::Milliseconds => { line += &self.timestamp.timestamp_millis().to_string(); }
-            Precision::Seconds      => { line += &(self.timestamp.timestamp() ).to_string(); }
-        }
+        Ok(line)
+    }
+}
 
-        line
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use chrono::TimeZone;
+    use chrono::Utc;
+
+    #[test]
+    fn line_escapes_names_and_types_values()
+    {
+        let mut msrmt = Measurement::new("soil moisture");
+
+        msrmt.tag("name", "Tomato bed 1")
+            .tag("zone", "east,west")
+            .field("raw value", 612i64)
+            .field("wet", false)
+            .field("note", "dry \"ish\"".to_owned())
+            .field("level", 0.5)
+            .timestamp(Utc.timestamp_opt(1590969600, 0).unwrap());
+
+        assert_eq!(
+            msrmt.to_line(&Precision::Seconds).unwrap(),
+            "soil\\ moisture,name=Tomato\\ bed\\ 1,zone=east\\,west level=0.5,note=\"dry \\\"ish\\\"\",raw\\ value=612i,wet=false 1590969600"
+        );
     }
 }
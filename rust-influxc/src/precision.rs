@@ -2,15 +2,22 @@
 //! Precision of the Measurement being Stored/Loaded
 //!
 use crate::InfluxError;
+use crate::InfluxResult;
+
+use chrono::Utc;
+use crate::DateTime;
+
+use chrono::TimeZone;
 
 use std::fmt;
 
 
 /// The time resolution the bucket is to keep its measurements
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum Precision
 {
     /// Self explanatory nanoseconds
+    #[default]
     Nanoseconds,
 
     /// Self explanatory microseconds
@@ -24,16 +31,57 @@ pub enum Precision
 }
 
 
+impl Precision
+{
+    /// Number of timestamp units in a second.
+    fn per_second(&self) -> i64
+    {
+        match self
+        {
+            Precision::Nanoseconds  => { 1_000_000_000 }
+            Precision::Microseconds => { 1_000_000 }
+            Precision::Milliseconds => { 1_000 }
+            Precision::Seconds      => { 1 }
+        }
+    }
+
+    /// Timestamp of the datetime in units of this precision since the epoch. Finer parts are cut off towards the past,
+    /// also before the epoch, so that the timestamp never lies after the datetime. Nanosecond timestamps only reach
+    /// from 1677 to 2262.
+    pub fn timestamp(&self, datetime: &DateTime) -> InfluxResult<i64>
+    {
+        let nanos_per_unit = 1_000_000_000 / self.per_second();
+
+        datetime.timestamp()
+            .checked_mul(self.per_second())
+            .and_then(|t| t.checked_add(i64::from(datetime.timestamp_subsec_nanos()) / nanos_per_unit))
+            .ok_or_else(|| format!("Datetime {} out of range at precision {}", datetime, self).into())
+    }
+
+    /// Datetime of a timestamp in units of this precision since the epoch.
+    pub fn datetime(&self, timestamp: i64) -> InfluxResult<DateTime>
+    {
+        let secs  = timestamp.div_euclid(self.per_second());
+        let nanos = timestamp.rem_euclid(self.per_second()) * (1_000_000_000 / self.per_second());
+
+        Utc.timestamp_opt(secs, nanos as u32)
+            .single()
+            .ok_or_else(|| format!("Timestamp {} out of range at precision {}", timestamp, self).into())
+    }
+}
+
+
 impl std::str::FromStr for Precision
 {
     type Err = InfluxError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err>
     {
+        // "n" and "u" are what InfluxDB 1.x calls them, and what backlog archives were named with before
         let p = match s
         {
-            "ns" => Precision::Nanoseconds,
-             "u" => Precision::Microseconds,
+            "ns" | "n" => Precision::Nanoseconds,
+            "us" | "u" => Precision::Microseconds,
             "ms" => Precision::Milliseconds,
              "s" => Precision::Seconds,
 
@@ -52,7 +100,7 @@ impl fmt::Display for Precision
         match self
         {
             Precision::Nanoseconds  => "ns".fmt(f),
-            Precision::Microseconds =>  "u".fmt(f),
+            Precision::Microseconds => "us".fmt(f),
             Precision::Milliseconds => "ms".fmt(f),
             Precision::Seconds      =>  "s".fmt(f),
         }
@@ -60,10 +108,49 @@ impl fmt::Display for Precision
 }
 
 
-impl Default for Precision
+
+#[cfg(test)]
+mod tests
 {
-    fn default() -> Self
+    use super::*;
+
+    const ALL: [Precision; 4] = [Precision::Nanoseconds, Precision::Microseconds, Precision::Milliseconds, Precision::Seconds];
+
+    #[test]
+    fn timestamps_at_every_precision()
+    {
+        let after  = Utc.timestamp_opt(1590969600, 123_456_789).unwrap();
+        let before = Utc.timestamp_opt(-1590969601, 876_543_211).unwrap();   // 0.123456789s before 1919-08-03
+
+        let stamps = ALL.iter().map(|p| (p.timestamp(&after).unwrap(), p.timestamp(&before).unwrap())).collect::<Vec<_>>();
+
+        assert_eq!(stamps, vec![
+            (1590969600123456789, -1590969600123456789),
+            (1590969600123456,    -1590969600123457),
+            (1590969600123,       -1590969600124),
+            (1590969600,          -1590969601),
+        ]);
+
+        for (precision, (after_stamp, before_stamp)) in ALL.iter().zip(stamps)
+        {
+            assert!(precision.datetime(after_stamp).unwrap()  <= after);
+            assert!(precision.datetime(before_stamp).unwrap() <= before);
+            assert_eq!(precision.timestamp(&precision.datetime(before_stamp).unwrap()).unwrap(), before_stamp);
+            assert_eq!(precision.to_string().parse::<Precision>().unwrap(), *precision);
+        }
+
+        assert_eq!(Precision::Milliseconds.datetime(-1).unwrap(), Utc.timestamp_opt(-1, 999_000_000).unwrap());
+    }
+
+    #[test]
+    fn nanoseconds_are_bounded()
     {
-        Self::Nanoseconds
+        let far = Utc.timestamp_opt(10413792000, 0).unwrap();    // 2300-01-01
+
+        assert!(Precision::Nanoseconds.timestamp(&far).is_err());
+        assert_eq!(Precision::Microseconds.timestamp(&far).unwrap(), 10413792000000000);
+
+        assert_eq!("u".parse::<Precision>().unwrap(), Precision::Microseconds);
+        assert_eq!(Precision::Microseconds.to_string(), "us");
     }
 }
@@ -0,0 +1,335 @@
+//!
+//! Flux Query Responses in Annotated CSV
+//!
+use crate::InfluxError;
+use crate::InfluxResult;
+
+use crate::json;
+
+use serde::de::DeserializeOwned;
+
+
+/// Description of a column in a [Table](struct.Table.html), as given by the annotations of the response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column
+{
+    /// Column label, e.g. `_time`, `_value` or a tag key.
+    pub name: String,
+
+    /// Flux data type, e.g. `long`, `double` or `dateTime:RFC3339`.
+    pub datatype: String,
+
+    /// Whether the column is part of the group key of the table.
+    pub group: bool,
+
+    /// Value to use where a row leaves this column empty.
+    pub default: String,
+}
+
+
+/// One table of a Flux query result. Rows hold the raw text of each cell; [decode](#method.decode) converts them
+/// to typed values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table
+{
+    pub(crate) columns: Vec<Column>,
+    pub(crate) rows:    Vec<Vec<String>>,
+}
+
+
+impl Table
+{
+    /// Columns of this table in response order.
+    pub fn columns(&self) -> &[Column]
+    {
+        &self.columns
+    }
+
+    /// Raw cell text of each row.
+    pub fn rows(&self) -> &[Vec<String>]
+    {
+        &self.rows
+    }
+
+    /// Raw cell text of the named column in the given row.
+    pub fn value(&self, row: usize, column: &str) -> Option<&str>
+    {
+        let index = self.columns.iter().position(|c| c.name == column)?;
+
+        self.rows.get(row)
+            .and_then(|r| r.get(index))
+            .map(|v| v.as_str())
+    }
+
+    /// Decode every row into a struct whose fields are named after the columns. Cells are typed according to the
+    /// column data type first, so a `long` column decodes into an integer field and a `dateTime:RFC3339` column
+    /// into a `chrono::DateTime`. Columns the struct does not name are ignored.
+    pub fn decode<T: DeserializeOwned>(&self) -> InfluxResult<Vec<T>>
+    {
+        self.rows.iter()
+            .map(|row| {
+                let object = self.columns.iter()
+                    .zip(row.iter())
+                    .map(|(column, cell)| (column.name.clone(), typed(column, cell)))
+                    .collect::<json::Map<String, json::Value>>();
+
+                json::from_value(json::Value::Object(object))
+                    .map_err(|e| InfluxError::Error(format!("Failed to decode query row {:?}: {}", row, e)))
+            })
+            .collect()
+    }
+}
+
+
+fn typed(column: &Column, cell: &str) -> json::Value
+{
+    let cell = if cell.is_empty() { column.default.as_str() } else { cell };
+
+    if cell.is_empty() && column.datatype != "string" {
+        return json::Value::Null;
+    }
+
+    let value = match column.datatype.as_str()
+    {
+        "long"         => { cell.parse::<i64>().ok().map(json::Value::from) }
+        "unsignedLong" => { cell.parse::<u64>().ok().map(json::Value::from) }
+        "double"       => { cell.parse::<f64>().ok().and_then(json::Number::from_f64).map(json::Value::Number) }
+        "boolean"      => { cell.parse::<bool>().ok().map(json::Value::from) }
+        _              => { None }
+    };
+
+    value.unwrap_or_else(|| json::Value::String(cell.to_owned()))
+}
+
+
+/// Request body of a Flux query asking for annotated CSV.
+pub(crate) fn query_body(flux: &str) -> json::Value
+{
+    json::json!({
+        "query":   flux,
+        "type":    "flux",
+        "dialect": {
+            "header":      true,
+            "delimiter":   ",",
+            "annotations": ["datatype", "group", "default"],
+        }
+    })
+}
+
+
+/// Split annotated CSV into the tables it contains. Each block of annotations and header row may carry several
+/// tables, told apart by their `table` column, and blocks are separated by blank lines.
+pub(crate) fn parse_tables(csv: &str) -> InfluxResult<Vec<Table>>
+{
+    let mut tables  = Vec::new();
+    let mut block   = Block::default();
+
+    for record in parse_csv(csv)?
+    {
+        if record.iter().all(|cell| cell.is_empty()) {
+            block = Block::default();
+            continue;
+        }
+
+        match record[0].as_str()
+        {
+            "#datatype" => { block.datatypes = record[1 ..].to_vec(); }
+            "#group"    => { block.groups    = record[1 ..].iter().map(|g| g == "true").collect(); }
+            "#default"  => { block.defaults  = record[1 ..].to_vec(); }
+
+            a if a.starts_with('#') => {}
+
+            _ => {
+                if block.header.is_none()
+                {
+                    if record.len() == 3 && record[1] == "error" {
+                        block.error = true;
+                    }
+
+                    block.header = Some(record[1 ..].to_vec());
+                }
+                else if block.error {
+                    return Err(format!("Query failed: {}", record[1 ..].join(": ")).into());
+                }
+                else {
+                    block.push(&mut tables, record[1 ..].to_vec());
+                }
+            }
+        }
+    }
+
+    Ok(tables)
+}
+
+
+#[derive(Default)]
+struct Block
+{
+    datatypes: Vec<String>,
+    groups:    Vec<bool>,
+    defaults:  Vec<String>,
+    header:    Option<Vec<String>>,
+    table:     Option<String>,
+    error:     bool,
+}
+
+
+impl Block
+{
+    fn push(&mut self, tables: &mut Vec<Table>, row: Vec<String>)
+    {
+        let header = self.header.as_ref().unwrap();
+        let id     = header.iter().position(|c| c == "table").and_then(|i| row.get(i)).cloned().unwrap_or_default();
+
+        if self.table.as_ref() != Some(&id)
+        {
+            let columns = header.iter()
+                .enumerate()
+                .map(|(i, name)| Column {
+                    name:     name.clone(),
+                    datatype: self.datatypes.get(i).cloned().unwrap_or_else(|| "string".to_owned()),
+                    group:    self.groups.get(i).cloned().unwrap_or(false),
+                    default:  self.defaults.get(i).cloned().unwrap_or_default(),
+                })
+                .collect();
+
+            tables.push(Table {columns, rows: Vec::new()});
+
+            self.table = Some(id);
+        }
+
+        tables.last_mut().unwrap().rows.push(row);
+    }
+}
+
+
+/// RFC 4180 CSV: quoted cells may contain commas, line breaks and doubled quotes. Blank lines come back as a record
+/// with a single empty cell.
+fn parse_csv(csv: &str) -> InfluxResult<Vec<Vec<String>>>
+{
+    let mut records  = Vec::new();
+    let mut record   = Vec::new();
+    let mut cell     = String::new();
+    let mut quoted   = false;
+    let mut chars    = csv.chars().peekable();
+
+    while let Some(c) = chars.next()
+    {
+        if quoted
+        {
+            match c
+            {
+                '"' if chars.peek() == Some(&'"') => { cell.push('"'); chars.next(); }
+                '"'                               => { quoted = false; }
+                _                                 => { cell.push(c); }
+            }
+        }
+        else
+        {
+            match c
+            {
+                '"'  => { quoted = true; }
+                ','  => { record.push(std::mem::take(&mut cell)); }
+                '\r' => {}
+                '\n' => {
+                    record.push(std::mem::take(&mut cell));
+                    records.push(std::mem::take(&mut record));
+                }
+                _    => { cell.push(c); }
+            }
+        }
+    }
+
+    if quoted {
+        return Err("Unterminated quoted cell in query response".into());
+    }
+
+    if ! cell.is_empty() || ! record.is_empty()
+    {
+        record.push(cell);
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::Utc;
+    use crate::DateTime;
+
+    const RESPONSE: &str = "\
+#datatype,string,long,dateTime:RFC3339,double,string,string\r\n\
+#group,false,false,false,false,true,true\r\n\
+#default,_result,,,,,\r\n\
+,result,table,_time,_value,_field,name\r\n\
+,,0,2020-06-01T11:12:00Z,612,value,tomato\r\n\
+,,0,2020-06-01T11:13:00Z,,value,tomato\r\n\
+,,1,2020-06-01T11:40:00Z,587.5,value,\"bed, \"\"east\"\"\"\r\n\
+\r\n\
+#datatype,string,long,boolean\r\n\
+#group,false,false,true\r\n\
+#default,_result,,\r\n\
+,result,table,state\r\n\
+,,2,true\r\n";
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Moisture
+    {
+        #[serde(rename="_time")]  time:  DateTime,
+        #[serde(rename="_value")] value: Option<f64>,
+        name: String,
+    }
+
+    #[test]
+    fn tables_are_split_by_table_column_and_blank_lines()
+    {
+        let tables = parse_tables(RESPONSE).unwrap();
+
+        assert_eq!(tables.len(), 3);
+        assert_eq!(tables[0].rows().len(), 2);
+        assert_eq!(tables[1].value(0, "name"), Some("bed, \"east\""));
+        assert_eq!(tables[1].value(0, "result"), Some(""));
+        assert_eq!(tables[2].columns()[2], Column {
+            name: "state".to_owned(), datatype: "boolean".to_owned(), group: true, default: "".to_owned()
+        });
+    }
+
+    #[test]
+    fn rows_decode_by_column_type()
+    {
+        let tables = parse_tables(RESPONSE).unwrap();
+        let rows   = tables[0].decode::<Moisture>().unwrap();
+
+        assert_eq!(rows, vec![
+            Moisture {time: "2020-06-01T11:12:00Z".parse::<DateTime>().unwrap(), value: Some(612.0), name: "tomato".to_owned()},
+            Moisture {time: "2020-06-01T11:13:00Z".parse::<DateTime>().unwrap(), value: None,        name: "tomato".to_owned()},
+        ]);
+
+        #[derive(Deserialize)]
+        struct State { result: String, state: bool }
+
+        let states = tables[2].decode::<State>().unwrap();
+        assert_eq!(states[0].result, "_result");
+        assert!(states[0].state);
+
+        assert!(tables[2].decode::<Moisture>().is_err());
+        assert!(Utc::now() > rows[0].time);
+    }
+
+    #[test]
+    fn empty_and_error_responses()
+    {
+        assert_eq!(parse_tables("").unwrap(), vec![]);
+        assert_eq!(parse_tables("\r\n").unwrap(), vec![]);
+
+        let error = parse_tables(",error,reference\r\n,\"bucket \"\"x\"\" not found\",\r\n").unwrap_err();
+        assert_eq!(error.to_string(), "Query failed: bucket \"x\" not found: ");
+
+        assert!(parse_tables(",result\r\n,\"open").is_err());
+    }
+}
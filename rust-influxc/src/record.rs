@@ -3,6 +3,7 @@
 //!
 use crate::Precision;
 use crate::Measurement;
+use crate::ApiVersion;
 
 use crate::InfluxResult;
 
@@ -11,6 +12,9 @@ use crate::FlateGzipBuilder;
 
 use crate::ReqwRequestBuilder;
 
+#[cfg(feature = "async")]
+use crate::ReqwAsyncRequestBuilder;
+
 use std::io::Write;
 
 
@@ -25,6 +29,9 @@ pub struct Record
     pub(crate) bucket:       String,
     pub(crate) precision:    Precision,
     pub(crate) measurements: Vec<Measurement>,
+
+    /// Sequence number of the backlog entry this record was read from, if any.
+    pub(crate) seq:          Option<u64>,
 }
 
 
@@ -37,7 +44,8 @@ impl Record
             org:          org.to_owned(),
             bucket:       bucket.to_owned(),
             precision:    Precision::default(),
-            measurements: Vec::new()
+            measurements: Vec::new(),
+            seq:          None,
         }
     }
 
@@ -47,6 +55,17 @@ impl Record
         self.precision = precision; self
     }
 
+    /// Add the measurements in a buffer of line protocol, such as a file exported from InfluxDB. Blank lines and `#`
+    /// comments are skipped, and timestamps are read at the precision of this record.
+    pub fn parse_lines(mut self, buffer: &str) -> InfluxResult<Self>
+    {
+        for line in buffer.lines().map(str::trim).filter(|l| ! l.is_empty() && ! l.starts_with('#')) {
+            self.measurements.push(Measurement::from_line(line, &self.precision)?);
+        }
+
+        Ok(self)
+    }
+
     /// Add and return a measurement for further parametrization.
     pub fn measurement<'r>(&'r mut self, name: &str) -> &'r mut Measurement
     {
@@ -58,44 +77,47 @@ impl Record
 
 impl Record
 {
-    pub(crate) fn to_lines(&self) -> Vec<String>
+    pub(crate) fn to_lines(&self) -> InfluxResult<Vec<String>>
     {
         let mut lines = Vec::new();
 
         for measurement in self.measurements.iter() {
-            lines.push(measurement.to_line(&self.precision));
+            lines.push(measurement.to_line(&self.precision)?);
         }
 
-        lines
+        Ok(lines)
+    }
+
+    pub(crate) fn to_line_buffer(&self) -> InfluxResult<String>
+    {
+        Ok(self.to_lines()?
+            .join("\n"))
+    }
+
+    pub(crate) fn to_write_request(&self, version: ApiVersion, builder: ReqwRequestBuilder) -> InfluxResult<ReqwRequestBuilder>
+    {
+        Ok(builder.header("Content-Encoding", "gzip")
+            .query(&version.write_query(self))
+            .body(self.to_write_body()?))
     }
 
-    pub(crate) fn to_line_buffer(&self) -> String
+    #[cfg(feature = "async")]
+    pub(crate) fn to_async_write_request(&self, version: ApiVersion, builder: ReqwAsyncRequestBuilder) -> InfluxResult<ReqwAsyncRequestBuilder>
     {
-        self.to_lines()
-            .join("\n")
+        Ok(builder.header("Content-Encoding", "gzip")
+            .query(&version.write_query(self))
+            .body(self.to_write_body()?))
     }
 
-    pub(crate) fn to_write_request(&self, mut builder: ReqwRequestBuilder) -> InfluxResult<ReqwRequestBuilder>
+    fn to_write_body(&self) -> InfluxResult<Vec<u8>>
     {
         // buffer compression
         let mut gzipenc = FlateGzipBuilder::new()
             .write(Vec::new(), FlateLevel::default());
 
-        gzipenc.write_all(self.to_line_buffer().as_bytes())?;
-
-        let buffer = gzipenc.finish()?;
-
-        // headers and query path
-        builder = builder.header("Content-Encoding", "gzip");
-
-        builder = builder.query(&[
-            ("org",       &self.org),
-            ("bucket",    &self.bucket),
-            ("precision", &self.precision.to_string()),
-        ]);
+        gzipenc.write_all(self.to_line_buffer()?.as_bytes())?;
 
-        // buffer body
-        Ok(builder.body(buffer))
+        Ok(gzipenc.finish()?)
     }
 }
 
@@ -0,0 +1,212 @@
+//!
+//! Retrying of Failed Writes
+//!
+use crate::InfluxError;
+
+use std::fmt::Debug;
+
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use std::sync::atomic::Ordering;
+use std::sync::atomic::AtomicU64;
+
+
+/// API definition of a policy deciding whether, and after how long, the [Client](struct.Client.html) attempts a
+/// failed write again. Applies to live writes as well as to replays of the backlog.
+pub trait RetryPolicy: Debug + Send + Sync
+{
+    /// Delay before attempting again after the given attempt, counting from 1, failed with `error`. `None` gives up
+    /// and has the error returned, or the record backlogged.
+    fn retry(&self, attempt: u32, error: &InfluxError) -> Option<Duration>;
+}
+
+
+/// Policy that never retries. This is the default.
+#[derive(Debug)]
+pub struct NoRetry;
+
+
+impl RetryPolicy for NoRetry
+{
+    fn retry(&self, _: u32, _: &InfluxError) -> Option<Duration>
+    {
+        None
+    }
+}
+
+
+/// Retry [transient](enum.InfluxError.html#method.is_transient) errors with exponentially growing delays, randomly
+/// shortened by up to the jitter fraction so that clients failing together do not retry together. A delay asked for
+/// by the server, in the response body or a `Retry-After` header, is waited for instead, unless it is longer than
+/// the [max_server_delay](#method.max_server_delay), in which case the policy gives up.
+#[derive(Debug)]
+pub struct ExponentialBackoff
+{
+    initial:      Duration,
+    max_delay:    Duration,
+    max_server:   Duration,
+    max_attempts: u32,
+    jitter:       f64,
+
+    state: AtomicU64,
+}
+
+
+impl ExponentialBackoff
+{
+    /// Up to 5 attempts, waiting 1s, 2s, 4s and 8s in between, with a jitter of 0.5, and at most 5 minutes for the
+    /// server.
+    pub fn new() -> Self
+    {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+
+        Self {
+            initial:      Duration::from_secs(1),
+            max_delay:    Duration::from_secs(60),
+            max_server:   Duration::from_secs(300),
+            max_attempts: 5,
+            jitter:       0.5,
+
+            state: AtomicU64::new(seed | 1),    // xorshift must not be seeded with zero
+        }
+    }
+
+    /// Delay after the first failed attempt. Doubles with each further one.
+    pub fn initial(mut self, delay: Duration) -> Self
+    {
+        self.initial = delay; self
+    }
+
+    /// Upper bound of the doubling delay. Delays asked for by the server are bounded by
+    /// [max_server_delay](#method.max_server_delay) instead.
+    pub fn max_delay(mut self, delay: Duration) -> Self
+    {
+        self.max_delay = delay; self
+    }
+
+    /// Longest delay asked for by the server that is waited for. Past it the policy gives up, so that a write does
+    /// not block for as long as a misbehaving server or proxy says.
+    pub fn max_server_delay(mut self, delay: Duration) -> Self
+    {
+        self.max_server = delay; self
+    }
+
+    /// Number of attempts, including the first, before giving up.
+    pub fn max_attempts(mut self, attempts: u32) -> Self
+    {
+        self.max_attempts = attempts; self
+    }
+
+    /// Fraction, between 0 and 1, of each delay that may randomly be cut off.
+    pub fn jitter(mut self, fraction: f64) -> Self
+    {
+        self.jitter = fraction.clamp(0.0, 1.0); self
+    }
+
+    /// Uniformly distributed in `[0, 1)`, by xorshift64
+    fn random(&self) -> f64
+    {
+        let mut x = self.state.load(Ordering::Relaxed);
+
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+
+        self.state.store(x, Ordering::Relaxed);
+
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+
+impl Default for ExponentialBackoff
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+
+impl RetryPolicy for ExponentialBackoff
+{
+    fn retry(&self, attempt: u32, error: &InfluxError) -> Option<Duration>
+    {
+        if attempt >= self.max_attempts || ! error.is_transient() {
+            return None;
+        }
+
+        if let Some(delay) = error.retry_after() {
+            return Some(delay).filter(|d| *d <= self.max_server);
+        }
+
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let delay  = self.initial.checked_mul(factor).unwrap_or(self.max_delay).min(self.max_delay);
+
+        Some(delay.mul_f64(1.0 - self.jitter * self.random()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::json;
+
+    fn unready(body: &str) -> InfluxError
+    {
+        InfluxError::WriteUnready(json::from_str(body).unwrap())
+    }
+
+    #[test]
+    fn backoff_doubles_within_bounds_and_gives_up()
+    {
+        let policy = ExponentialBackoff::new()
+            .initial(Duration::from_millis(100))
+            .max_delay(Duration::from_millis(300))
+            .max_attempts(4)
+            .jitter(0.5);
+
+        let error = InfluxError::Error("ignored".into());
+        assert_eq!(policy.retry(1, &error), None);
+
+        let error = unready("{\"delay\": 0}");
+
+        for _ in 0 .. 100
+        {
+            let delays = (1 ..= 4).map(|a| policy.retry(a, &error)).collect::<Vec<_>>();
+
+            assert!(delays[0] >= Some(Duration::from_millis(50))  && delays[0] <= Some(Duration::from_millis(100)));
+            assert!(delays[1] >= Some(Duration::from_millis(100)) && delays[1] <= Some(Duration::from_millis(200)));
+            assert!(delays[2] >= Some(Duration::from_millis(150)) && delays[2] <= Some(Duration::from_millis(300)));
+            assert_eq!(delays[3], None);
+        }
+    }
+
+    #[test]
+    fn server_delay_is_honoured()
+    {
+        let policy = ExponentialBackoff::new()
+            .max_delay(Duration::from_secs(10));
+
+        assert_eq!(policy.retry(1, &unready("{\"delay\": 30}")), Some(Duration::from_secs(30)));
+        assert_eq!(NoRetry.retry(1, &unready("{\"delay\": 30}")), None);
+    }
+
+    #[test]
+    fn server_delay_past_the_bound_gives_up()
+    {
+        let policy = ExponentialBackoff::new()
+            .max_server_delay(Duration::from_secs(60));
+
+        assert_eq!(policy.retry(1, &unready("{\"delay\": 60}")), Some(Duration::from_secs(60)));
+        assert_eq!(policy.retry(1, &unready("{\"delay\": 86400}")), None);
+        assert_eq!(ExponentialBackoff::new().retry(1, &unready("{\"delay\": 3600}")), None);
+    }
+}
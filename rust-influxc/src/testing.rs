@@ -0,0 +1,56 @@
+//!
+//! Test Fixtures
+//!
+use std::path::Path;
+use std::path::PathBuf;
+
+
+/// Scratch directory for a test, removed when dropped so that it is cleaned up even if the test fails.
+pub(crate) struct TempDir
+{
+    path: PathBuf,
+}
+
+
+impl TempDir
+{
+    /// Create an empty directory, named after the test and the process running it.
+    pub(crate) fn new(name: &str) -> Self
+    {
+        let path = std::env::temp_dir().join(format!("influxc-{}-{}", name, std::process::id()));
+        let _    = std::fs::remove_dir_all(&path);
+
+        std::fs::create_dir_all(&path).unwrap();
+
+        Self {path}
+    }
+}
+
+
+impl std::ops::Deref for TempDir
+{
+    type Target = Path;
+
+    fn deref(&self) -> &Path
+    {
+        &self.path
+    }
+}
+
+
+impl AsRef<Path> for TempDir
+{
+    fn as_ref(&self) -> &Path
+    {
+        &self.path
+    }
+}
+
+
+impl Drop for TempDir
+{
+    fn drop(&mut self)
+    {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
@@ -0,0 +1,130 @@
+use seed::prelude::*;
+use std::borrow::Cow;
+use common::auth::Session;
+
+const TOKEN_KEY: &str = "pirrigator-token";
+
+#[derive(Clone, Debug)]
+pub enum Model {
+    Checking,
+    LoggedOut { token: String, error: Option<String> },
+    LoggedIn(Session)
+}
+
+impl Default for Model {
+    fn default() -> Self { Model::Checking }
+}
+
+impl Model {
+    pub fn can_operate(&self) -> bool {
+        match self {
+            Model::LoggedIn(session) => session.can_operate(),
+            _ => false
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum Message {
+    Check,
+    TokenChanged(String),
+    LogIn,
+    LoggedIn(Session),
+    LoggedOut(Option<String>),
+    Failed(String),
+    LogOut
+}
+
+// The token the API was last logged in with, if any
+pub fn stored_token() -> Option<String> {
+    LocalStorage::get(TOKEN_KEY).ok()
+}
+
+// An API request carrying the stored token
+pub fn request<'a>(url: impl Into<Cow<'a, str>>) -> Request<'a> {
+    match stored_token() {
+        Some(token) => Request::new(url).header(Header::bearer(token)),
+        None => Request::new(url)
+    }
+}
+
+pub fn render(model: &Model) -> Node<Message> {
+    match model {
+        Model::Checking =>
+            p![attrs!{At::Class => "placeholder"}, "Checking token..."],
+
+        Model::LoggedOut { token, error } =>
+            div![
+                attrs!{At::Class => "login"},
+                h2!["Log In"],
+                input![
+                    attrs!{At::Type => "password", At::Placeholder => "API token", At::Value => token},
+                    input_ev(Ev::Input, Message::TokenChanged)
+                ],
+                button![simple_ev(Ev::Click, Message::LogIn), "Log In"],
+                error.as_ref().map_or(empty![], |e| p![e])
+            ],
+
+        Model::LoggedIn(session) =>
+            div![
+                attrs!{At::Class => "session"},
+                format!("{} ({})", session.name, if session.can_operate() { "operator" } else { "read only" }),
+                stored_token().map_or(empty![], |_| button![simple_ev(Ev::Click, Message::LogOut), "Log Out"])
+            ]
+    }
+}
+
+pub fn update(msg: Message, model: &mut Model, orders: &mut impl Orders<Message>) {
+    match msg {
+        Message::Check => {
+            orders.perform_cmd(fetch_session());
+            *model = Model::Checking;
+        }
+        Message::TokenChanged(new_token) => {
+            if let Model::LoggedOut { ref mut token, error: _ } = model {
+                *token = new_token;
+            }
+        }
+        Message::LogIn => {
+            if let Model::LoggedOut { ref token, error: _ } = model {
+                if LocalStorage::insert(TOKEN_KEY, token).is_ok() {
+                    orders.send_msg(Message::Check);
+                }
+            }
+        }
+        Message::LoggedIn(session) => {
+            *model = Model::LoggedIn(session);
+        }
+        Message::LoggedOut(error) => {
+            let _ = LocalStorage::remove(TOKEN_KEY);
+            *model = Model::LoggedOut { token: String::new(), error };
+        }
+        // The stored token is kept, as it may only be the app that is down
+        Message::Failed(e) => {
+            *model = Model::LoggedOut { token: stored_token().unwrap_or_default(), error: Some(e) };
+        }
+        Message::LogOut => {
+            orders.send_msg(Message::LoggedOut(None));
+        }
+    }
+}
+
+pub fn after_mount(orders: &mut impl Orders<Message>) {
+    orders.send_msg(Message::Check);
+}
+
+async fn fetch_session() -> Message {
+    match fetch(request("/api/session")).await.and_then(Response::check_status) {
+        Err(FetchError::StatusError(status)) if status.code == 401 =>
+            Message::LoggedOut(stored_token().map(|_| "That token isn't recognised".to_string())),
+
+        Err(e) =>
+            Message::Failed(format!("Failed to check token: {:?}", e)),
+
+        Ok(response) =>
+            response.json::<Session>().await.map_or_else(
+                |e| Message::Failed(format!("Failed to parse session: {:?}", e)),
+                Message::LoggedIn
+            )
+    }
+}
@@ -1,43 +1,102 @@
-#[macro_use] extern crate serde_derive;
 #[macro_use] extern crate seed;
 
+mod auth;
 mod chart;
 mod zones;
 mod weather;
 mod utils;
 
 use seed::prelude::*;
+use common::event::Event;
+use web_sys::{EventSource, MessageEvent};
 
 #[derive(Default, Debug)]
 struct Pirrigator {
+    auth: auth::Model,
     weather: weather::Model,
-    zones: zones::Model
+    zones: zones::Model,
+    events: Option<EventSource>
 }
 
 #[derive(Clone)]
 enum Message {
+    Auth(auth::Message),
     Weather(weather::Message),
     Zones(zones::Message),
+    Event(Event)
 }
 
 fn update(msg: Message, model: &mut Pirrigator, orders: &mut impl Orders<Message>) {
     match msg {
+        Message::Auth(msg) => {
+            let logged_in = matches!(msg, auth::Message::LoggedIn(_));
+            let logged_out = matches!(msg, auth::Message::LoggedOut(_) | auth::Message::Failed(_));
+            auth::update(msg, &mut model.auth, &mut orders.proxy(Message::Auth));
+            if logged_in {
+                load(model, orders);
+            } else if logged_out {
+                unload(model);
+            }
+        }
         Message::Weather(msg) => weather::update(msg, &mut model.weather, &mut orders.proxy(Message::Weather)),
-        Message::Zones(msg) => zones::update(msg, &mut model.zones, &mut orders.proxy(Message::Zones))
+        Message::Zones(msg) => zones::update(msg, &mut model.zones, &mut orders.proxy(Message::Zones)),
+        Message::Event(Event::WeatherEvent(w)) =>
+            weather::update(weather::Message::Received(w), &mut model.weather, &mut orders.proxy(Message::Weather)),
+        Message::Event(event) =>
+            zones::update(zones::Message::Received(event), &mut model.zones, &mut orders.proxy(Message::Zones))
     }
 }
 
+fn load(model: &mut Pirrigator, orders: &mut impl Orders<Message>) {
+    weather::after_mount(&mut orders.proxy(Message::Weather));
+    zones::after_mount(&mut orders.proxy(Message::Zones));
+    model.events = subscribe(orders);
+}
+
+fn unload(model: &mut Pirrigator) {
+    if let Some(events) = model.events.take() {
+        events.close();
+    }
+    model.weather = weather::Model::default();
+    model.zones = zones::Model::default();
+}
+
+// Events the app sends as they happen, so the charts fill in without polling.
+// An event stream can't carry headers, so the token goes in the query.
+fn subscribe(orders: &mut impl Orders<Message>) -> Option<EventSource> {
+    let url = match auth::stored_token() {
+        Some(token) => format!("/api/events?token={}", urlencoding::encode(&token)),
+        None => "/api/events".to_string()
+    };
+    let events = EventSource::new(&url).ok()?;
+    let (app, msg_mapper) = (orders.clone_app(), orders.msg_mapper());
+    let on_message = Closure::wrap(Box::new(move |e: MessageEvent| {
+        let event = e.data().as_string().and_then(|data| serde_json::from_str::<Event>(&data).ok());
+        if let Some(event) = event {
+            app.update(msg_mapper(Message::Event(event)));
+        }
+    }) as Box<dyn FnMut(MessageEvent)>);
+    events.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    on_message.forget();
+    Some(events)
+}
+
 fn view(model: &Pirrigator) -> Node<Message> {
     div![
         h1!["Pirrigator"],
-        weather::render(&model.weather).map_msg(Message::Weather),
-        zones::render(&model.zones).map_msg(Message::Zones)
+        auth::render(&model.auth).map_msg(Message::Auth),
+        match model.auth {
+            auth::Model::LoggedIn(_) => div![
+                weather::render(&model.weather).map_msg(Message::Weather),
+                zones::render(&model.zones, model.auth.can_operate()).map_msg(Message::Zones)
+            ],
+            _ => empty![]
+        }
     ]
 }
 
 fn after_mount(_: Url, orders: &mut impl Orders<Message>) -> AfterMount<Pirrigator> {
-    weather::after_mount(&mut orders.proxy(Message::Weather));
-    zones::after_mount(&mut orders.proxy(Message::Zones));
+    auth::after_mount(&mut orders.proxy(Message::Auth));
     AfterMount::default()
 }
 
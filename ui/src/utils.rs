@@ -1,6 +1,8 @@
 extern crate chrono;
 
 use chrono::prelude::*;
+use common::time::{COVERED_FROM_HEADER, UnixTime};
+use seed::prelude::*;
 use std::f64;
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -20,6 +22,22 @@ pub fn to_utc(system_time: &SystemTime) -> DateTime<Utc> {
     Utc.timestamp(unixtime.as_secs() as i64, unixtime.subsec_nanos())
 }
 
+// Set when the app no longer holds the start of the range asked for
+pub fn covered_from(response: &Response) -> Option<UnixTime> {
+    response.raw_response().headers().get(COVERED_FROM_HEADER).ok()??
+        .parse().ok()
+        .map(UnixTime::from_secs)
+}
+
+pub fn missing_before<Ms: 'static>(covered_from: Option<UnixTime>) -> Node<Ms> {
+    match covered_from {
+        Some(time) =>
+            p![attrs!{At::Class => "missing"}, format!("No readings kept before {}", time.date_time().format("%Y-%m-%d %H:%M UTC"))],
+        None =>
+            empty![]
+    }
+}
+
 pub trait FloatIterExt {
 	fn min_value(&mut self) -> f64;
 	fn max_value(&mut self) -> f64;
@@ -1,6 +1,8 @@
 use seed::prelude::*;
+use crate::auth;
 use crate::chart;
 use crate::utils::*;
+use common::time::{TimeRange, UnixTime};
 use common::weather::WeatherEvent;
 
 type WeatherData = Vec<WeatherEvent>;
@@ -9,7 +11,7 @@ type WeatherData = Vec<WeatherEvent>;
 pub enum Model {
     NotLoaded,
     Loading { duration: u32 },
-    Loaded { duration: u32, data: WeatherData },
+    Loaded { duration: u32, data: WeatherData, covered_from: Option<UnixTime> },
     Failed(String)
 }
 
@@ -21,7 +23,7 @@ impl Model {
     fn selected_duration(&self) -> u32 {
         match self {
             Model::Loading { duration } => *duration,
-            Model::Loaded { duration, data: _ } => *duration,
+            Model::Loaded { duration, .. } => *duration,
             _ => 0
         }
     }
@@ -30,7 +32,8 @@ impl Model {
 #[derive(Clone)]
 pub enum Message {
     Fetch { duration: u32 },
-    Fetched { duration: u32, data: WeatherData },
+    Fetched { duration: u32, data: WeatherData, covered_from: Option<UnixTime> },
+    Received(WeatherEvent),
     Failed(String)
 }
 
@@ -74,8 +77,9 @@ pub fn render(model: &Model) -> Node<Message> {
                 p![attrs!{At::Class => "placeholder"}, "Loading..."],
             Model::Failed(e) =>
                 p![attrs!{At::Class => "placeholder"}, e],
-            Model::Loaded { duration: _, data } => {
+            Model::Loaded { duration: _, data, covered_from } => {
                 div![attrs!{At::Class => "chart"},
+                    missing_before(*covered_from),
                     chart(data, "Temperature", Some(0.0), &|r| r.temperature)
                         .render()
                         .map_msg(|_| Message::Fetch { duration: DAY }),
@@ -97,13 +101,18 @@ pub fn update(msg: Message, model: &mut Model, orders: &mut impl Orders<Message>
             orders.perform_cmd(fetch_weather(duration));
             *model = Model::Loading { duration };
         }
-        Message::Fetched { duration, data } => {
+        Message::Fetched { duration, data, covered_from } => {
             *model = if data.is_empty() { 
                 Model::NotLoaded
             } else { 
-                Model::Loaded { duration, data } 
+                Model::Loaded { duration, data, covered_from } 
             };
         }
+        Message::Received(event) => {
+            if let Model::Loaded { ref mut data, .. } = model {
+                data.push(event);
+            }
+        }
 
         Message::Failed(e) => {
             *model = Model::Failed(e);
@@ -116,16 +125,18 @@ pub fn after_mount(orders: &mut impl Orders<Message>) {
 }
 
 async fn fetch_weather(duration: u32) -> Message {
-    let request = Request::new(format!("/api/weather/-{}/-0", duration));
+    let request = auth::request(format!("/api/weather/{}", TimeRange::last(duration).path()));
     match fetch(request).await {
         Err(e) =>
             Message::Failed(format!("Failed to fetch weather: {:?}", e)),
 
-        Ok(response) =>
+        Ok(response) => {
+            let covered_from = covered_from(&response);
             response.json::<Vec<WeatherEvent>>().await
                 .map_or_else(
                     |e| Message::Failed(format!("Failed to parse weather data: {:?}", e)),
-                    |data| Message::Fetched { duration, data }
+                    |data| Message::Fetched { duration, data, covered_from }
                 )
+        }
     }
 }
\ No newline at end of file
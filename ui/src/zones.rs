@@ -1,18 +1,17 @@
 extern crate urlencoding;
 
 use seed::prelude::*;
+use chrono::{DateTime, Utc};
 use std::collections::HashMap;
-use std::time::Duration;
 use crate::utils::*;
+use crate::auth;
 use crate::chart;
-use common::moisture::Measurement;
-use common::time::{TimeSeries, UnixTime};
-
-#[derive(Clone, Debug, Deserialize)]
-pub struct IrrigationRow {
-    start: UnixTime,
-    duration: Duration
-}
+use common::event::Event;
+use common::irrigation::{IrrigatedEvent, IrrigationRow, ValveActivity, ValveEvent};
+use common::moisture::{Measurement, MoistureEvent, ZoneMoisture};
+use common::time::{TimeRange, TimeSeries, UnixTime};
+use common::zone::ZoneSummary;
+use std::time::Duration;
 
 type ZoneMoistureData = HashMap<String, TimeSeries<Measurement>>;
 type ZoneIrrigationData = Vec<IrrigationRow>;
@@ -28,20 +27,24 @@ pub enum ZoneData {
 #[derive(Clone, Debug)]
 pub struct Zone {
     pub name: String,
+    pub valve: String,
     pub sensors: Vec<String>,
-    pub data: ZoneData
+    pub activity: ValveActivity,
+    pub data: ZoneData,
+    // Where the history kept by the app starts, if after the selected range
+    pub covered_from: Option<UnixTime>
 }
 
 #[derive(Clone, Debug)]
 pub enum Model {
     NotLoaded,
     Loading,
-    Loaded { zones: Vec<Zone> },
+    Loaded { zones: Vec<Zone>, ticking: bool },
     Failed(String)
 }
 
 type MoistureRow = (UnixTime, Measurement);
-type MoistureData = Vec<(String, TimeSeries<Measurement>)>;
+type MoistureData = ZoneMoisture;
 type IrrigationData = Vec<IrrigationRow>;
 
 impl Default for Model {
@@ -51,15 +54,25 @@ impl Default for Model {
 #[derive(Clone)]
 pub enum Message {
     FetchZones,
-    FetchedZones(Vec<String>),
+    FetchedZones(Vec<ZoneSummary>),
     FetchMoistureData { zone: String, duration: u32 },
-    FetchedMoistureData { zone: String, data: MoistureData, duration: u32 },
+    FetchedMoistureData { zone: String, data: MoistureData, duration: u32, covered_from: Option<UnixTime> },
     FetchIrrigationData { zone: String },
-    FetchedIrrigationData { zone: String, data: IrrigationData },
+    FetchedIrrigationData { zone: String, data: IrrigationData, covered_from: Option<UnixTime> },
+    FetchValves,
+    FetchedValves(Vec<ValveEvent>),
+    Received(Event),
     Failed(String),
-    Irrigate { zone: String }
+    Irrigate { zone: String },
+    IrrigateFor { zone: String, seconds: u32 },
+    Stop { zone: String },
+    StopAll,
+    Tick
 }
 
+// Times offered for irrigating a zone by hand, besides its configured time
+const MANUAL_IRRIGATION: [(u32, &str); 3] = [(60, "1 min"), (300, "5 min"), (900, "15 min")];
+
 impl From<&MoistureRow> for chart::DataPoint {
     fn from(m: &MoistureRow) -> Self {
         chart::DataPoint { time: m.0.system_time(), value: m.1 as f64 }
@@ -79,11 +92,44 @@ impl From<&IrrigationRow> for chart::Bar {
 }
 
 impl Zone {
-    fn new(name: &str) -> Self {
+    fn new(summary: &ZoneSummary) -> Self {
         Zone {
-            name: name.to_string(),
-            sensors: vec![],
-            data: ZoneData::NotLoaded
+            name: summary.name.clone(),
+            valve: summary.valve.clone(),
+            sensors: summary.sensors.clone(),
+            activity: ValveActivity::Closed,
+            data: ZoneData::NotLoaded,
+            covered_from: None
+        }
+    }
+
+    fn is_irrigating(&self) -> bool {
+        match self.activity {
+            ValveActivity::Open { .. } => true,
+            _ => false
+        }
+    }
+
+    fn add_moisture(&mut self, event: &MoistureEvent) {
+        if !self.sensors.contains(&event.name) {
+            return;
+        }
+        let moisture = match self.data {
+            ZoneData::LoadedMoisture { duration: _, ref mut moisture } => moisture,
+            ZoneData::LoadedAll { duration: _, ref mut moisture, irrigation: _ } => moisture,
+            _ => return
+        };
+        moisture.entry(event.name.clone()).or_default().push((UnixTime::from(event.time), event.value));
+    }
+
+    fn add_irrigation(&mut self, event: &IrrigatedEvent) {
+        if let ZoneData::LoadedAll { duration: _, moisture: _, ref mut irrigation } = self.data {
+            if self.valve == event.name {
+                irrigation.push(IrrigationRow {
+                    start: UnixTime::from(event.time),
+                    duration: Duration::from_secs(event.seconds as u64)
+                });
+            }
         }
     }
 
@@ -95,7 +141,7 @@ impl Zone {
         }
     }
 
-    fn render(&self) -> Node<Message> {
+    fn render(&self, can_operate: bool) -> Node<Message> {
         let buttons = vec![
             (HOURS_6, "Last 6 Hours"),
             (DAY, "Last Day"),
@@ -116,7 +162,7 @@ impl Zone {
                 ]
             ),
 
-            self.irrigate_button(),
+            self.valve_controls(can_operate),
 
             div![
                 match self.data {
@@ -129,13 +175,15 @@ impl Zone {
                     ZoneData::LoadedMoisture { duration: _, ref moisture } => 
                         div![
                             attrs!{At::Class => "chart"},
+                            missing_before(self.covered_from),
                             self.render_chart(&moisture, &vec![]),
-                            self.irrigate_button()
+                            self.irrigate_button(can_operate)
                         ],
 
                     ZoneData::LoadedAll { duration: _, ref moisture, ref irrigation } =>
                         div![
                             attrs!{At::Class => "chart"},
+                            missing_before(self.covered_from),
                             self.render_chart(&moisture, &irrigation)
                         ]
                 }
@@ -155,7 +203,46 @@ impl Zone {
         c.render().map_msg(|_| Message::FetchZones)
     }
 
-    fn irrigate_button(&self) -> Node<Message> {
+    // Whether the zone's valve is open, with the time left counting down.
+    // Only operators get the buttons.
+    fn valve_controls(&self, can_operate: bool) -> Node<Message> {
+        let stop = || if can_operate {
+            button![
+                attrs!{At::Class => "irrigate"},
+                simple_ev(Ev::Click, Message::Stop { zone: self.name.clone() }),
+                "Stop"
+            ]
+        } else {
+            empty![]
+        };
+        match self.activity {
+            ValveActivity::Open { until } =>
+                span![attrs!{At::Class => "irrigating"}, format!("Irrigating, {} left", remaining(&until)), stop()],
+
+            ValveActivity::Queued { seconds } =>
+                span![attrs!{At::Class => "irrigating"}, format!("Waiting to irrigate for {}", minutes(seconds as i64)), stop()],
+
+            ValveActivity::Closed if can_operate =>
+                span![
+                    self.irrigate_button(can_operate),
+                    MANUAL_IRRIGATION.iter().map(|(seconds, title)|
+                        button![
+                            simple_ev(Ev::Click, Message::IrrigateFor { zone: self.name.clone(), seconds: *seconds }),
+                            title
+                        ]
+                    )
+                ],
+
+            ValveActivity::Closed =>
+                empty![]
+        }
+    }
+
+    // Only operators may irrigate, so read-only users don't get the button
+    fn irrigate_button(&self, can_operate: bool) -> Node<Message> {
+        if !can_operate {
+            return empty![];
+        }
         button![
             attrs!{At::Class => "irrigate"},
             simple_ev(Ev::Click, Message::Irrigate { zone: self.name.clone() }),
@@ -164,17 +251,51 @@ impl Zone {
     }
 }
 
+fn minutes(seconds: i64) -> String {
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+fn remaining(until: &DateTime<Utc>) -> String {
+    let millis = until.timestamp_millis() as f64 - js_sys::Date::now();
+    minutes((millis / 1000.0).ceil().max(0.0) as i64)
+}
+
 impl Model {
     fn zone(&mut self, name: &str) -> &mut Zone {
-        if let Model::Loaded { ref mut zones } = self {
+        if let Model::Loaded { ref mut zones, ticking: _ } = self {
             zones.iter_mut().find(|ref z| z.name == name).unwrap()
         } else {
             panic!("unknown zone {}", name)
         }
     }
+
+    fn set_activity(&mut self, valve: &ValveEvent) {
+        if let Model::Loaded { ref mut zones, ticking: _ } = self {
+            zones.iter_mut()
+                .filter(|z| z.valve == valve.name)
+                .for_each(|z| z.activity = valve.activity.clone());
+        }
+    }
+
+    // Redraws every second while a valve is open, for the countdown
+    fn tick(&mut self, orders: &mut impl Orders<Message>) {
+        if let Model::Loaded { ref zones, ref mut ticking } = self {
+            *ticking = zones.iter().any(Zone::is_irrigating);
+            if *ticking {
+                orders.perform_cmd(cmds::timeout(1000, || Message::Tick));
+            }
+        }
+    }
+
+    fn is_ticking(&self) -> bool {
+        match self {
+            Model::Loaded { zones: _, ticking } => *ticking,
+            _ => false
+        }
+    }
 }
 
-pub fn render(model: &Model) -> Node<Message> {
+pub fn render(model: &Model, can_operate: bool) -> Node<Message> {
     div![
         h2!["Zones"],
         match model {
@@ -195,10 +316,15 @@ pub fn render(model: &Model) -> Node<Message> {
                     p![e],
                     button![simple_ev(Ev::Click, Message::FetchZones), "Try Again"]
                 ],
-            Model::Loaded { zones } => {
-                let els: Vec<Node<Message>> = zones.iter().map(|z| z.render()).collect();
+            Model::Loaded { zones, ticking: _ } => {
+                let els: Vec<Node<Message>> = zones.iter().map(|z| z.render(can_operate)).collect();
                 div![
                     attrs!{At::Class => "zones"},
+                    if can_operate && zones.iter().any(|z| z.activity != ValveActivity::Closed) {
+                        button![attrs!{At::Class => "irrigate"}, simple_ev(Ev::Click, Message::StopAll), "Stop All"]
+                    } else {
+                        empty![]
+                    },
                     els
                 ]
             }
@@ -213,17 +339,28 @@ pub fn update(msg: Message, model: &mut Model, orders: &mut impl Orders<Message>
             *model = Model::Loading;
         }   
         Message::FetchedZones(zones) => {
-            *model = Model::Loaded { zones: zones.iter().map(|name| Zone::new(name)).collect() };
+            *model = Model::Loaded { zones: zones.iter().map(Zone::new).collect(), ticking: false };
             for zone in zones.iter() {
-                orders.send_msg(Message::FetchMoistureData { zone: zone.clone(), duration: HOURS_6 });
+                orders.send_msg(Message::FetchMoistureData { zone: zone.name.clone(), duration: HOURS_6 });
+            }
+            orders.send_msg(Message::FetchValves);
+        }
+        Message::FetchValves => {
+            orders.perform_cmd(fetch_valves());
+        }
+        Message::FetchedValves(valves) => {
+            valves.iter().for_each(|valve| model.set_activity(valve));
+            if !model.is_ticking() {
+                model.tick(orders);
             }
         }
         Message::FetchMoistureData { ref zone, duration } => {
             orders.perform_cmd(fetch_moisture_data(zone.clone(), duration));
             model.zone(&zone).data = ZoneData::Loading;
         }
-        Message::FetchedMoistureData { zone: name, data, duration } => {
+        Message::FetchedMoistureData { zone: name, data, duration, covered_from } => {
             let zone = model.zone(&name);
+            zone.covered_from = covered_from;
             let mut moisture = HashMap::new();
             for (sensor_name, data) in data {
                 moisture.insert(sensor_name, data);
@@ -235,18 +372,50 @@ pub fn update(msg: Message, model: &mut Model, orders: &mut impl Orders<Message>
         Message::FetchIrrigationData { zone } => {
             orders.perform_cmd(fetch_irrigation_data(zone.clone(), model.zone(&zone).duration()));
         }
-        Message::FetchedIrrigationData { zone: name, data } => {
+        Message::FetchedIrrigationData { zone: name, data, covered_from } => {
             let zone = model.zone(&name);
+            zone.covered_from = zone.covered_from.max(covered_from);
             if let ZoneData::LoadedMoisture { duration, ref moisture } = zone.data {
                 zone.data = ZoneData::LoadedAll { duration, moisture: moisture.clone(), irrigation: data }
             }
         }
+        Message::Received(Event::ValveEvent(valve)) => {
+            model.set_activity(&valve);
+            if !model.is_ticking() {
+                model.tick(orders);
+            }
+        }
+        Message::Received(event) => {
+            if let Model::Loaded { ref mut zones, ticking: _ } = model {
+                for zone in zones.iter_mut() {
+                    match event {
+                        Event::MoistureEvent(ref m) => zone.add_moisture(m),
+                        Event::IrrigatedEvent(ref i) => zone.add_irrigation(i),
+                        _ => {}
+                    }
+                }
+            }
+        }
         Message::Failed(e) => {
             *model = Model::Failed(e);
         },
         Message::Irrigate { ref zone } => {
             orders.perform_cmd(irrigate_zone(zone.clone()));
         }
+        Message::IrrigateFor { zone, seconds } => {
+            let path = format!("/api/zone/{}/irrigate/{}", urlencoding::encode(&zone), seconds);
+            orders.perform_cmd(post(path, Message::FetchValves));
+        }
+        Message::Stop { zone } => {
+            let path = format!("/api/zone/{}/stop", urlencoding::encode(&zone));
+            orders.perform_cmd(post(path, Message::FetchValves));
+        }
+        Message::StopAll => {
+            orders.perform_cmd(post("/api/valves/stop".to_string(), Message::FetchValves));
+        }
+        Message::Tick => {
+            model.tick(orders);
+        }
     }
 }
 
@@ -255,13 +424,13 @@ pub fn after_mount(orders: &mut impl Orders<Message>) {
 }
 
 async fn fetch_zones() -> Message {
-    let request = Request::new("/api/zone/list");
+    let request = auth::request("/api/zone/list");
     match fetch(request).await {
         Err(e) =>
             Message::Failed(format!("Failed to fetch zone list: {:?}", e)),
 
         Ok(response) =>
-            response.json::<Vec<String>>().await.map_or_else(
+            response.json::<Vec<ZoneSummary>>().await.map_or_else(
                 |e| Message::Failed(format!("Failed to parse zone list: {:?}", e)),
                 Message::FetchedZones
             )
@@ -269,35 +438,62 @@ async fn fetch_zones() -> Message {
 }
 
 async fn fetch_moisture_data(name: String, duration: u32) -> Message {
-    let request = Request::new(format!("/api/zone/{}/moisture/-{}/-0", urlencoding::encode(&name), duration));
+    let request = auth::request(format!("/api/zone/{}/moisture/{}", urlencoding::encode(&name), TimeRange::last(duration).path()));
     match fetch(request).await {
         Err(e) => 
             Message::Failed(format!("Failed to fetch zone data: {:?}", e)),
 
-        Ok(response) =>
+        Ok(response) => {
+            let covered_from = covered_from(&response);
             response.json::<MoistureData>().await.map_or_else(
                 |e| Message::Failed(format!("Failed to parse zone data: {:?}", e)),
-                |data| Message::FetchedMoistureData { zone: name, data, duration }
+                |data| Message::FetchedMoistureData { zone: name, data, duration, covered_from }
             )
+        }
     }
 }
 
 async fn fetch_irrigation_data(name: String, duration: u32) -> Message {
-    let request = Request::new(format!("/api/zone/{}/irrigation/-{}/-0", urlencoding::encode(&name), duration));
+    let request = auth::request(format!("/api/zone/{}/irrigation/{}", urlencoding::encode(&name), TimeRange::last(duration).path()));
     match fetch(request).await {
         Err(e) =>
             Message::Failed(format!("Failed to fetch irrigation data {:?}", e)),
 
-        Ok(response) =>
+        Ok(response) => {
+            let covered_from = covered_from(&response);
             response.json::<IrrigationData>().await.map_or_else(
                 |e| Message::Failed(format!("Failed to parse irrigation data: {:?}", e)),
-                |data| Message::FetchedIrrigationData { zone: name, data }
+                |data| Message::FetchedIrrigationData { zone: name, data, covered_from }
             )
+        }
+    }
+}
+
+async fn fetch_valves() -> Message {
+    match fetch(auth::request("/api/valves")).await {
+        Err(e) =>
+            Message::Failed(format!("Failed to fetch valves: {:?}", e)),
+
+        Ok(response) =>
+            response.json::<Vec<ValveEvent>>().await.map_or_else(
+                |e| Message::Failed(format!("Failed to parse valves: {:?}", e)),
+                Message::FetchedValves
+            )
+    }
+}
+
+async fn post(path: String, then: Message) -> Message {
+    match fetch(auth::request(path).method(Method::Post)).await.and_then(Response::check_status) {
+        Err(e) =>
+            Message::Failed(format!("Failed to send valve command {:?}", e)),
+
+        Ok(_) =>
+            then
     }
 }
 
 async fn irrigate_zone(name: String) -> Message {
-    let request = Request::new(format!("/api/zone/{}/irrigate", urlencoding::encode(&name)))
+    let request = auth::request(format!("/api/zone/{}/irrigate", urlencoding::encode(&name)))
         .method(Method::Post);
     match fetch(request).await {
         Err(e) =>